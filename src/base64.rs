@@ -0,0 +1,79 @@
+//! Base64 as used by YAML's `!!binary` type, which is the encoding of RFC 2045
+//! with its standard alphabet and padding.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Longest line of encoded output, as in MIME.
+const LINE_LENGTH: usize = 76;
+
+/// Encodes `bytes` as lines of at most 76 characters, each ending in a
+/// newline. Empty input gives an empty string.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4 + bytes.len() / 57 + 1);
+    let mut line = 0;
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+        line += 4;
+        if line == LINE_LENGTH {
+            encoded.push('\n');
+            line = 0;
+        }
+    }
+    if line != 0 {
+        encoded.push('\n');
+    }
+    encoded
+}
+
+/// Decodes base64 text, ignoring whitespace. Returns `None` if the text
+/// contains any other character outside the alphabet, or is not a whole
+/// number of padded groups of 4 characters.
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut len = 0;
+    let mut padding = 0;
+    let mut finished = false;
+    for byte in text.bytes() {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' if len >= 2 => {
+                padding += 1;
+                0
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return None,
+        };
+        // Padding ends the text, and only padding may follow padding.
+        if finished || padding != 0 && byte != b'=' {
+            return None;
+        }
+        group = group << 6 | u32::from(sextet);
+        len += 1;
+        if len == 4 {
+            decoded.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+            finished = padding != 0;
+            group = 0;
+            len = 0;
+        }
+    }
+    if len == 0 {
+        Some(decoded)
+    } else {
+        None
+    }
+}
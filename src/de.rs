@@ -1,18 +1,28 @@
-use crate::error::{self, Error, ErrorImpl};
+use crate::base64;
+use crate::encoding::Encoding;
+use crate::error::{self, Error, ErrorImpl, Errors, Location};
 use crate::libyaml::error::Mark;
-use crate::libyaml::parser::{Scalar, ScalarStyle};
+use crate::libyaml::parser::{MappingStart, Scalar, ScalarStyle, SequenceStart, Span};
 use crate::libyaml::tag::Tag;
 use crate::loader::{Document, Loader};
-use crate::path::Path;
+use crate::mapping;
+use crate::number::Number;
+use crate::path::{Path, PathBuf};
+use crate::schema::{Resolved, Schema};
+use crate::timestamp;
+use crate::value::{self, anchored};
 use serde::de::{
-    self, value::BorrowedStrDeserializer, Deserialize, DeserializeOwned, DeserializeSeed, Expected,
-    IgnoredAny as Ignore, IntoDeserializer, Unexpected, Visitor,
+    self, value::BorrowedStrDeserializer, value::MapDeserializer, value::SeqDeserializer,
+    Deserialize, DeserializeOwned, DeserializeSeed, Expected, IgnoredAny as Ignore,
+    IntoDeserializer, Unexpected, Visitor,
 };
-use std::fmt;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
 use std::io;
+use std::iter;
 use std::marker::PhantomData;
 use std::mem;
-use std::num::ParseIntError;
 use std::str;
 use std::sync::Arc;
 
@@ -58,6 +68,273 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 /// ```
 pub struct Deserializer<'de> {
     progress: Progress<'de>,
+    options: DeserializerOptions,
+}
+
+/// Limits on the work a [`Deserializer`] is willing to do for its input.
+///
+/// The defaults are suitable for trusted input. When deserializing YAML from
+/// an untrusted source, tighten them to bound the memory and time spent on
+/// deeply nested documents, documents that expand aliases exponentially ("a
+/// billion laughs"), very large documents, or streams of many documents. Each
+/// limit fails with its own error, reported at the location in the input
+/// where it was exceeded.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_yaml::{Deserializer, DeserializerOptions, Value};
+///
+/// let options = DeserializerOptions::new()
+///     .max_depth(2)
+///     .max_alias_expansions(100)
+///     .max_document_bytes(4096)
+///     .max_documents(1);
+///
+/// let de = Deserializer::from_str("a: [1, 2]").with_options(options);
+/// assert!(Value::deserialize(de).is_ok());
+///
+/// let de = Deserializer::from_str("a: [1, [2]]").with_options(options);
+/// let err = Value::deserialize(de).unwrap_err();
+/// assert_eq!(err.to_string(), "recursion limit exceeded at line 1 column 8");
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DeserializerOptions {
+    pub(crate) max_depth: usize,
+    pub(crate) max_alias_expansions: Option<usize>,
+    pub(crate) max_document_bytes: Option<usize>,
+    pub(crate) max_documents: Option<usize>,
+    pub(crate) deny_duplicate_keys: bool,
+    pub(crate) schema: Schema,
+    pub(crate) encoding: Option<Encoding>,
+    pub(crate) preserve_anchors: bool,
+}
+
+impl DeserializerOptions {
+    /// The default options: nesting up to 128 levels deep, up to 100 alias
+    /// expansions per event in the document, no limit on the size or number
+    /// of documents, duplicate mapping keys rejected only when deserializing
+    /// into [`Value`](crate::Value) or [`Mapping`](crate::Mapping),
+    /// [`Schema::Compat`], and the input encoding detected from its first
+    /// bytes.
+    pub fn new() -> Self {
+        DeserializerOptions {
+            max_depth: 128,
+            max_alias_expansions: None,
+            max_document_bytes: None,
+            max_documents: None,
+            deny_duplicate_keys: false,
+            schema: Schema::Compat,
+            encoding: None,
+            preserve_anchors: false,
+        }
+    }
+
+    /// Maximum nesting depth of sequences and mappings, including mappings
+    /// pulled in through `<<` merge keys.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Maximum number of times an alias may be expanded while deserializing
+    /// one document. Every `*alias` encountered counts once, including those
+    /// inside the nodes that other aliases expand to, and so does every alias
+    /// merged through a `<<` merge key. Going over the limit is reported at
+    /// the alias whose expansion started it.
+    ///
+    /// Unless set, the limit is 100 times the number of events in the
+    /// document, where every scalar and alias counts as one event, and every
+    /// sequence and mapping as two.
+    pub fn max_alias_expansions(mut self, expansions: usize) -> Self {
+        self.max_alias_expansions = Some(expansions);
+        self
+    }
+
+    /// Maximum size of the source text of one document, in bytes.
+    ///
+    /// The limit is checked while parsing. Input from a reader is read into
+    /// memory in full before that, so bound the reader itself, for example
+    /// with [`Read::take`](std::io::Read::take), to limit how much is read.
+    pub fn max_document_bytes(mut self, bytes: usize) -> Self {
+        self.max_document_bytes = Some(bytes);
+        self
+    }
+
+    /// Maximum number of documents in the input.
+    pub fn max_documents(mut self, documents: usize) -> Self {
+        self.max_documents = Some(documents);
+        self
+    }
+
+    /// Whether to reject a mapping that contains the same key more than once,
+    /// whatever type it is deserialized into. The error names the key and
+    /// points at both of its occurrences.
+    ///
+    /// [`Value`](crate::Value) and [`Mapping`](crate::Mapping) always reject
+    /// duplicate keys. Other types, like `HashMap`, otherwise silently keep
+    /// the last entry.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_yaml::{Deserializer, DeserializerOptions};
+    /// use std::collections::HashMap;
+    ///
+    /// let yaml = "a: 1\nb: 2\nb: 3\n";
+    ///
+    /// let map = HashMap::<String, u8>::deserialize(Deserializer::from_str(yaml)).unwrap();
+    /// assert_eq!(map["b"], 3);
+    ///
+    /// let options = DeserializerOptions::new().deny_duplicate_keys(true);
+    /// let de = Deserializer::from_str(yaml).with_options(options);
+    /// let err = HashMap::<String, u8>::deserialize(de).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "b: duplicate key `b` at line 3 column 1, first defined at line 2 column 1",
+    /// );
+    /// ```
+    pub fn deny_duplicate_keys(mut self, deny: bool) -> Self {
+        self.deny_duplicate_keys = deny;
+        self
+    }
+
+    /// The rules that decide the type of plain scalars, like whether `yes` is
+    /// a boolean. See [`Schema`].
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// The character encoding of the input, instead of detecting it. A byte
+    /// order mark matching the encoding is skipped. Input given as a `&str`
+    /// is assumed to be UTF-8 unless an encoding is set here.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Whether to keep `&anchor` and `*alias` nodes in a
+    /// [`Value`](crate::Value), as [`Value::Anchored`](crate::Value::Anchored)
+    /// and [`Value::Alias`](crate::Value::Alias), instead of expanding every
+    /// alias to a copy of its anchored node. Serializing the value writes the
+    /// anchors and aliases back out.
+    ///
+    /// Only `Value` understands anchors; other types see them as maps with
+    /// private keys. `<<` merge keys are left unresolved, as ordinary entries
+    /// whose value is an alias. Use [`Value::expand_aliases`] and then
+    /// [`Value::apply_merge`] to resolve them.
+    ///
+    /// [`Value::expand_aliases`]: crate::Value::expand_aliases
+    /// [`Value::apply_merge`]: crate::Value::apply_merge
+    pub fn preserve_anchors(mut self, preserve: bool) -> Self {
+        self.preserve_anchors = preserve;
+        self
+    }
+
+    fn alias_budget(&self, document: &Document) -> usize {
+        self.max_alias_expansions
+            .unwrap_or_else(|| document.events.len().saturating_mul(100))
+    }
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        DeserializerOptions::new()
+    }
+}
+
+/// What is known about a document of a YAML stream besides its content.
+///
+/// Available from each [`Deserializer`] produced by iterating over a
+/// multi-document input.
+///
+/// ```
+/// use serde_yaml::Deserializer;
+///
+/// let input = "a: 1\n...\n%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\nb: 2\n";
+///
+/// let documents: Vec<_> = Deserializer::from_str(input).collect();
+/// let first = documents[0].document_info().unwrap();
+/// assert_eq!(first.index(), 0);
+/// assert_eq!(first.version(), None);
+/// assert!(!first.explicit_start() && first.explicit_end());
+///
+/// let second = documents[1].document_info().unwrap();
+/// assert_eq!(second.index(), 1);
+/// assert_eq!(second.version(), Some((1, 2)));
+/// assert_eq!(second.tag_directives()[0].0, "!e!");
+/// assert!(second.explicit_start() && !second.explicit_end());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentInfo {
+    pub(crate) index: usize,
+    pub(crate) version: Option<(u32, u32)>,
+    pub(crate) tags: Vec<(String, String)>,
+    pub(crate) explicit_start: bool,
+    pub(crate) explicit_end: bool,
+}
+
+impl DocumentInfo {
+    /// Position of the document in the stream, counting from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Major and minor version from the document's `%YAML` directive, if it
+    /// has one.
+    pub fn version(&self) -> Option<(u32, u32)> {
+        self.version
+    }
+
+    /// Handle and prefix of each of the document's `%TAG` directives, in the
+    /// order they were written.
+    pub fn tag_directives(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Whether the document starts with a `---` marker.
+    pub fn explicit_start(&self) -> bool {
+        self.explicit_start
+    }
+
+    /// Whether the document ends with a `...` marker.
+    pub fn explicit_end(&self) -> bool {
+        self.explicit_end
+    }
+}
+
+/// A mapping value that was skipped over during deserialization, typically
+/// because its key is not a field of the struct being deserialized.
+///
+/// Returned by [`Deserializer::deserialize_with_ignored`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IgnoredField {
+    path: PathBuf,
+    location: Location,
+}
+
+impl IgnoredField {
+    /// Path of the ignored value. Its last segment is the key that was not
+    /// recognized.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Location of the key of the ignored value.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+impl fmt::Display for IgnoredField {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ignored field `{}` at line {} column {}",
+            self.path,
+            self.location.line(),
+            self.location.column(),
+        )
+    }
 }
 
 pub(crate) enum Progress<'de> {
@@ -73,13 +350,15 @@ impl<'de> Deserializer<'de> {
     /// Creates a YAML deserializer from a `&str`.
     pub fn from_str(s: &'de str) -> Self {
         let progress = Progress::Str(s);
-        Deserializer { progress }
+        let options = DeserializerOptions::new();
+        Deserializer { progress, options }
     }
 
     /// Creates a YAML deserializer from a `&[u8]`.
     pub fn from_slice(v: &'de [u8]) -> Self {
         let progress = Progress::Slice(v);
-        Deserializer { progress }
+        let options = DeserializerOptions::new();
+        Deserializer { progress, options }
     }
 
     /// Creates a YAML deserializer from an `io::Read`.
@@ -92,37 +371,147 @@ impl<'de> Deserializer<'de> {
         R: io::Read + 'de,
     {
         let progress = Progress::Read(Box::new(rdr));
-        Deserializer { progress }
+        let options = DeserializerOptions::new();
+        Deserializer { progress, options }
+    }
+
+    /// Applies limits to the work done by this deserializer. When iterating
+    /// over a multi-document input, the limits carry over to every document.
+    pub fn with_options(mut self, options: DeserializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Metadata of the document this deserializer reads, if it was produced
+    /// by iterating over a multi-document input. Deserializers created
+    /// directly from the input return `None`, as their document has not
+    /// been parsed yet.
+    pub fn document_info(&self) -> Option<&DocumentInfo> {
+        match &self.progress {
+            Progress::Document(document) => Some(&document.info),
+            _ => None,
+        }
+    }
+
+    /// Deserializes the document, collecting every error instead of stopping
+    /// at the first one.
+    ///
+    /// When a value does not fit the type being deserialized, such as a
+    /// string where a number belongs or a number out of range, the error is
+    /// recorded, the value is skipped, and the `Deserialize` impl is handed a
+    /// placeholder, like zero or an empty string, in its place. The document
+    /// is deserialized once; the placeholders only let it carry on to the
+    /// errors further along. An error that the `Deserialize` impl raises
+    /// itself, such as an unknown or missing field, ends deserialization and
+    /// is reported after the errors found before it, as is a syntax error.
+    ///
+    /// ```
+    /// use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Container {
+    ///     name: String,
+    ///     replicas: u32,
+    ///     ports: Vec<u16>,
+    /// }
+    ///
+    /// let yaml = "name: web\nreplicas: many\nports: [80, 443, 100000]\n";
+    /// let de = Deserializer::from_str(yaml);
+    /// let errors = de.deserialize_recovering::<Container>().unwrap_err();
+    ///
+    /// let paths: Vec<_> = errors.iter().map(|err| err.path().unwrap().to_string()).collect();
+    /// assert_eq!(paths, ["replicas", "ports[2]"]);
+    /// assert_eq!(errors.iter().next().unwrap().location().unwrap().line(), 2);
+    /// ```
+    pub fn deserialize_recovering<T>(self) -> Result<T, Errors>
+    where
+        T: Deserialize<'de>,
+    {
+        let options = self.options;
+        match &self.progress {
+            Progress::Iterable(_) => return Err(Errors::from(error::more_than_one_document())),
+            Progress::Document(document) => {
+                return recover(document, &options, Some(document.info.index));
+            }
+            _ => {}
+        }
+
+        let mut loader = Loader::new(self.progress, options)?;
+        let document = loader.next_document().ok_or_else(error::end_of_stream)?;
+        let t = recover(&document, &options, None)?;
+        if loader.next_document().is_none() {
+            Ok(t)
+        } else {
+            Err(Errors::from(error::more_than_one_document()))
+        }
+    }
+
+    /// Deserializes the document, reporting every mapping value that the
+    /// `Deserialize` impl skipped over with
+    /// [`deserialize_ignored_any`](serde::Deserializer::deserialize_ignored_any),
+    /// such as the value of a struct field that does not exist.
+    ///
+    /// This catches typos in the names of fields, which are otherwise dropped
+    /// silently, without rejecting unknown fields outright as
+    /// `#[serde(deny_unknown_fields)]` does.
+    ///
+    /// ```
+    /// use serde_derive::Deserialize;
+    /// use serde_yaml::Deserializer;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Server {
+    ///     host: String,
+    ///     #[serde(default)]
+    ///     timeout: u64,
+    /// }
+    ///
+    /// let yaml = "host: example.com\ntimeuot: 30\n";
+    /// let de = Deserializer::from_str(yaml);
+    /// let (server, ignored) = de.deserialize_with_ignored::<Server>().unwrap();
+    /// assert_eq!(server.timeout, 0);
+    ///
+    /// assert_eq!(ignored.len(), 1);
+    /// assert_eq!(ignored[0].path().to_string(), "timeuot");
+    /// assert_eq!(ignored[0].location().line(), 2);
+    /// assert_eq!(ignored[0].to_string(), "ignored field `timeuot` at line 2 column 1");
+    /// ```
+    pub fn deserialize_with_ignored<T>(self) -> Result<(T, Vec<IgnoredField>)>
+    where
+        T: Deserialize<'de>,
+    {
+        let ignored = RefCell::new(Vec::new());
+        let t = self.de_with(Some(&ignored), |de| T::deserialize(de))?;
+        Ok((t, ignored.into_inner()))
     }
 
     fn de<T>(
         self,
         f: impl for<'document> FnOnce(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
     ) -> Result<T> {
+        self.de_with(None, f)
+    }
+
+    fn de_with<T>(
+        self,
+        ignored: Option<&RefCell<Vec<IgnoredField>>>,
+        f: impl for<'document> FnOnce(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
+    ) -> Result<T> {
+        let options = self.options;
         match &self.progress {
             Progress::Iterable(_) => return Err(error::more_than_one_document()),
             Progress::Document(document) => {
-                let mut pos = 0;
-                let t = f(&mut DeserializerFromEvents {
-                    document,
-                    pos: &mut pos,
-                    path: Path::Root,
-                    remaining_depth: 128,
-                })?;
+                let t = deserialize_document(document, &options, None, ignored, f)
+                    .map_err(|err| error::in_document(err, document.info.index))?;
                 return Ok(t);
             }
             _ => {}
         }
 
-        let mut loader = Loader::new(self.progress)?;
+        let mut loader = Loader::new(self.progress, options)?;
         let document = loader.next_document().ok_or_else(error::end_of_stream)?;
-        let mut pos = 0;
-        let t = f(&mut DeserializerFromEvents {
-            document: &document,
-            pos: &mut pos,
-            path: Path::Root,
-            remaining_depth: 128,
-        })?;
+        let t = deserialize_document(&document, &options, None, ignored, f)?;
         if loader.next_document().is_none() {
             Ok(t)
         } else {
@@ -131,6 +520,92 @@ impl<'de> Deserializer<'de> {
     }
 }
 
+fn deserialize_document<'de, T>(
+    document: &Document<'de>,
+    options: &DeserializerOptions,
+    recovery: Option<&Recovery>,
+    ignored: Option<&RefCell<Vec<IgnoredField>>>,
+    f: impl for<'document> FnOnce(&mut DeserializerFromEvents<'de, 'document>) -> Result<T>,
+) -> Result<T> {
+    let mut pos = 0;
+    let mut remaining_aliases = options.alias_budget(document);
+    f(&mut DeserializerFromEvents {
+        document,
+        pos: &mut pos,
+        path: Path::Root,
+        remaining_depth: options.max_depth,
+        remaining_aliases: &mut remaining_aliases,
+        expanding_alias: None,
+        options,
+        recovery,
+        ignored,
+        tagged_already: false,
+        pair_element: false,
+        key_pos: None,
+    })
+}
+
+/// Deserializes the node whose first event is at `pos`, for
+/// [`Document::get`](crate::Document::get). Paths in errors are relative to
+/// the node.
+pub(crate) fn from_node<'de, T>(document: &Document<'de>, pos: usize) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let options = DeserializerOptions::new();
+    let mut pos = pos;
+    let mut remaining_aliases = options.alias_budget(document);
+    T::deserialize(&mut DeserializerFromEvents {
+        document,
+        pos: &mut pos,
+        path: Path::Root,
+        remaining_depth: options.max_depth,
+        remaining_aliases: &mut remaining_aliases,
+        expanding_alias: None,
+        options: &options,
+        recovery: None,
+        ignored: None,
+        tagged_already: false,
+        pair_element: false,
+        key_pos: None,
+    })
+}
+
+// Deserializes the document in a single pass. A value that the deserializer
+// finds to be of the wrong type is recorded as an error and skipped, and the
+// visitor is handed a placeholder in its place so that the rest of the
+// document is still checked. An error raised by a visitor itself ends the
+// pass, since the failed visitor has no value to carry on with.
+fn recover<'de, T>(
+    document: &Document<'de>,
+    options: &DeserializerOptions,
+    index: Option<usize>,
+) -> Result<T, Errors>
+where
+    T: Deserialize<'de>,
+{
+    let recovery = Recovery::default();
+    let result = deserialize_document(document, options, Some(&recovery), None, |de| {
+        T::deserialize(de)
+    });
+    let placeholder_refused = recovery.placeholder_refused.get();
+    let mut errors = recovery.errors.into_inner();
+    match result {
+        Ok(t) if errors.is_empty() => return Ok(t),
+        Ok(_) => {}
+        // The node whose placeholder was refused is already reported.
+        Err(_) if placeholder_refused => {}
+        Err(err) => errors.push(err),
+    }
+    if let Some(index) = index {
+        errors = errors
+            .into_iter()
+            .map(|err| error::in_document(err, index))
+            .collect();
+    }
+    Err(Errors::new(errors))
+}
+
 impl<'de> Iterator for Deserializer<'de> {
     type Item = Self;
 
@@ -140,12 +615,14 @@ impl<'de> Iterator for Deserializer<'de> {
                 let document = loader.next_document()?;
                 return Some(Deserializer {
                     progress: Progress::Document(document),
+                    options: self.options,
                 });
             }
             Progress::Document(_) => return None,
             Progress::Fail(err) => {
                 return Some(Deserializer {
                     progress: Progress::Fail(Arc::clone(err)),
+                    options: self.options,
                 });
             }
             _ => {}
@@ -153,7 +630,7 @@ impl<'de> Iterator for Deserializer<'de> {
 
         let dummy = Progress::Str("");
         let input = mem::replace(&mut self.progress, dummy);
-        match Loader::new(input) {
+        match Loader::new(input, self.options) {
             Ok(loader) => {
                 self.progress = Progress::Iterable(loader);
                 self.next()
@@ -163,6 +640,7 @@ impl<'de> Iterator for Deserializer<'de> {
                 self.progress = Progress::Fail(Arc::clone(&fail));
                 Some(Deserializer {
                     progress: Progress::Fail(fail),
+                    options: self.options,
                 })
             }
         }
@@ -409,9 +887,9 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
 pub(crate) enum Event<'de> {
     Alias(usize),
     Scalar(Scalar<'de>),
-    SequenceStart,
+    SequenceStart(SequenceStart),
     SequenceEnd,
-    MappingStart,
+    MappingStart(MappingStart),
     MappingEnd,
 }
 
@@ -419,7 +897,40 @@ struct DeserializerFromEvents<'de, 'document> {
     document: &'document Document<'de>,
     pos: &'document mut usize,
     path: Path<'document>,
-    remaining_depth: u8,
+    remaining_depth: usize,
+    /// Number of alias expansions still allowed, shared by every deserializer
+    /// working on the document.
+    remaining_aliases: &'document mut usize,
+    /// Position of the alias whose node is being deserialized, the outermost
+    /// one if aliases are nested. Running out of alias expansions is reported
+    /// there, where the expansion started.
+    expanding_alias: Option<usize>,
+    options: &'document DeserializerOptions,
+    /// Present when deserializing with
+    /// [`Deserializer::deserialize_recovering`].
+    recovery: Option<&'document Recovery>,
+    /// Collects the ignored mapping values when deserializing with
+    /// [`Deserializer::deserialize_with_ignored`].
+    ignored: Option<&'document RefCell<Vec<IgnoredField>>>,
+    /// Whether the tag of the next node has already been consumed as the
+    /// variant of an enum, in which case the node is visited as if untagged.
+    tagged_already: bool,
+    /// Whether the node is an element of a `!!pairs` or `!!omap` sequence,
+    /// where a pair may be written as a mapping with a single entry.
+    pair_element: bool,
+    /// Position of the key when deserializing the value of a mapping entry,
+    /// which is reported if the value is ignored.
+    key_pos: Option<usize>,
+}
+
+/// What deserializing a document in recovery mode collects.
+#[derive(Default)]
+struct Recovery {
+    /// The errors that deserialization carried on past.
+    errors: RefCell<Vec<Error>>,
+    /// Whether a visitor refused the placeholder it was handed for a node
+    /// whose error is already recorded.
+    placeholder_refused: Cell<bool>,
 }
 
 impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
@@ -428,8 +939,12 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
     }
 
     fn peek_event_mark(&self) -> Result<(&'document Event<'de>, Mark)> {
-        match self.document.events.get(*self.pos) {
-            Some((event, mark)) => Ok((event, *mark)),
+        self.event_at(*self.pos)
+    }
+
+    fn event_at(&self, pos: usize) -> Result<(&'document Event<'de>, Mark)> {
+        match self.document.events.get(pos) {
+            Some((event, span)) => Ok((event, span.start)),
             None => Err(match &self.document.error {
                 Some(parse_error) => error::shared(Arc::clone(parse_error)),
                 None => error::end_of_stream(),
@@ -448,10 +963,14 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         })
     }
 
+    // Called just after consuming an alias event, to deserialize the node it
+    // refers to.
     fn jump<'anchor>(
-        &'anchor self,
+        &'anchor mut self,
         pos: &'anchor mut usize,
     ) -> Result<DeserializerFromEvents<'de, 'anchor>> {
+        let alias = *self.pos - 1;
+        self.charge_alias(alias)?;
         match self.document.aliases.get(pos) {
             Some(found) => {
                 *pos = *found;
@@ -460,12 +979,63 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
                     pos,
                     path: Path::Alias { parent: &self.path },
                     remaining_depth: self.remaining_depth,
+                    remaining_aliases: self.remaining_aliases,
+                    expanding_alias: Some(self.expanding_alias.unwrap_or(alias)),
+                    options: self.options,
+                    recovery: self.recovery,
+                    ignored: self.ignored,
+                    tagged_already: false,
+                    pair_element: self.pair_element,
+                    key_pos: None,
                 })
             }
             None => panic!("unresolved alias: {}", *pos),
         }
     }
 
+    /// Counts one expansion of the alias at `pos` against the limit.
+    fn charge_alias(&mut self, pos: usize) -> Result<()> {
+        *self.remaining_aliases = match self.remaining_aliases.checked_sub(1) {
+            Some(remaining) => remaining,
+            None => {
+                let (_alias, mark) = self.event_at(self.expanding_alias.unwrap_or(pos))?;
+                return Err(error::alias_expansion_limit_exceeded(mark));
+            }
+        };
+        Ok(())
+    }
+
+    /// Fails with `err`, raised for the node whose first event was just read,
+    /// unless deserializing in recovery mode and the error leaves the rest of
+    /// the input fit to deserialize. Then the error is recorded, the node is
+    /// skipped, and `placeholder` hands the visitor a stand-in value so that
+    /// deserialization carries on.
+    fn fail_or_recover<V, F>(
+        &mut self,
+        err: Error,
+        mark: Mark,
+        visitor: V,
+        placeholder: F,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        F: FnOnce(V) -> Result<V::Value>,
+    {
+        let recovery = match self.recovery {
+            Some(recovery) if err.is_recoverable() => recovery,
+            _ => return Err(err),
+        };
+        // A node cut short by a syntax error is reported as that error alone.
+        let err = error::fix_mark(err, mark, self.path);
+        *self.pos -= 1;
+        self.ignore_any()?;
+        recovery.errors.borrow_mut().push(err);
+        placeholder(visitor).map_err(|err| {
+            recovery.placeholder_refused.set(true);
+            err
+        })
+    }
+
     fn ignore_any(&mut self) -> Result<()> {
         enum Nest {
             Sequence,
@@ -477,10 +1047,10 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         loop {
             match self.next_event()? {
                 Event::Alias(_) | Event::Scalar(_) => {}
-                Event::SequenceStart => {
+                Event::SequenceStart(_) => {
                     stack.push(Nest::Sequence);
                 }
-                Event::MappingStart => {
+                Event::MappingStart(_) => {
                     stack.push(Nest::Mapping);
                 }
                 Event::SequenceEnd => match stack.pop() {
@@ -502,12 +1072,12 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         }
     }
 
-    fn visit_sequence<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
+    fn visit_sequence<V>(&mut self, visitor: V, mark: Mark, pairs: bool) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let (value, len) = self.recursion_check(mark, |de| {
-            let mut seq = SeqAccess { de, len: 0 };
+            let mut seq = SeqAccess { de, len: 0, pairs };
             let value = visitor.visit_seq(&mut seq)?;
             Ok((value, seq.len))
         })?;
@@ -515,23 +1085,125 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         Ok(value)
     }
 
-    fn visit_mapping<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
+    /// Visits the keys of a `!!set` as a sequence, ignoring their values.
+    fn visit_set<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let (value, len) = self.recursion_check(mark, |de| {
-            let mut map = MapAccess {
+            let mut set = SetAccess(MapAccess {
                 de,
                 len: 0,
                 key: None,
-            };
-            let value = visitor.visit_map(&mut map)?;
-            Ok((value, map.len))
+                key_pos: 0,
+                fields: Fields::default(),
+            });
+            let value = visitor.visit_seq(&mut set)?;
+            Ok((value, set.0.len))
         })?;
         self.end_mapping(len)?;
         Ok(value)
     }
 
+    /// Visits the key and value of a mapping with a single entry as a
+    /// sequence of two elements.
+    fn visit_pair<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.recursion_check(mark, |de| {
+            let mut pair = PairAccess {
+                map: MapAccess {
+                    de,
+                    len: 0,
+                    key: None,
+                    key_pos: 0,
+                    fields: Fields::default(),
+                },
+                value_next: false,
+            };
+            visitor.visit_seq(&mut pair)
+        })?;
+        self.end_mapping(1)?;
+        Ok(value)
+    }
+
+    /// Visits the mapping whose MappingStart was just read, refusing the
+    /// repeated keys that `duplicates` names. Every repeated key is refused if
+    /// the options deny them.
+    fn visit_mapping<V>(
+        &mut self,
+        visitor: V,
+        mark: Mark,
+        duplicates: Duplicates,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let start = *self.pos - 1;
+        let mut fields = Fields::default();
+        match duplicates {
+            Duplicates::Denied => self.check_duplicate_keys(start)?,
+            _ if self.options.deny_duplicate_keys => self.check_duplicate_keys(start)?,
+            Duplicates::Fields(names) => fields = Fields::new(names),
+            Duplicates::Allowed => {}
+        }
+        let merged = if self.options.preserve_anchors {
+            None
+        } else {
+            self.merged_entries(start, mark, self.remaining_depth)?
+        };
+        match merged {
+            Some(entries) => self.visit_merged_mapping(visitor, mark, start, entries, fields),
+            None => self
+                .recursion_check(mark, |de| {
+                    let mut map = MapAccess {
+                        de,
+                        len: 0,
+                        key: None,
+                        key_pos: 0,
+                        fields,
+                    };
+                    let value = visitor.visit_map(&mut map)?;
+                    Ok((value, map.len))
+                })
+                .and_then(|(value, len)| {
+                    self.end_mapping(len)?;
+                    Ok(value)
+                }),
+        }
+    }
+
+    /// Fails if two entries of the mapping whose MappingStart is at `start`
+    /// have the same scalar key. Keys pulled in by `<<` merge keys are allowed
+    /// to repeat the mapping's own keys, which override them.
+    fn check_duplicate_keys(&self, start: usize) -> Result<()> {
+        let mut seen = HashMap::new();
+        for (pos, _value) in self.mapping_entries(start)?.0 {
+            let identity = match self.key_identity(pos)? {
+                Some(identity) => identity,
+                None => continue,
+            };
+            if let Some(first) = seen.insert(identity, pos) {
+                return Err(self.duplicate_key(identity.0, first, pos));
+            }
+        }
+        Ok(())
+    }
+
+    /// The error for the key at `second`, which repeats `key` given first at
+    /// `first`. Both keys must have been read already.
+    fn duplicate_key(&self, key: &[u8], first: usize, second: usize) -> Error {
+        let key = String::from_utf8_lossy(key);
+        let path = Path::Map {
+            parent: &self.path,
+            key: &key,
+        };
+        let first = self.document.events[first].1.start;
+        let second = self.document.events[second].1.start;
+        error::duplicate_key(&key, first, second, path)
+    }
+
     fn visit_spanned<V>(&mut self, visitor: V, mark: Mark) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -549,7 +1221,11 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
 
     fn end_sequence(&mut self, len: usize) -> Result<()> {
         let total = {
-            let mut seq = SeqAccess { de: self, len };
+            let mut seq = SeqAccess {
+                de: self,
+                len,
+                pairs: false,
+            };
             while de::SeqAccess::next_element::<Ignore>(&mut seq)?.is_some() {}
             seq.len
         };
@@ -580,6 +1256,8 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
                 de: self,
                 len,
                 key: None,
+                key_pos: 0,
+                fields: Fields::default(),
             };
             while de::MapAccess::next_entry::<Ignore, Ignore>(&mut map)?.is_some() {}
             map.len
@@ -591,94 +1269,447 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         if total == len {
             Ok(())
         } else {
-            struct ExpectedMap(usize);
-            impl Expected for ExpectedMap {
-                fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                    if self.0 == 1 {
-                        write!(formatter, "map containing 1 entry")
-                    } else {
-                        write!(formatter, "map containing {} entries", self.0)
-                    }
-                }
-            }
             Err(de::Error::invalid_length(total, &ExpectedMap(len)))
         }
     }
 
-    fn recursion_check<F: FnOnce(&mut Self) -> Result<T>, T>(
-        &mut self,
-        mark: Mark,
-        f: F,
-    ) -> Result<T> {
-        let previous_depth = self.remaining_depth;
-        self.remaining_depth = match previous_depth.checked_sub(1) {
-            Some(depth) => depth,
-            None => return Err(error::recursion_limit_exceeded(mark)),
-        };
-        let result = f(self);
-        self.remaining_depth = previous_depth;
-        result
+    /// Returns the position just past the node that begins at `pos`.
+    fn node_end(&self, mut pos: usize) -> Result<usize> {
+        let mut depth = 0usize;
+        loop {
+            match self.event_at(pos)?.0 {
+                Event::Alias(_) | Event::Scalar(_) => {}
+                Event::SequenceStart(_) | Event::MappingStart(_) => depth += 1,
+                Event::SequenceEnd | Event::MappingEnd => depth -= 1,
+            }
+            pos += 1;
+            if depth == 0 {
+                return Ok(pos);
+            }
+        }
     }
-}
 
-struct SeqAccess<'de, 'document, 'seq> {
-    de: &'seq mut DeserializerFromEvents<'de, 'document>,
-    len: usize,
-}
+    /// Follows an alias to the position of the node it refers to.
+    fn resolve_alias(&self, pos: usize) -> Result<usize> {
+        match self.event_at(pos)?.0 {
+            Event::Alias(id) => match self.document.aliases.get(id) {
+                Some(found) => Ok(*found),
+                None => panic!("unresolved alias: {}", id),
+            },
+            _ => Ok(pos),
+        }
+    }
 
-impl<'de, 'document, 'seq> de::SeqAccess<'de> for SeqAccess<'de, 'document, 'seq> {
-    type Error = Error;
+    /// How the mapping key at `pos` is shown in paths: the value of a scalar
+    /// key, or a flow-style rendering like `[1, 2]` of any other key, which is
+    /// only written out if a path through it is needed.
+    fn key_text(&self, pos: usize) -> Option<KeyText<'de, 'document>> {
+        match self.event_at(pos).ok()?.0 {
+            Event::Scalar(scalar) => str::from_utf8(&scalar.value).ok().map(KeyText::Scalar),
+            _ => Some(KeyText::Flow(FlowKey {
+                document: self.document,
+                pos,
+            })),
+        }
+    }
 
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        match self.de.peek_event()? {
-            Event::SequenceEnd => Ok(None),
-            _ => {
-                let mut element_de = DeserializerFromEvents {
-                    document: self.de.document,
-                    pos: self.de.pos,
-                    path: Path::Seq {
-                        parent: &self.de.path,
-                        index: self.len,
-                    },
-                    remaining_depth: self.de.remaining_depth,
-                };
-                self.len += 1;
-                seed.deserialize(&mut element_de).map(Some)
+    /// Lists the positions of the key and value of every entry in the mapping
+    /// whose MappingStart is at `start`, along with the positions of the
+    /// values of any `<<` merge keys, which are not included in the entries.
+    fn mapping_entries(&self, start: usize) -> Result<(Vec<Entry>, Vec<usize>)> {
+        let mut entries = Vec::new();
+        let mut merges = Vec::new();
+        let mut pos = start + 1;
+        loop {
+            let key = match self.event_at(pos)?.0 {
+                Event::MappingEnd => return Ok((entries, merges)),
+                key => key,
+            };
+            let value = self.node_end(pos)?;
+            if is_merge_key(key) {
+                merges.push(value);
+            } else {
+                entries.push((pos, value));
             }
+            pos = self.node_end(value)?;
         }
     }
-}
-
-struct MapAccess<'de, 'document, 'map> {
-    de: &'map mut DeserializerFromEvents<'de, 'document>,
-    len: usize,
-    key: Option<&'document [u8]>,
-}
 
-impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map> {
-    type Error = Error;
+    /// Lists the entries of the mapping whose MappingStart is at `start` after
+    /// resolving `<<` merge keys. Returns None if the mapping does not contain
+    /// a merge key, in which case its entries are visited in place.
+    fn merged_entries(
+        &mut self,
+        start: usize,
+        mark: Mark,
+        remaining_depth: usize,
+    ) -> Result<Option<Vec<Entry>>> {
+        if !self.document.merges.contains(&start) {
+            return Ok(None);
+        }
+        let (mut entries, merges) = self.mapping_entries(start)?;
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
-    where
-        K: DeserializeSeed<'de>,
-    {
-        match self.de.peek_event()? {
-            Event::MappingEnd => Ok(None),
+        let remaining_depth = match remaining_depth.checked_sub(1) {
+            Some(depth) => depth,
+            None => return Err(error::recursion_limit_exceeded(mark)),
+        };
+        let mut present = HashSet::new();
+        for &(key, _value) in &entries {
+            if let Some(identity) = self.key_identity(key)? {
+                present.insert(identity);
+            }
+        }
+        for value in merges {
+            let source = self.follow_merge_alias(value)?;
+            let (event, mark) = self.event_at(source)?;
+            match event {
+                Event::MappingStart(_) => {
+                    let merge = (value, source);
+                    self.merge_from(&mut entries, &mut present, merge, mark, remaining_depth)?;
+                }
+                Event::SequenceStart(_) => {
+                    let mut pos = source + 1;
+                    let mut index = 0;
+                    loop {
+                        let element = match self.event_at(pos)?.0 {
+                            Event::SequenceEnd => break,
+                            _ => self.follow_merge_alias(pos)?,
+                        };
+                        let (event, mark) = self.event_at(element)?;
+                        if let Event::MappingStart(_) = event {
+                            self.merge_from(
+                                &mut entries,
+                                &mut present,
+                                (pos, element),
+                                mark,
+                                remaining_depth,
+                            )?;
+                        } else {
+                            let err =
+                                invalid_type(event, &"a mapping for merging", &self.options.schema);
+                            let path = Path::Map {
+                                parent: &self.path,
+                                key: "<<",
+                            };
+                            let path = Path::Seq {
+                                parent: &path,
+                                index,
+                            };
+                            return Err(error::fix_mark(err, mark, path));
+                        }
+                        pos = self.node_end(pos)?;
+                        index += 1;
+                    }
+                }
+                other => {
+                    let err = invalid_type(
+                        other,
+                        &"a mapping or list of mappings for merging",
+                        &self.options.schema,
+                    );
+                    let path = Path::Map {
+                        parent: &self.path,
+                        key: "<<",
+                    };
+                    return Err(error::fix_mark(err, mark, path));
+                }
+            }
+        }
+        Ok(Some(entries))
+    }
+
+    /// Appends the entries of the mapping at `source` whose keys are not
+    /// already `present`, where `source` is the node that the merged node at
+    /// `via` is or refers to. Keys that appear earlier take precedence.
+    fn merge_from(
+        &mut self,
+        entries: &mut Vec<Entry>,
+        present: &mut HashSet<KeyIdentity<'document>>,
+        (via, source): (usize, usize),
+        mark: Mark,
+        remaining_depth: usize,
+    ) -> Result<()> {
+        let outer_alias = self.expanding_alias;
+        if via != source {
+            self.expanding_alias = Some(outer_alias.unwrap_or(via));
+        }
+        let result = self.merged_entries(source, mark, remaining_depth);
+        self.expanding_alias = outer_alias;
+        let merged = match result? {
+            Some(merged) => merged,
+            None => self.mapping_entries(source)?.0,
+        };
+        for (key, value) in merged {
+            match self.key_identity(key)? {
+                Some(identity) if !present.insert(identity) => {}
+                _ => entries.push((key, value)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows the value of a `<<` merge key, or one element of it, if it is
+    /// an alias. Every alias merged counts against the alias expansion limit.
+    fn follow_merge_alias(&mut self, pos: usize) -> Result<usize> {
+        if let Event::Alias(_) = self.event_at(pos)?.0 {
+            self.charge_alias(pos)?;
+        }
+        self.resolve_alias(pos)
+    }
+
+    /// What makes the mapping key at `pos` equal to another key: its value
+    /// and whether it is a string. None for keys other than scalars, which are
+    /// never considered equal.
+    fn key_identity(&self, pos: usize) -> Result<Option<KeyIdentity<'document>>> {
+        Ok(match self.event_at(self.resolve_alias(pos)?)?.0 {
             Event::Scalar(scalar) => {
-                self.len += 1;
-                self.key = Some(&scalar.value);
-                seed.deserialize(&mut *self.de).map(Some)
+                Some((&scalar.value, is_string_key(scalar, &self.options.schema)))
             }
+            _ => None,
+        })
+    }
+
+    fn visit_merged_mapping<V>(
+        &mut self,
+        visitor: V,
+        mark: Mark,
+        start: usize,
+        entries: Vec<Entry>,
+        fields: Fields,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let total = entries.len();
+        let (value, len) = self.recursion_check(mark, |de| {
+            let mut map = MergedMapAccess {
+                de,
+                entries: entries.into_iter(),
+                len: 0,
+                value: None,
+                key_pos: 0,
+                fields,
+            };
+            let value = visitor.visit_map(&mut map)?;
+            Ok((value, map.len))
+        })?;
+        *self.pos = self.node_end(start)?;
+        if total == len {
+            Ok(value)
+        } else {
+            Err(de::Error::invalid_length(total, &ExpectedMap(len)))
+        }
+    }
+
+    fn recursion_check<F: FnOnce(&mut Self) -> Result<T>, T>(
+        &mut self,
+        mark: Mark,
+        f: F,
+    ) -> Result<T> {
+        let previous_depth = self.remaining_depth;
+        self.remaining_depth = match previous_depth.checked_sub(1) {
+            Some(depth) => depth,
+            None => return Err(error::recursion_limit_exceeded(mark)),
+        };
+        let result = f(self);
+        self.remaining_depth = previous_depth;
+        result
+    }
+}
+
+struct SeqAccess<'de, 'document, 'seq> {
+    de: &'seq mut DeserializerFromEvents<'de, 'document>,
+    len: usize,
+    /// Whether the sequence is tagged `!!pairs` or `!!omap`.
+    pairs: bool,
+}
+
+impl<'de, 'document, 'seq> de::SeqAccess<'de> for SeqAccess<'de, 'document, 'seq> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.de.peek_event()? {
+            Event::SequenceEnd => Ok(None),
             _ => {
+                let mut element_de = DeserializerFromEvents {
+                    document: self.de.document,
+                    pos: self.de.pos,
+                    path: Path::Seq {
+                        parent: &self.de.path,
+                        index: self.len,
+                    },
+                    remaining_depth: self.de.remaining_depth,
+                    remaining_aliases: self.de.remaining_aliases,
+                    expanding_alias: self.de.expanding_alias,
+                    options: self.de.options,
+                    recovery: self.de.recovery,
+                    ignored: self.de.ignored,
+                    tagged_already: false,
+                    pair_element: self.pairs,
+                    key_pos: None,
+                };
                 self.len += 1;
-                self.key = None;
-                seed.deserialize(&mut *self.de).map(Some)
+                seed.deserialize(&mut element_de).map(Some)
             }
         }
     }
+}
+
+struct SetAccess<'de, 'document, 'map>(MapAccess<'de, 'document, 'map>);
+
+impl<'de, 'document, 'map> de::SeqAccess<'de> for SetAccess<'de, 'document, 'map> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let element = de::MapAccess::next_key_seed(&mut self.0, seed)?;
+        if element.is_some() {
+            // The values of a set are nulls that carry no data, so skipping
+            // them is not reported as ignoring a field.
+            self.0.de.ignore_any()?;
+        }
+        Ok(element)
+    }
+}
+
+struct PairAccess<'de, 'document, 'map> {
+    map: MapAccess<'de, 'document, 'map>,
+    value_next: bool,
+}
+
+impl<'de, 'document, 'map> de::SeqAccess<'de> for PairAccess<'de, 'document, 'map> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if mem::replace(&mut self.value_next, false) {
+            return de::MapAccess::next_value_seed(&mut self.map, seed).map(Some);
+        }
+        if self.map.len != 0 {
+            return Ok(None);
+        }
+        let key = de::MapAccess::next_key_seed(&mut self.map, seed)?;
+        self.value_next = key.is_some();
+        Ok(key)
+    }
+}
+
+/// A mapping key as shown in paths.
+enum KeyText<'de, 'document> {
+    Scalar(&'document str),
+    Flow(FlowKey<'de, 'document>),
+}
+
+/// Path to the value of the mapping entry whose key is `key`.
+fn key_path<'a>(parent: &'a Path<'a>, key: &'a Option<KeyText>) -> Path<'a> {
+    match key {
+        Some(KeyText::Scalar(key)) => Path::Map { parent, key },
+        Some(KeyText::Flow(key)) => Path::LazyMap { parent, key },
+        None => Path::Unknown { parent },
+    }
+}
+
+/// Longest flow-style rendering of a mapping key in a path, past which it is
+/// cut short with `...`.
+const MAX_FLOW_KEY_LEN: usize = 100;
+
+/// A sequence, mapping or alias used as a mapping key, displayed in flow style
+/// like `[0, {x: 1.5}]`. Aliases are displayed as `*name` rather than followed,
+/// so that showing a key never expands them.
+struct FlowKey<'de, 'document> {
+    document: &'document Document<'de>,
+    pos: usize,
+}
+
+impl<'de, 'document> Display for FlowKey<'de, 'document> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        // Whether each open collection is a mapping, and how many nodes it has
+        // shown so far.
+        let mut open: Vec<(bool, usize)> = Vec::new();
+        let mut pos = self.pos;
+        while let Some((event, _span)) = self.document.events.get(pos) {
+            if out.len() > MAX_FLOW_KEY_LEN {
+                let mut end = MAX_FLOW_KEY_LEN;
+                while !out.is_char_boundary(end) {
+                    end -= 1;
+                }
+                out.truncate(end);
+                out.push_str("...");
+                break;
+            }
+            pos += 1;
+            if let Event::SequenceEnd | Event::MappingEnd = event {
+                let (mapping, _len) = open.pop().expect("unbalanced collection");
+                out.push(if mapping { '}' } else { ']' });
+            } else {
+                if let Some((mapping, len)) = open.last_mut() {
+                    if *len > 0 {
+                        let after_key = *mapping && *len % 2 == 1;
+                        out.push_str(if after_key { ": " } else { ", " });
+                    }
+                    *len += 1;
+                }
+                match event {
+                    Event::Scalar(scalar) => out.push_str(&String::from_utf8_lossy(&scalar.value)),
+                    Event::Alias(id) => {
+                        out.push('*');
+                        let anchor = self.document.aliases.get(id);
+                        if let Some(name) = anchor.and_then(|pos| self.document.anchors.get(pos)) {
+                            out.push_str(name);
+                        }
+                    }
+                    Event::SequenceStart(_) => {
+                        out.push('[');
+                        open.push((false, 0));
+                    }
+                    Event::MappingStart(_) => {
+                        out.push('{');
+                        open.push((true, 0));
+                    }
+                    Event::SequenceEnd | Event::MappingEnd => unreachable!(),
+                }
+            }
+            if open.is_empty() {
+                break;
+            }
+        }
+        formatter.write_str(&out)
+    }
+}
+
+struct MapAccess<'de, 'document, 'map> {
+    de: &'map mut DeserializerFromEvents<'de, 'document>,
+    len: usize,
+    key: Option<KeyText<'de, 'document>>,
+    /// Position of the current entry's key.
+    key_pos: usize,
+    fields: Fields,
+}
+
+impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.key_pos = *self.de.pos;
+        if let Event::MappingEnd = self.de.peek_event()? {
+            return Ok(None);
+        }
+        self.fields.check(self.de, self.key_pos)?;
+        self.key = self.de.key_text(self.key_pos);
+        self.len += 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
@@ -687,112 +1718,198 @@ impl<'de, 'document, 'map> de::MapAccess<'de> for MapAccess<'de, 'document, 'map
         let mut value_de = DeserializerFromEvents {
             document: self.de.document,
             pos: self.de.pos,
-            path: if let Some(key) = self.key.and_then(|key| str::from_utf8(key).ok()) {
-                Path::Map {
-                    parent: &self.de.path,
-                    key,
-                }
-            } else {
-                Path::Unknown {
-                    parent: &self.de.path,
-                }
-            },
+            path: key_path(&self.de.path, &self.key),
             remaining_depth: self.de.remaining_depth,
+            remaining_aliases: self.de.remaining_aliases,
+            expanding_alias: self.de.expanding_alias,
+            options: self.de.options,
+            recovery: self.de.recovery,
+            ignored: self.de.ignored,
+            tagged_already: false,
+            pair_element: false,
+            key_pos: Some(self.key_pos),
         };
         seed.deserialize(&mut value_de)
     }
 }
 
-
-struct SpannedMapAccess<'de, 'document, 'variant> {
-    de: &'variant mut DeserializerFromEvents<'de, 'document>,
-    pos: usize,
-    state: SpannedMapAccessState,
+/// Positions of the key and the value of a mapping entry.
+type Entry = (usize, usize);
+
+/// Which repeated keys of a mapping are refused.
+#[derive(Copy, Clone)]
+enum Duplicates {
+    /// None, unless the options deny duplicate keys.
+    Allowed,
+    /// Every repeated scalar key, as `Value` and `Mapping` want.
+    Denied,
+    /// Repeated keys naming one of these fields of the struct being
+    /// deserialized, which serde would refuse without saying where the field
+    /// was first given.
+    Fields(&'static [&'static str]),
 }
 
-impl<'de, 'document, 'variant> SpannedMapAccess<'de, 'document, 'variant> {
-    fn start_location(&self) -> Result<usize> {
-        let (event, marker) = self
-            .de
-            .document
-            .events
-            .get(self.pos)
-            .ok_or_else(crate::error::end_of_stream)?;
+/// The fields of the struct being deserialized from a mapping, along with
+/// each given field's index and the position of its key.
+#[derive(Default)]
+struct Fields {
+    names: &'static [&'static str],
+    given: Vec<(usize, usize)>,
+}
 
-        let mut index = marker.index() as usize; 
+impl Fields {
+    fn new(names: &'static [&'static str]) -> Self {
+        Fields {
+            names,
+            given: Vec::new(),
+        }
+    }
 
-        if let Event::Scalar(scalar) = event {
-            if matches!(scalar.style, ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted) {
-                index += 1;
+    /// Fails if the key at `pos` names a field that was already given.
+    fn check(&mut self, de: &DeserializerFromEvents, pos: usize) -> Result<()> {
+        if self.names.is_empty() {
+            return Ok(());
+        }
+        let name = match de.key_identity(pos)? {
+            Some((name, true)) => name,
+            _ => return Ok(()),
+        };
+        let field = match self.names.iter().position(|field| field.as_bytes() == name) {
+            Some(field) => field,
+            None => return Ok(()),
+        };
+        match self.given.iter().find(|(given, _pos)| *given == field) {
+            Some(&(_, first)) => Err(de.duplicate_key(name, first, pos)),
+            None => {
+                self.given.push((field, pos));
+                Ok(())
             }
         }
-
-        Ok(index)
     }
+}
 
-    fn index_of_sequence_end(&self) -> Result<usize> {
-        let mut nesting_level = 0;
+type KeyIdentity<'document> = (&'document [u8], bool);
 
-        for (event, marker) in &self.de.document.events[self.pos..] {
-            if matches!(event, Event::SequenceStart) {
-                nesting_level += 1;
-            } else if matches!(event, Event::SequenceEnd) {
-                nesting_level -= 1;
+struct MergedMapAccess<'de, 'document, 'map> {
+    de: &'map mut DeserializerFromEvents<'de, 'document>,
+    entries: std::vec::IntoIter<Entry>,
+    len: usize,
+    value: Option<(usize, Option<KeyText<'de, 'document>>)>,
+    /// Position of the current entry's key.
+    key_pos: usize,
+    fields: Fields,
+}
 
-                if nesting_level == 0 {
-                    return Ok(marker.index() as usize + 1);
-                }
-            }
-        }
+impl<'de, 'document, 'map> de::MapAccess<'de> for MergedMapAccess<'de, 'document, 'map> {
+    type Error = Error;
 
-        Err(crate::error::end_of_stream())
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let (mut pos, value) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        self.fields.check(self.de, pos)?;
+        self.key_pos = pos;
+        let key = self.de.key_text(pos);
+        self.len += 1;
+        self.value = Some((value, key));
+        let mut key_de = DeserializerFromEvents {
+            document: self.de.document,
+            pos: &mut pos,
+            path: self.de.path,
+            remaining_depth: self.de.remaining_depth,
+            remaining_aliases: self.de.remaining_aliases,
+            expanding_alias: self.de.expanding_alias,
+            options: self.de.options,
+            recovery: self.de.recovery,
+            ignored: self.de.ignored,
+            tagged_already: false,
+            pair_element: false,
+            key_pos: None,
+        };
+        seed.deserialize(&mut key_de).map(Some)
     }
 
-    fn index_of_mapping_end(&self) -> Result<usize> {
-        let mut nesting_level = 0;
-        let mut last_index = None;
-
-        for (event, marker) in &self.de.document.events[self.pos - 1..] {
-            if matches!(event, Event::SequenceStart) {
-                nesting_level += 1;
-            } else if matches!(event, Event::SequenceEnd) {
-                nesting_level -= 1;
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (mut pos, key) = self.value.take().expect("value is missing");
+        let mut value_de = DeserializerFromEvents {
+            document: self.de.document,
+            pos: &mut pos,
+            path: key_path(&self.de.path, &key),
+            remaining_depth: self.de.remaining_depth,
+            remaining_aliases: self.de.remaining_aliases,
+            expanding_alias: self.de.expanding_alias,
+            options: self.de.options,
+            recovery: self.de.recovery,
+            ignored: self.de.ignored,
+            tagged_already: false,
+            pair_element: false,
+            key_pos: Some(self.key_pos),
+        };
+        seed.deserialize(&mut value_de)
+    }
+}
 
-                if nesting_level == 0 {
-                    return last_index.ok_or_else(crate::error::end_of_stream);
-                }
-            }
+struct ExpectedMap(usize);
 
-            // Note: subtract one because of inclusive end, then subtract
-            // another because that's what makes tests pass
-            last_index = Some(marker.index() as usize);
+impl Expected for ExpectedMap {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 1 {
+            write!(formatter, "map containing 1 entry")
+        } else {
+            write!(formatter, "map containing {} entries", self.0)
         }
+    }
+}
 
-        last_index.ok_or_else(crate::error::end_of_stream)
+pub(crate) fn is_merge_key(event: &Event) -> bool {
+    match event {
+        Event::Scalar(scalar) => {
+            *scalar.value == *b"<<"
+                && scalar.style == ScalarStyle::Plain
+                && scalar.tag.as_ref().map_or(true, |tag| tag == Tag::MERGE)
+        }
+        _ => false,
     }
+}
 
-    fn current_item_length(&self) -> Result<usize> {
-        // Note: The serde-yaml crate only records the start of each event and
-        // not the end position/length, so we try to calculate it ourselves.
-        let (event, marker) = self
-            .de
-            .document
-            .events
-            .get(self.pos)
-            .ok_or_else(crate::error::end_of_stream)?;
+// Whether a scalar key resolves to a string, as opposed to a null, bool or
+// number spelled the same way.
+fn is_string_key(scalar: &Scalar, schema: &Schema) -> bool {
+    if let Some(tag) = &scalar.tag {
+        return !(tag == Tag::NULL
+            || tag == Tag::BOOL
+            || tag == Tag::INT
+            || tag == Tag::FLOAT
+            || tag == Tag::TIMESTAMP);
+    }
+    if scalar.style != ScalarStyle::Plain {
+        return true;
+    }
+    match str::from_utf8(&scalar.value) {
+        Ok(v) => schema.resolve(v) == Resolved::String,
+        Err(_) => true,
+    }
+}
 
-        let length = match event {
-            // just add the length of the token. Don't forget to subtract by 1
-            // because of our inclusive end bound.
-            Event::Scalar(token) => token.value.len(),
-            // find the index of the end token
-            Event::SequenceStart => self.index_of_sequence_end()? - marker.index() as usize,
-            // find the index of the end token
-            Event::MappingStart => self.index_of_mapping_end()? - marker.index() as usize,
-            _ => 0,
-        };
+struct SpannedMapAccess<'de, 'document, 'variant> {
+    de: &'variant mut DeserializerFromEvents<'de, 'document>,
+    pos: usize,
+    state: SpannedMapAccessState,
+}
 
-        Ok(length)
+impl<'de, 'document, 'variant> SpannedMapAccess<'de, 'document, 'variant> {
+    fn span(&self) -> Result<Span> {
+        match self.de.document.events.get(self.pos) {
+            Some((_event, span)) => Ok(*span),
+            None => Err(error::end_of_stream()),
+        }
     }
 }
 
@@ -814,9 +1931,9 @@ impl<'de, 'document, 'variant> de::MapAccess<'de> for SpannedMapAccess<'de, 'doc
                 seed.deserialize(BorrowedStrDeserializer::new(crate::spanned::VALUE))
                     .map(Some)
             }
-            SpannedMapAccessState::LengthKey => {
-                self.state = SpannedMapAccessState::DeserializeLength;
-                seed.deserialize(BorrowedStrDeserializer::new(crate::spanned::LENGTH))
+            SpannedMapAccessState::EndKey => {
+                self.state = SpannedMapAccessState::DeserializeEnd;
+                seed.deserialize(BorrowedStrDeserializer::new(crate::spanned::END))
                     .map(Some)
             }
             SpannedMapAccessState::PathKey => {
@@ -835,41 +1952,146 @@ impl<'de, 'document, 'variant> de::MapAccess<'de> for SpannedMapAccess<'de, 'doc
     {
         match self.state {
             SpannedMapAccessState::DeserializeStart => {
-                let marker = self.start_location()?;
+                let span = self.span()?;
                 self.state = SpannedMapAccessState::ValueKey;
-                seed.deserialize(marker.into_deserializer())
+                let location = Location::from_mark(span.start);
+                seed.deserialize(crate::spanned::location_deserializer(location))
             }
             SpannedMapAccessState::DeserializeValue => {
-                self.state = SpannedMapAccessState::LengthKey;
+                self.state = SpannedMapAccessState::EndKey;
                 let mut value_de = DeserializerFromEvents {
                     document: self.de.document,
                     pos: self.de.pos,
                     path: self.de.path,
                     remaining_depth: self.de.remaining_depth,
+                    remaining_aliases: self.de.remaining_aliases,
+                    expanding_alias: self.de.expanding_alias,
+                    options: self.de.options,
+                    recovery: self.de.recovery,
+                    ignored: self.de.ignored,
+                    tagged_already: self.de.tagged_already,
+                    pair_element: self.de.pair_element,
+                    key_pos: self.de.key_pos,
                 };
                 seed.deserialize(&mut value_de)
             }
-            SpannedMapAccessState::DeserializeLength => {
+            SpannedMapAccessState::DeserializeEnd => {
+                let span = self.span()?;
                 self.state = SpannedMapAccessState::PathKey;
-                seed.deserialize(self.current_item_length()?.into_deserializer())
+                let location = Location::from_mark(span.end);
+                seed.deserialize(crate::spanned::location_deserializer(location))
             }
             SpannedMapAccessState::DeserializePath => {
                 self.state = SpannedMapAccessState::Done;
-                seed.deserialize(self.de.path.to_string().into_deserializer())
+                let path = self.de.path.to_path_buf();
+                seed.deserialize(crate::spanned::path_deserializer(&path))
             }
             _ => todo!(),
         }
     }
 }
 
+/// Hands an anchored node or an alias to `Value` as a map with private keys,
+/// when deserializing with [`DeserializerOptions::preserve_anchors`].
+struct AnchorMapAccess<'de, 'document, 'variant> {
+    /// Positioned at the anchored node, or `None` for an alias.
+    de: Option<&'variant mut DeserializerFromEvents<'de, 'document>>,
+    name: &'document str,
+    state: AnchorMapAccessState,
+}
+
+impl<'de, 'document, 'variant> de::MapAccess<'de> for AnchorMapAccess<'de, 'document, 'variant> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let key = match self.state {
+            AnchorMapAccessState::NameKey => {
+                self.state = AnchorMapAccessState::DeserializeName;
+                match self.de {
+                    Some(_) => anchored::ANCHOR,
+                    None => anchored::ALIAS,
+                }
+            }
+            AnchorMapAccessState::ValueKey if self.de.is_some() => {
+                self.state = AnchorMapAccessState::DeserializeValue;
+                anchored::VALUE
+            }
+            _ => return Ok(None),
+        };
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.state {
+            AnchorMapAccessState::DeserializeName => {
+                self.state = AnchorMapAccessState::ValueKey;
+                seed.deserialize(self.name.into_deserializer())
+            }
+            AnchorMapAccessState::DeserializeValue => {
+                self.state = AnchorMapAccessState::Done;
+                let de = self.de.as_mut().expect("anchored node");
+                seed.deserialize(AnchoredNode(de))
+            }
+            other => unreachable!("Invalid state: {:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum AnchorMapAccessState {
+    NameKey,
+    DeserializeName,
+    ValueKey,
+    DeserializeValue,
+    Done,
+}
+
+/// The node behind an anchor that has already been handed over.
+struct AnchoredNode<'a, 'de, 'document>(&'a mut DeserializerFromEvents<'de, 'document>);
+
+impl<'a, 'de, 'document> de::Deserializer<'de> for AnchoredNode<'a, 'de, 'document> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.deserialize_node(visitor, false)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == value::NAME {
+            self.0.deserialize_node(visitor, true)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 enum SpannedMapAccessState {
     StartKey,
     DeserializeStart,
     ValueKey,
     DeserializeValue,
-    LengthKey,
-    DeserializeLength,
+    EndKey,
+    DeserializeEnd,
     PathKey,
     DeserializePath,
     Done,
@@ -877,8 +2099,8 @@ enum SpannedMapAccessState {
 
 struct EnumAccess<'de, 'document, 'variant> {
     de: &'variant mut DeserializerFromEvents<'de, 'document>,
-    name: &'static str,
-    tag: Option<&'static str>,
+    name: Option<&'static str>,
+    tag: Option<&'document str>,
 }
 
 impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document, 'variant> {
@@ -893,17 +2115,21 @@ impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document
         enum Nope {}
 
         struct BadKey {
-            name: &'static str,
+            name: Option<&'static str>,
         }
 
         impl<'de> Visitor<'de> for BadKey {
             type Value = Nope;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                write!(formatter, "variant of enum `{}`", self.name)
+                match self.name {
+                    Some(name) => write!(formatter, "variant of enum `{}`", name),
+                    None => formatter.write_str("enum variant"),
+                }
             }
         }
 
+        let key_pos = *self.de.pos;
         let variant = if let Some(tag) = self.tag {
             tag
         } else {
@@ -911,17 +2137,11 @@ impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document
                 Event::Scalar(scalar) => str::from_utf8(&scalar.value).ok(),
                 Event::MappingEnd => {
                     let bad = BadKey { name: self.name };
-                    return Err(de::Error::invalid_type(
-                        Unexpected::Map,
-                        &bad,
-                    ));
+                    return Err(de::Error::invalid_type(Unexpected::Map, &bad));
                 }
                 Event::SequenceEnd => {
                     let bad = BadKey { name: self.name };
-                    return Err(de::Error::invalid_type(
-                        Unexpected::Seq,
-                        &bad,
-                    ));
+                    return Err(de::Error::invalid_type(Unexpected::Seq, &bad));
                 }
                 _ => None,
             } {
@@ -934,8 +2154,21 @@ impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document
             }
         };
 
+        // Where the variant name is in the input, for the suggestion of an
+        // unknown one: at the end of the tag's suffix as written, or the whole
+        // node if the tag's handle gives part of it.
+        let span = match self.tag {
+            Some(tag) => {
+                let (event, span) = &self.de.document.events[key_pos];
+                tag_variant_span(event, tag).unwrap_or(*span)
+            }
+            None => self.de.document.events[key_pos].1,
+        };
+        let (start, end) = (span.start, span.end);
         let str_de = IntoDeserializer::<Error>::into_deserializer(variant);
-        let ret = seed.deserialize(str_de)?;
+        let ret = seed
+            .deserialize(str_de)
+            .map_err(|err| error::fix_end(error::fix_mark(err, start, self.de.path), end))?;
         let variant_visitor = DeserializerFromEvents {
             document: self.de.document,
             pos: self.de.pos,
@@ -944,6 +2177,14 @@ impl<'de, 'document, 'variant> de::EnumAccess<'de> for EnumAccess<'de, 'document
                 key: variant,
             },
             remaining_depth: self.de.remaining_depth,
+            remaining_aliases: self.de.remaining_aliases,
+            expanding_alias: self.de.expanding_alias,
+            options: self.de.options,
+            recovery: self.de.recovery,
+            ignored: self.de.ignored,
+            tagged_already: self.tag.is_some(),
+            pair_element: false,
+            key_pos: None,
         };
         Ok((ret, variant_visitor))
     }
@@ -1034,7 +2275,16 @@ impl<'de, 'document, 'variant> de::VariantAccess<'de>
     }
 }
 
-fn visit_scalar<'de, V>(visitor: V, scalar: &Scalar<'de>) -> Result<V::Value>
+// Visits a scalar. Timestamps are handed to the visitors of `Value` and
+// `SpannedValue`, told apart by `as_value`, as maps with a private key, and to
+// other visitors as strings.
+fn visit_scalar<'de, V>(
+    visitor: V,
+    scalar: &Scalar<'de>,
+    tagged_already: bool,
+    as_value: bool,
+    schema: &Schema,
+) -> Result<V::Value>
 where
     V: Visitor<'de>,
 {
@@ -1048,251 +2298,148 @@ where
         }
     };
     if let Some(tag) = &scalar.tag {
+        let tag_schema = schema.for_tags();
         if tag == Tag::BOOL {
-            return match parse_bool(v) {
+            return match tag_schema.parse_bool(v) {
                 Some(v) => visitor.visit_bool(v),
                 None => Err(de::Error::invalid_value(Unexpected::Str(v), &"a boolean")),
-            };
-        } else if tag == Tag::INT {
-            return match visit_int(visitor, v) {
-                Ok(result) => result,
-                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(v), &"an integer")),
-            };
-        } else if tag == Tag::FLOAT {
-            return match parse_f64(v) {
-                Some(v) => visitor.visit_f64(v),
-                None => Err(de::Error::invalid_value(Unexpected::Str(v), &"a float")),
-            };
-        } else if tag == Tag::NULL {
-            return match parse_null(v.as_bytes()) {
-                Some(()) => visitor.visit_unit(),
-                None => Err(de::Error::invalid_value(Unexpected::Str(v), &"null")),
-            };
-        }
-    } else if scalar.style == ScalarStyle::Plain {
-        return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style);
-    }
-    if let Some(borrowed) = parse_borrowed_str(v, scalar.repr, scalar.style) {
-        visitor.visit_borrowed_str(borrowed)
-    } else {
-        visitor.visit_str(v)
-    }
-}
-
-fn parse_borrowed_str<'de>(
-    utf8_value: &str,
-    repr: Option<&'de [u8]>,
-    style: ScalarStyle,
-) -> Option<&'de str> {
-    let borrowed_repr = repr?;
-    let expected_offset = match style {
-        ScalarStyle::Plain => 0,
-        ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => 1,
-        ScalarStyle::Literal | ScalarStyle::Folded => return None,
-    };
-    let expected_end = borrowed_repr.len().checked_sub(expected_offset)?;
-    let expected_start = expected_end.checked_sub(utf8_value.len())?;
-    let borrowed_bytes = borrowed_repr.get(expected_start..expected_end)?;
-    if borrowed_bytes == utf8_value.as_bytes() {
-        return Some(unsafe { str::from_utf8_unchecked(borrowed_bytes) });
-    }
-    None
-}
-
-fn parse_null(scalar: &[u8]) -> Option<()> {
-    if scalar == b"~" || scalar == b"null" {
-        Some(())
-    } else {
-        None
-    }
-}
-
-fn parse_bool(scalar: &str) -> Option<bool> {
-    if scalar == "true" {
-        Some(true)
-    } else if scalar == "false" {
-        Some(false)
-    } else {
-        None
-    }
-}
-
-fn parse_unsigned_int<T>(
-    scalar: &str,
-    from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
-) -> Option<T> {
-    let unpositive = scalar.strip_prefix('+').unwrap_or(scalar);
-    if let Some(rest) = unpositive.strip_prefix("0x") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 16) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = unpositive.strip_prefix("0o") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 8) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = unpositive.strip_prefix("0b") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 2) {
-            return Some(int);
-        }
-    }
-    if unpositive.starts_with(['+', '-']) {
-        return None;
-    }
-    if digits_but_not_number(scalar) {
-        return None;
-    }
-    from_str_radix(unpositive, 10).ok()
-}
-
-fn parse_signed_int<T>(
-    scalar: &str,
-    from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
-) -> Option<T> {
-    let unpositive = if let Some(unpositive) = scalar.strip_prefix('+') {
-        if unpositive.starts_with(['+', '-']) {
-            return None;
-        }
-        unpositive
-    } else {
-        scalar
-    };
-    if let Some(rest) = unpositive.strip_prefix("0x") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 16) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = scalar.strip_prefix("-0x") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 16) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = unpositive.strip_prefix("0o") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 8) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = scalar.strip_prefix("-0o") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 8) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = unpositive.strip_prefix("0b") {
-        if rest.starts_with(['+', '-']) {
-            return None;
-        }
-        if let Ok(int) = from_str_radix(rest, 2) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = scalar.strip_prefix("-0b") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 2) {
-            return Some(int);
+            };
+        } else if tag == Tag::INT {
+            return match tag_schema.resolve(v) {
+                int @ (Resolved::PosInt(_) | Resolved::NegInt(_)) => visit_number(visitor, v, int),
+                _ => Err(de::Error::invalid_value(Unexpected::Str(v), &"an integer")),
+            };
+        } else if tag == Tag::FLOAT {
+            return match tag_schema.parse_f64(v) {
+                Some(float) => visit_number(visitor, v, Resolved::Float(float)),
+                None => Err(de::Error::invalid_value(Unexpected::Str(v), &"a float")),
+            };
+        } else if tag == Tag::NULL {
+            return if tag_schema.parse_null(v) {
+                visitor.visit_unit()
+            } else {
+                Err(de::Error::invalid_value(Unexpected::Str(v), &"null"))
+            };
+        } else if tag == Tag::TIMESTAMP {
+            match v.parse() {
+                Ok(timestamp) if as_value => return timestamp::visit(visitor, &timestamp),
+                Ok(_) => {}
+                Err(_) => {
+                    return Err(de::Error::invalid_value(Unexpected::Str(v), &"a timestamp"));
+                }
+            }
+        } else if tag == Tag::BINARY {
+            return decode_binary(&scalar.value).and_then(|bytes| visitor.visit_byte_buf(bytes));
+        } else if tagged_already
+            && parse_tag(&scalar.tag).is_some()
+            && scalar.style == ScalarStyle::Plain
+        {
+            return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style, as_value, schema);
         }
+    } else if scalar.style == ScalarStyle::Plain {
+        return visit_untagged_scalar(visitor, v, scalar.repr, scalar.style, as_value, schema);
     }
-    if digits_but_not_number(scalar) {
-        return None;
+    if let Some(borrowed) = parse_borrowed_str(v, scalar.repr, scalar.style) {
+        visitor.visit_borrowed_str(borrowed)
+    } else {
+        visitor.visit_str(v)
     }
-    from_str_radix(unpositive, 10).ok()
 }
 
-fn parse_negative_int<T>(
-    scalar: &str,
-    from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
-) -> Option<T> {
-    if let Some(rest) = scalar.strip_prefix("-0x") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 16) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = scalar.strip_prefix("-0o") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 8) {
-            return Some(int);
-        }
-    }
-    if let Some(rest) = scalar.strip_prefix("-0b") {
-        let negative = format!("-{}", rest);
-        if let Ok(int) = from_str_radix(&negative, 2) {
-            return Some(int);
-        }
-    }
-    if digits_but_not_number(scalar) {
-        return None;
-    }
-    from_str_radix(scalar, 10).ok()
+fn is_binary(scalar: &Scalar) -> bool {
+    scalar.tag.as_ref().map_or(false, |tag| tag == Tag::BINARY)
 }
 
-fn parse_f64(scalar: &str) -> Option<f64> {
-    let unpositive = if let Some(unpositive) = scalar.strip_prefix('+') {
-        if unpositive.starts_with(['+', '-']) {
-            return None;
-        }
-        unpositive
-    } else {
-        scalar
-    };
-    if let ".inf" | ".Inf" | ".INF" = unpositive {
-        return Some(f64::INFINITY);
-    }
-    if let "-.inf" | "-.Inf" | "-.INF" = scalar {
-        return Some(f64::NEG_INFINITY);
-    }
-    if let ".nan" | ".NaN" | ".NAN" = scalar {
-        return Some(f64::NAN);
-    }
-    if let Ok(float) = unpositive.parse::<f64>() {
-        if float.is_finite() {
-            return Some(float);
-        }
-    }
-    None
+fn is_set(mapping: &MappingStart) -> bool {
+    mapping.tag.as_ref().map_or(false, |tag| tag == Tag::SET)
 }
 
-fn digits_but_not_number(scalar: &str) -> bool {
-    // Leading zero(s) followed by numeric characters is a string according to
-    // the YAML 1.2 spec. https://yaml.org/spec/1.2/spec.html#id2761292
-    let scalar = scalar.strip_prefix(['-', '+']).unwrap_or(scalar);
-    scalar.len() > 1 && scalar.starts_with('0') && scalar[1..].bytes().all(|b| b.is_ascii_digit())
+fn is_pairs(sequence: &SequenceStart) -> bool {
+    sequence
+        .tag
+        .as_ref()
+        .map_or(false, |tag| tag == Tag::PAIRS || tag == Tag::OMAP)
 }
 
-fn visit_int<'de, V>(visitor: V, v: &str) -> Result<Result<V::Value>, V>
-where
-    V: Visitor<'de>,
-{
-    if let Some(int) = parse_unsigned_int(v, u64::from_str_radix) {
-        return Ok(visitor.visit_u64(int));
+// Decodes the base64 content of a `!!binary` scalar.
+fn decode_binary(value: &[u8]) -> Result<Vec<u8>> {
+    let decoded = str::from_utf8(value).ok().and_then(base64::decode);
+    decoded.ok_or_else(|| {
+        let unexpected = match str::from_utf8(value) {
+            Ok(v) => Unexpected::Str(v),
+            Err(_) => Unexpected::Bytes(value),
+        };
+        de::Error::invalid_value(unexpected, &"base64-encoded binary data")
+    })
+}
+
+// Returns the name of an application-specific `!Tag`, without the leading
+// '!'. Tags in the YAML core schema, such as `!!str`, are resolved by libyaml
+// to `tag:yaml.org,2002:str` and are not application-specific.
+fn parse_tag(libyaml_tag: &Option<Tag>) -> Option<&str> {
+    let bytes: &[u8] = libyaml_tag.as_ref()?;
+    match bytes.split_first()? {
+        (b'!', rest) if !rest.is_empty() => str::from_utf8(rest).ok(),
+        _ => None,
     }
-    if let Some(int) = parse_negative_int(v, i64::from_str_radix) {
-        return Ok(visitor.visit_i64(int));
+}
+
+// Where the variant name given by a node's tag is written, if the suffix of the
+// tag as written ends with all of it.
+fn tag_variant_span(event: &Event, variant: &str) -> Option<Span> {
+    let suffix = match event {
+        Event::Scalar(scalar) => scalar.tag_span,
+        Event::SequenceStart(sequence) => sequence.tag_span,
+        Event::MappingStart(mapping) => mapping.tag_span,
+        _ => None,
+    }?;
+    let end = suffix.end;
+    let len = variant.len() as u64;
+    if end.index() - suffix.start.index() < len {
+        return None;
     }
-    if let Some(int) = parse_unsigned_int(v, u128::from_str_radix) {
-        return Ok(visitor.visit_u128(int));
+    let start = Mark::new(
+        end.index() - len,
+        end.line(),
+        end.column() - variant.chars().count() as u64,
+    );
+    Some(Span { start, end })
+}
+
+// Returns `!!set`, `!!omap` or `!!pairs` for a node with one of the YAML
+// collection tags that give a mapping or sequence a different meaning, which
+// `Value` keeps as a tag.
+fn collection_tag(libyaml_tag: &Option<Tag>) -> Option<&'static str> {
+    let tag = libyaml_tag.as_ref()?;
+    if tag == Tag::SET {
+        Some("!!set")
+    } else if tag == Tag::OMAP {
+        Some("!!omap")
+    } else if tag == Tag::PAIRS {
+        Some("!!pairs")
+    } else {
+        None
     }
-    if let Some(int) = parse_negative_int(v, i128::from_str_radix) {
-        return Ok(visitor.visit_i128(int));
+}
+
+fn parse_borrowed_str<'de>(
+    utf8_value: &str,
+    repr: Option<&'de [u8]>,
+    style: ScalarStyle,
+) -> Option<&'de str> {
+    let borrowed_repr = repr?;
+    let expected_offset = match style {
+        ScalarStyle::Plain => 0,
+        ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => 1,
+        ScalarStyle::Literal | ScalarStyle::Folded => return None,
+    };
+    let expected_end = borrowed_repr.len().checked_sub(expected_offset)?;
+    let expected_start = expected_end.checked_sub(utf8_value.len())?;
+    let borrowed_bytes = borrowed_repr.get(expected_start..expected_end)?;
+    if borrowed_bytes == utf8_value.as_bytes() {
+        return Some(unsafe { str::from_utf8_unchecked(borrowed_bytes) });
     }
-    Err(visitor)
+    None
 }
 
 pub(crate) fn visit_untagged_scalar<'de, V>(
@@ -1300,33 +2447,56 @@ pub(crate) fn visit_untagged_scalar<'de, V>(
     v: &str,
     repr: Option<&'de [u8]>,
     style: ScalarStyle,
+    as_value: bool,
+    schema: &Schema,
 ) -> Result<V::Value>
 where
     V: Visitor<'de>,
 {
-    if v.is_empty() || parse_null(v.as_bytes()) == Some(()) {
-        return visitor.visit_unit();
-    }
-    if let Some(boolean) = parse_bool(v) {
-        return visitor.visit_bool(boolean);
-    }
-    let visitor = match visit_int(visitor, v) {
-        Ok(result) => return result,
-        Err(visitor) => visitor,
-    };
-    if !digits_but_not_number(v) {
-        if let Some(float) = parse_f64(v) {
-            return visitor.visit_f64(float);
+    match schema.resolve(v) {
+        Resolved::Null => visitor.visit_unit(),
+        Resolved::Bool(boolean) => visitor.visit_bool(boolean),
+        number @ (Resolved::PosInt(_) | Resolved::NegInt(_) | Resolved::Float(_)) => {
+            visit_number(visitor, v, number)
+        }
+        Resolved::Timestamp(timestamp) if as_value => timestamp::visit(visitor, &timestamp),
+        Resolved::Timestamp(_) | Resolved::String => {
+            if let Some(borrowed) = parse_borrowed_str(v, repr, style) {
+                visitor.visit_borrowed_str(borrowed)
+            } else {
+                visitor.visit_str(v)
+            }
         }
     }
-    if let Some(borrowed) = parse_borrowed_str(v, repr, style) {
-        visitor.visit_borrowed_str(borrowed)
-    } else {
-        visitor.visit_str(v)
+}
+
+// Visits a number written as `v`, which `Value` and `Number` remember unless it
+// is written the way the serializer would write the number anyway.
+fn visit_number<'de, V>(visitor: V, v: &str, resolved: Resolved) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    if let Some(number) = Number::from_scalar(v, &resolved) {
+        return de::Deserializer::deserialize_any(&number, visitor);
     }
+    // Without arbitrary precision, a Number holds no integers beyond 64 bits.
+    match resolved {
+        Resolved::PosInt(int) => visitor.visit_u128(int),
+        Resolved::NegInt(int) => visitor.visit_i128(int),
+        _ => unreachable!(),
+    }
+}
+
+/// Hands the visitor an empty mapping, the placeholder for a mapping or struct
+/// whose node failed in recovery mode.
+fn visit_empty_map<'de, V>(visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    visitor.visit_map(MapDeserializer::new(iter::empty::<((), ())>()))
 }
 
-fn invalid_type(event: &Event, exp: &dyn Expected) -> Error {
+fn invalid_type(event: &Event, exp: &dyn Expected, schema: &Schema) -> Error {
     enum Void {}
 
     struct InvalidType<'a> {
@@ -1345,13 +2515,13 @@ fn invalid_type(event: &Event, exp: &dyn Expected) -> Error {
         Event::Alias(_) => unreachable!(),
         Event::Scalar(scalar) => {
             let get_type = InvalidType { exp };
-            match visit_scalar(get_type, scalar) {
+            match visit_scalar(get_type, scalar, false, false, schema) {
                 Ok(void) => match void {},
                 Err(invalid_type) => invalid_type,
             }
         }
-        Event::SequenceStart => de::Error::invalid_type(Unexpected::Seq, exp),
-        Event::MappingStart => de::Error::invalid_type(Unexpected::Map, exp),
+        Event::SequenceStart(_) => de::Error::invalid_type(Unexpected::Seq, exp),
+        Event::MappingStart(_) => de::Error::invalid_type(Unexpected::Map, exp),
         Event::SequenceEnd => panic!("unexpected end of sequence"),
         Event::MappingEnd => panic!("unexpected end of mapping"),
     }
@@ -1365,99 +2535,264 @@ impl<'de, 'document> DeserializerFromEvents<'de, 'document> {
         let (next, mark) = self.next_event_mark()?;
         match next {
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_scalar(visitor),
-            Event::Scalar(scalar) => visit_scalar(visitor, scalar),
-            other => Err(invalid_type(other, &visitor)),
+            Event::Scalar(scalar) => visit_scalar(
+                visitor,
+                scalar,
+                self.tagged_already,
+                false,
+                &self.options.schema,
+            ),
+            other => Err(invalid_type(other, &visitor, &self.options.schema)),
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
-}
-
-impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de, 'document> {
-    type Error = Error;
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    /// Deserializes the next node, ignoring any anchor on it. Only the
+    /// visitors of `Value`, `TaggedValue` and `SpannedValue` are given the
+    /// node's `!Tag`, or its `!!set`, `!!omap` or `!!pairs` tag, as an enum;
+    /// other visitors see the untagged content.
+    fn deserialize_node<V>(&mut self, visitor: V, as_value: bool) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        let tagged_already = self.tagged_already;
         let (next, mark) = self.next_event_mark()?;
-        match next {
-            Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_any(visitor),
-            Event::Scalar(scalar) => visit_scalar(visitor, scalar),
-            Event::SequenceStart => self.visit_sequence(visitor, mark),
-            Event::MappingStart => self.visit_mapping(visitor, mark),
-            Event::SequenceEnd => panic!("unexpected end of sequence"),
-            Event::MappingEnd => panic!("unexpected end of mapping"),
+        loop {
+            let tag = match next {
+                Event::Alias(mut pos) => {
+                    break self.jump(&mut pos)?.deserialize_node(visitor, as_value)
+                }
+                Event::Scalar(scalar) => &scalar.tag,
+                Event::SequenceStart(sequence) => &sequence.tag,
+                Event::MappingStart(mapping) => &mapping.tag,
+                Event::SequenceEnd => panic!("unexpected end of sequence"),
+                Event::MappingEnd => panic!("unexpected end of mapping"),
+            };
+            if as_value && !tagged_already {
+                if let Some(tag) = parse_tag(tag).or_else(|| collection_tag(tag)) {
+                    *self.pos -= 1;
+                    break visitor.visit_enum(EnumAccess {
+                        de: self,
+                        name: None,
+                        tag: Some(tag),
+                    });
+                }
+            }
+            break match next {
+                Event::Scalar(scalar) => visit_scalar(
+                    visitor,
+                    scalar,
+                    tagged_already,
+                    as_value,
+                    &self.options.schema,
+                ),
+                Event::SequenceStart(sequence) => {
+                    self.visit_sequence(visitor, mark, is_pairs(sequence))
+                }
+                Event::MappingStart(_) => {
+                    let duplicates = if as_value {
+                        Duplicates::Denied
+                    } else {
+                        Duplicates::Allowed
+                    };
+                    self.visit_mapping(visitor, mark, duplicates)
+                }
+                _ => unreachable!(),
+            };
         }
         // The de::Error impl creates errors with unknown line and column. Fill
         // in the position here by looking at the current index in the input.
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    /// Deserializes the next node, handing an anchor or alias on it to the
+    /// visitor as a map with private keys when anchors are preserved.
+    fn deserialize_anchored<V>(&mut self, visitor: V, as_value: bool) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.options.preserve_anchors || self.tagged_already {
+            return self.deserialize_node(visitor, as_value);
+        }
+        let document = self.document;
+        let path = self.path;
+        let (next, mark) = self.peek_event_mark()?;
+        let mut map = if let Event::Alias(pos) = next {
+            *self.pos += 1;
+            AnchorMapAccess {
+                de: None,
+                name: &document.anchors[&document.aliases[pos]],
+                state: AnchorMapAccessState::NameKey,
+            }
+        } else if let Some(name) = document.anchors.get(&*self.pos) {
+            AnchorMapAccess {
+                de: Some(self),
+                name,
+                state: AnchorMapAccessState::NameKey,
+            }
+        } else {
+            return self.deserialize_node(visitor, as_value);
+        };
+        visitor
+            .visit_map(&mut map)
+            .map_err(|err| error::fix_mark(err, mark, path))
+    }
+
+    /// Deserializes an integer for a visitor that accepts those from `min` to
+    /// `max`.
+    fn deserialize_signed<V>(&mut self, visitor: V, min: i64, max: i64) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let (next, mark) = self.next_event_mark()?;
         loop {
             match next {
-                Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_bool(visitor),
+                Event::Alias(mut pos) => {
+                    break self.jump(&mut pos)?.deserialize_signed(visitor, min, max)
+                }
                 Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(boolean) = parse_bool(value) {
-                            break visitor.visit_bool(boolean);
+                        if let Some(int) = self.options.schema.parse_int::<i64>(value) {
+                            // The visitor refuses an integer out of its range,
+                            // but recovery has to catch that before the
+                            // visitor is used up.
+                            if !(min..=max).contains(&int) && self.recovery.is_some() {
+                                let unexpected = Unexpected::Signed(int);
+                                let err = de::Error::invalid_value(unexpected, &visitor);
+                                break self.fail_or_recover(err, mark, visitor, |visitor| {
+                                    visitor.visit_i64(0)
+                                });
+                            }
+                            break visitor.visit_i64(int);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_i64(0));
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    /// Deserializes an integer for a visitor that accepts those from `min` to
+    /// `max`.
+    fn deserialize_unsigned<V>(&mut self, visitor: V, min: u64, max: u64) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        let (next, mark) = self.next_event_mark()?;
+        loop {
+            match next {
+                Event::Alias(mut pos) => {
+                    break self.jump(&mut pos)?.deserialize_unsigned(visitor, min, max)
+                }
+                Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
+                    if let Ok(value) = str::from_utf8(&scalar.value) {
+                        if let Some(int) = self.options.schema.parse_int::<u64>(value) {
+                            // The visitor refuses an integer out of its range,
+                            // but recovery has to catch that before the
+                            // visitor is used up.
+                            if !(min..=max).contains(&int) && self.recovery.is_some() {
+                                let unexpected = Unexpected::Unsigned(int);
+                                let err = de::Error::invalid_value(unexpected, &visitor);
+                                break self.fail_or_recover(err, mark, visitor, |visitor| {
+                                    visitor.visit_u64(0)
+                                });
+                            }
+                            break visitor.visit_u64(int);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_u64(0));
+        }
+        .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    /// Deserializes the next node, which must be a mapping.
+    fn deserialize_mapping<V>(&mut self, visitor: V, duplicates: Duplicates) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        let (next, mark) = self.next_event_mark()?;
+        match next {
+            Event::Alias(mut pos) => self
+                .jump(&mut pos)?
+                .deserialize_mapping(visitor, duplicates),
+            Event::MappingStart(_) => self.visit_mapping(visitor, mark, duplicates),
+            other => {
+                let err = invalid_type(other, &visitor, &self.options.schema);
+                self.fail_or_recover(err, mark, visitor, visit_empty_map)
+            }
+        }
+        .map_err(|err| error::fix_mark(err, mark, self.path))
     }
+}
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de, 'document> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        self.deserialize_anchored(visitor, false)
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let (next, mark) = self.next_event_mark()?;
         loop {
             match next {
-                Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_i64(visitor),
+                Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_bool(visitor),
                 Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_signed_int(value, i64::from_str_radix) {
-                            break visitor.visit_i64(int);
+                        if let Some(boolean) = self.options.schema.parse_bool(value) {
+                            break visitor.visit_bool(boolean);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_bool(false));
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_signed(visitor, i8::MIN.into(), i8::MAX.into())
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_signed(visitor, i16::MIN.into(), i16::MAX.into())
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_signed(visitor, i32::MIN.into(), i32::MAX.into())
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_signed(visitor, i64::MIN, i64::MAX)
+    }
+
     fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -1468,14 +2803,15 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_i128(visitor),
                 Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_signed_int(value, i128::from_str_radix) {
+                        if let Some(int) = self.options.schema.parse_int::<i128>(value) {
                             break visitor.visit_i128(int);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_i128(0));
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
@@ -1484,43 +2820,28 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        self.deserialize_u64(visitor)
+        self.deserialize_unsigned(visitor, u8::MIN.into(), u8::MAX.into())
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_u64(visitor)
+        self.deserialize_unsigned(visitor, u16::MIN.into(), u16::MAX.into())
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_u64(visitor)
+        self.deserialize_unsigned(visitor, u32::MIN.into(), u32::MAX.into())
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.next_event_mark()?;
-        loop {
-            match next {
-                Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_u64(visitor),
-                Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
-                    if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_unsigned_int(value, u64::from_str_radix) {
-                            break visitor.visit_u64(int);
-                        }
-                    }
-                }
-                _ => {}
-            }
-            break Err(invalid_type(next, &visitor));
-        }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        self.deserialize_unsigned(visitor, u64::MIN, u64::MAX)
     }
 
     fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
@@ -1533,14 +2854,15 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_u128(visitor),
                 Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(int) = parse_unsigned_int(value, u128::from_str_radix) {
+                        if let Some(int) = self.options.schema.parse_int::<u128>(value) {
                             break visitor.visit_u128(int);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_u128(0));
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
@@ -1562,14 +2884,15 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                 Event::Alias(mut pos) => break self.jump(&mut pos)?.deserialize_f64(visitor),
                 Event::Scalar(scalar) if scalar.style == ScalarStyle::Plain => {
                     if let Ok(value) = str::from_utf8(&scalar.value) {
-                        if let Some(float) = parse_f64(value) {
+                        if let Some(float) = self.options.schema.parse_f64(value) {
                             break visitor.visit_f64(float);
                         }
                     }
                 }
                 _ => {}
             }
-            break Err(invalid_type(next, &visitor));
+            let err = invalid_type(next, &visitor, &self.options.schema);
+            break self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_f64(0.0));
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
@@ -1595,11 +2918,15 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                         visitor.visit_str(v)
                     }
                 } else {
-                    Err(invalid_type(next, &visitor))
+                    let err = invalid_type(next, &visitor, &self.options.schema);
+                    self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_str(""))
                 }
             }
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_str(visitor),
-            other => Err(invalid_type(other, &visitor)),
+            other => {
+                let err = invalid_type(other, &visitor, &self.options.schema);
+                self.fail_or_recover(err, mark, visitor, |visitor| visitor.visit_str(""))
+            }
         }
         .map_err(|err: Error| error::fix_mark(err, mark, self.path))
     }
@@ -1640,10 +2967,13 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     true
                 } else if let Some(tag) = &scalar.tag {
                     if tag == Tag::NULL {
-                        if let Some(()) = parse_null(&scalar.value) {
-                            false
-                        } else if let Ok(v) = str::from_utf8(&scalar.value) {
-                            return Err(de::Error::invalid_value(Unexpected::Str(v), &"null"));
+                        let schema = self.options.schema.for_tags();
+                        if let Ok(v) = str::from_utf8(&scalar.value) {
+                            if schema.parse_null(v) {
+                                false
+                            } else {
+                                return Err(de::Error::invalid_value(Unexpected::Str(v), &"null"));
+                            }
                         } else {
                             return Err(de::Error::invalid_value(
                                 Unexpected::Bytes(&scalar.value),
@@ -1654,10 +2984,12 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                         true
                     }
                 } else {
-                    !scalar.value.is_empty() && parse_null(&scalar.value).is_none()
+                    !scalar.value.is_empty()
+                        && str::from_utf8(&scalar.value)
+                            .map_or(true, |v| !self.options.schema.parse_null(v))
                 }
             }
-            Event::SequenceStart | Event::MappingStart => true,
+            Event::SequenceStart(_) | Event::MappingStart(_) => true,
             Event::SequenceEnd => panic!("unexpected end of sequence"),
             Event::MappingEnd => panic!("unexpected end of mapping"),
         };
@@ -1684,11 +3016,17 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     }
 
     /// Parses a newtype struct as the underlying value.
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == value::NAME {
+            self.deserialize_anchored(visitor, true)
+        } else if name == mapping::NAME {
+            self.deserialize_mapping(visitor, Duplicates::Denied)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
@@ -1698,17 +3036,43 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
         let (next, mark) = self.next_event_mark()?;
         match next {
             Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_seq(visitor),
-            Event::SequenceStart => self.visit_sequence(visitor, mark),
-            other => Err(invalid_type(other, &visitor)),
+            Event::SequenceStart(sequence) => {
+                self.visit_sequence(visitor, mark, is_pairs(sequence))
+            }
+            // Lets sets, such as `HashSet<T>`, read the keys of a `!!set`.
+            Event::MappingStart(mapping) if is_set(mapping) => self.visit_set(visitor, mark),
+            // Lets sequences of bytes, such as `Vec<u8>`, read `!!binary`.
+            Event::Scalar(scalar) if is_binary(scalar) => decode_binary(&scalar.value)
+                .and_then(|bytes| SeqDeserializer::new(bytes.into_iter()).deserialize_any(visitor)),
+            other => {
+                let err = invalid_type(other, &visitor, &self.options.schema);
+                self.fail_or_recover(err, mark, visitor, |visitor| {
+                    visitor.visit_seq(SeqDeserializer::new(iter::empty::<()>()))
+                })
+            }
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    /// Parses a pair from a sequence of two elements or, inside `!!pairs` and
+    /// `!!omap`, from a mapping with a single entry, which is how they write
+    /// each pair.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let (next, mark) = self.next_event_mark()?;
+        match next {
+            Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_tuple(len, visitor),
+            Event::MappingStart(_) if len == 2 && self.pair_element => {
+                self.visit_pair(visitor, mark)
+            }
+            _ => {
+                *self.pos -= 1;
+                return self.deserialize_seq(visitor);
+            }
+        }
+        .map_err(|err| error::fix_mark(err, mark, self.path))
     }
 
     fn deserialize_tuple_struct<V>(
@@ -1727,13 +3091,7 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        let (next, mark) = self.next_event_mark()?;
-        match next {
-            Event::Alias(mut pos) => self.jump(&mut pos)?.deserialize_map(visitor),
-            Event::MappingStart => self.visit_mapping(visitor, mark),
-            other => Err(invalid_type(other, &visitor)),
-        }
-        .map_err(|err| error::fix_mark(err, mark, self.path))
+        self.deserialize_mapping(visitor, Duplicates::Allowed)
     }
 
     fn deserialize_struct<V>(
@@ -1756,9 +3114,14 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
             Event::Alias(mut pos) => self
                 .jump(&mut pos)?
                 .deserialize_struct(name, fields, visitor),
-            Event::SequenceStart => self.visit_sequence(visitor, mark),
-            Event::MappingStart => self.visit_mapping(visitor, mark),
-            other => Err(invalid_type(other, &visitor)),
+            Event::SequenceStart(sequence) => {
+                self.visit_sequence(visitor, mark, is_pairs(sequence))
+            }
+            Event::MappingStart(_) => self.visit_mapping(visitor, mark, Duplicates::Fields(fields)),
+            other => {
+                let err = invalid_type(other, &visitor, &self.options.schema);
+                self.fail_or_recover(err, mark, visitor, visit_empty_map)
+            }
         }
         .map_err(|err| error::fix_mark(err, mark, self.path))
     }
@@ -1783,28 +3146,45 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
                     .deserialize_enum(name, variants, visitor)
             }
             Event::Scalar(scalar) => {
-                if let Some((b'!', tag)) = scalar.tag.as_ref().and_then(|tag| tag.split_first()) {
-                    if let Some(tag) = variants.iter().find(|v| v.as_bytes() == tag) {
+                if let Some(tag) = parse_tag(&scalar.tag) {
+                    // A tag that names no variant is ignored if the scalar
+                    // names one, as in `!local A`.
+                    let names_variant = || variants.iter().any(|v| v.as_bytes() == &*scalar.value);
+                    if variants.contains(&tag) || !names_variant() {
                         return visitor.visit_enum(EnumAccess {
                             de: self,
-                            name,
+                            name: Some(name),
                             tag: Some(tag),
                         });
                     }
                 }
                 visitor.visit_enum(UnitVariantAccess { de: self })
             }
-            Event::MappingStart => {
+            Event::MappingStart(mapping) => {
+                if let Some(tag) = parse_tag(&mapping.tag) {
+                    return visitor.visit_enum(EnumAccess {
+                        de: self,
+                        name: Some(name),
+                        tag: Some(tag),
+                    });
+                }
                 *self.pos += 1;
                 let value = visitor.visit_enum(EnumAccess {
                     de: self,
-                    name,
+                    name: Some(name),
                     tag: None,
                 })?;
                 self.end_mapping(1)?;
                 Ok(value)
             }
-            Event::SequenceStart => {
+            Event::SequenceStart(sequence) => {
+                if let Some(tag) = parse_tag(&sequence.tag) {
+                    return visitor.visit_enum(EnumAccess {
+                        de: self,
+                        name: Some(name),
+                        tag: Some(tag),
+                    });
+                }
                 let err = de::Error::invalid_type(Unexpected::Seq, &"string or singleton map");
                 Err(error::fix_mark(err, mark, self.path))
             }
@@ -1817,13 +3197,29 @@ impl<'de, 'document> de::Deserializer<'de> for &mut DeserializerFromEvents<'de,
     where
         V: Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        // Where the name ends, for the suggestion of an unknown field.
+        let end = self
+            .resolve_alias(*self.pos)
+            .ok()
+            .and_then(|pos| self.document.events.get(pos))
+            .map(|(_event, span)| span.end);
+        self.deserialize_str(visitor).map_err(|err| match end {
+            Some(end) => error::fix_end(err, end),
+            None => err,
+        })
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if let (Some(ignored), Some(key_pos)) = (self.ignored, self.key_pos) {
+            let mark = self.event_at(key_pos)?.1;
+            ignored.borrow_mut().push(IgnoredField {
+                path: self.path.to_path_buf(),
+                location: Location::from_mark(mark),
+            });
+        }
         self.ignore_any()?;
         visitor.visit_unit()
     }
@@ -1934,3 +3330,74 @@ where
 {
     seed.deserialize(Deserializer::from_slice(v))
 }
+
+/// Deserialize an instance of type `T` from a string of YAML text, along with
+/// every mapping value that was ignored.
+///
+/// See [`Deserializer::deserialize_with_ignored`] for which values are
+/// reported.
+pub fn from_str_with_ignored<'de, T>(s: &'de str) -> Result<(T, Vec<IgnoredField>)>
+where
+    T: Deserialize<'de>,
+{
+    Deserializer::from_str(s).deserialize_with_ignored()
+}
+
+/// Deserialize an instance of type `T` from an IO stream of YAML, along with
+/// every mapping value that was ignored.
+///
+/// See [`Deserializer::deserialize_with_ignored`] for which values are
+/// reported.
+pub fn from_reader_with_ignored<R, T>(rdr: R) -> Result<(T, Vec<IgnoredField>)>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    Deserializer::from_reader(rdr).deserialize_with_ignored()
+}
+
+/// Deserialize an instance of type `T` from bytes of YAML text, along with
+/// every mapping value that was ignored.
+///
+/// See [`Deserializer::deserialize_with_ignored`] for which values are
+/// reported.
+pub fn from_slice_with_ignored<'de, T>(v: &'de [u8]) -> Result<(T, Vec<IgnoredField>)>
+where
+    T: Deserialize<'de>,
+{
+    Deserializer::from_slice(v).deserialize_with_ignored()
+}
+
+/// Deserialize an instance of type `T` from a string of YAML text, collecting
+/// every error instead of stopping at the first one.
+///
+/// See [`Deserializer::deserialize_recovering`] for which errors are reported.
+pub fn from_str_recovering<'de, T>(s: &'de str) -> Result<T, Errors>
+where
+    T: Deserialize<'de>,
+{
+    Deserializer::from_str(s).deserialize_recovering()
+}
+
+/// Deserialize an instance of type `T` from an IO stream of YAML, collecting
+/// every error instead of stopping at the first one.
+///
+/// See [`Deserializer::deserialize_recovering`] for which errors are reported.
+pub fn from_reader_recovering<R, T>(rdr: R) -> Result<T, Errors>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    Deserializer::from_reader(rdr).deserialize_recovering()
+}
+
+/// Deserialize an instance of type `T` from bytes of YAML text, collecting
+/// every error instead of stopping at the first one.
+///
+/// See [`Deserializer::deserialize_recovering`] for which errors are reported.
+pub fn from_slice_recovering<'de, T>(v: &'de [u8]) -> Result<T, Errors>
+where
+    T: Deserialize<'de>,
+{
+    Deserializer::from_slice(v).deserialize_recovering()
+}
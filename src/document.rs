@@ -0,0 +1,755 @@
+use crate::de::{self, DeserializerOptions, Event, Progress};
+use crate::error::{self, Error, Result};
+use crate::libyaml::parser::Span;
+use crate::loader::{self, Loader};
+use crate::path::{PathBuf, Segment};
+use crate::value::Value;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Serialize;
+use std::fmt::{self, Debug, Display};
+use std::io;
+use std::ops::Range;
+use std::str::{self, FromStr};
+use std::sync::Arc;
+
+/// A YAML document that can be edited without disturbing its formatting.
+///
+/// The document keeps its source text. Values are looked up by
+/// [path](crate::path::PathBuf), and [`replace`](Document::replace),
+/// [`insert`](Document::insert) and [`remove`](Document::remove) rewrite
+/// only the text of the node they change. Comments, blank lines, quoting,
+/// indentation and the order of keys everywhere else stay byte-for-byte the
+/// same.
+///
+/// New values are serialized the way [`to_string`](crate::to_string) writes
+/// them and indented to fit where they go. Inside a flow collection like
+/// `[1, 2]` only values that fit on one line can be written.
+///
+/// Paths follow the keys and indices written in the document. Keys pulled in
+/// through a `<<` merge key cannot be edited, and neither can anything inside
+/// an alias, since that would change every other place the anchor is used.
+///
+/// ```
+/// use serde_yaml::Document;
+///
+/// let yaml = "# Server settings
+/// server:
+///   host: localhost  # overridden in production
+///   port: 8080
+/// ";
+///
+/// let mut doc: Document = yaml.parse().unwrap();
+/// assert_eq!(doc.get::<u16>("server.port").unwrap(), 8080);
+///
+/// doc.replace("server.port", &9090).unwrap();
+/// doc.insert("server.tls", &true).unwrap();
+/// assert_eq!(
+///     doc.as_str(),
+///     "# Server settings
+/// server:
+///   host: localhost  # overridden in production
+///   port: 9090
+///   tls: true
+/// ",
+/// );
+/// ```
+pub struct Document {
+    source: String,
+    /// Absent if the source contains no document at all.
+    parsed: Option<Parsed>,
+}
+
+struct Parsed {
+    events: loader::Document<'static>,
+    root: Node,
+}
+
+struct Node {
+    /// Index of the node's first event.
+    pos: usize,
+    span: Range<usize>,
+    kind: Kind,
+}
+
+enum Kind {
+    Scalar,
+    Alias,
+    Sequence { flow: bool, items: Vec<Node> },
+    Mapping { flow: bool, entries: Vec<Entry> },
+}
+
+struct Entry {
+    /// The key if it is a scalar, as it reads after unquoting.
+    key: Option<String>,
+    key_node: Node,
+    value: Node,
+}
+
+impl Node {
+    fn build(events: &[(Event, Span)], pos: &mut usize, remaining_depth: usize) -> Result<Node> {
+        let start = *pos;
+        let (event, span) = &events[start];
+        *pos += 1;
+        let nested = || {
+            if remaining_depth == 0 {
+                Err(error::recursion_limit_exceeded(span.start))
+            } else {
+                Ok(remaining_depth - 1)
+            }
+        };
+        let kind = match event {
+            Event::Alias(_) => Kind::Alias,
+            Event::Scalar(_) => Kind::Scalar,
+            Event::SequenceStart(sequence) => {
+                let remaining_depth = nested()?;
+                let mut items = Vec::new();
+                while !matches!(events[*pos].0, Event::SequenceEnd) {
+                    items.push(Node::build(events, pos, remaining_depth)?);
+                }
+                *pos += 1;
+                Kind::Sequence {
+                    flow: sequence.flow,
+                    items,
+                }
+            }
+            Event::MappingStart(mapping) => {
+                let remaining_depth = nested()?;
+                let mut entries = Vec::new();
+                while !matches!(events[*pos].0, Event::MappingEnd) {
+                    let key = match &events[*pos].0 {
+                        Event::Scalar(scalar) => {
+                            str::from_utf8(&scalar.value).ok().map(str::to_owned)
+                        }
+                        _ => None,
+                    };
+                    let key_node = Node::build(events, pos, remaining_depth)?;
+                    let value = Node::build(events, pos, remaining_depth)?;
+                    entries.push(Entry {
+                        key,
+                        key_node,
+                        value,
+                    });
+                }
+                *pos += 1;
+                Kind::Mapping {
+                    flow: mapping.flow,
+                    entries,
+                }
+            }
+            Event::SequenceEnd | Event::MappingEnd => unreachable!(),
+        };
+        Ok(Node {
+            pos: start,
+            span: span.start.index() as usize..span.end.index() as usize,
+            kind,
+        })
+    }
+
+    fn is_flow(&self) -> bool {
+        match self.kind {
+            Kind::Sequence { flow, .. } | Kind::Mapping { flow, .. } => flow,
+            Kind::Scalar | Kind::Alias => false,
+        }
+    }
+}
+
+/// Where a node sits in its parent, which decides how text replacing it has
+/// to be laid out.
+#[derive(Copy, Clone)]
+enum Slot {
+    Root,
+    /// An element of a flow collection, where only one line fits.
+    Flow,
+    /// The value of a block mapping entry whose key starts at this column.
+    MapValue {
+        key_column: usize,
+    },
+    SeqItem,
+}
+
+struct Located<'a> {
+    node: &'a Node,
+    slot: Slot,
+}
+
+impl Document {
+    fn parse(source: String) -> Result<Self> {
+        let cursor = io::Cursor::new(source.clone().into_bytes());
+        let progress = Progress::Read(Box::new(cursor));
+        let options = DeserializerOptions::new();
+        let max_depth = options.max_depth;
+        let mut loader = Loader::new(progress, options)?;
+        let events = match loader.next_document() {
+            Some(events) => events,
+            None => {
+                return Ok(Document {
+                    source,
+                    parsed: None,
+                })
+            }
+        };
+        if let Some(err) = &events.error {
+            return Err(error::shared(Arc::clone(err)));
+        }
+        if loader.next_document().is_some() {
+            return Err(error::more_than_one_document());
+        }
+        let root = Node::build(&events.events, &mut 0, max_depth)?;
+        Ok(Document {
+            source,
+            parsed: Some(Parsed { events, root }),
+        })
+    }
+
+    /// The text of the document, including every edit made so far.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the text of the document.
+    pub fn into_string(self) -> String {
+        self.source
+    }
+
+    /// The byte range of the source text taken up by the value at the path,
+    /// as a [`Spanned`](crate::Spanned) value would record it.
+    pub fn span(&self, path: &str) -> Result<Range<usize>> {
+        let path = path.parse()?;
+        Ok(self.locate(&path)?.node.span.clone())
+    }
+
+    /// Deserializes the value at the path.
+    ///
+    /// Paths in errors are relative to the value at the path.
+    pub fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let path = path.parse()?;
+        let node = self.locate(&path)?.node;
+        let parsed = self.parsed.as_ref().unwrap();
+        de::from_node(&parsed.events, node.pos)
+    }
+
+    /// Replaces the value at the path with the serialized form of `value`.
+    pub fn replace<T>(&mut self, path: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let path = path.parse()?;
+        let (text, block) = render(value)?;
+        let located = self.locate(&path)?;
+        let Range { mut start, mut end } = located.node.span.clone();
+        let source = &self.source;
+        let mut empty = start == end && preceded_by_indicator(source, start);
+        if empty && source[start..].starts_with(' ') {
+            start += 1;
+            end = start;
+            empty = false;
+        }
+        let column = column(source, start) + empty as usize;
+        let replacement = match located.slot {
+            Slot::Flow if block || text.contains('\n') => {
+                return Err(Error::custom(format_args!(
+                    "cannot write a value that needs block style inside the flow collection at `{}`",
+                    path,
+                )));
+            }
+            Slot::MapValue { key_column } if block && !own_line(source, start) => {
+                start = source[..start].trim_end_matches(' ').len();
+                let indent = key_column + 2;
+                format!(
+                    "\n{:indent$}{}",
+                    "",
+                    indent_lines(&text, indent),
+                    indent = indent
+                )
+            }
+            Slot::MapValue { key_column } if !own_line(source, start) => {
+                let text = indent_lines(&text, key_column);
+                if empty {
+                    format!(" {}", text)
+                } else {
+                    text
+                }
+            }
+            Slot::Root | Slot::Flow | Slot::MapValue { .. } | Slot::SeqItem => {
+                let text = indent_lines(&text, column);
+                if empty {
+                    format!(" {}", text)
+                } else {
+                    text
+                }
+            }
+        };
+        self.splice(start..end, &replacement)
+    }
+
+    /// Adds a value to a mapping or sequence. The last step of the path is
+    /// either a key that the mapping does not have yet, which is added after
+    /// the mapping's last entry, or the index in the sequence to insert the
+    /// value at, shifting the elements from that index on. An index equal to
+    /// the length of the sequence appends to it.
+    pub fn insert<T>(&mut self, path: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut path: PathBuf = path.parse()?;
+        let (text, block) = render(value)?;
+        let last = pop_step(&mut path)?;
+        let parent = self.locate(&path)?.node;
+        let source = &self.source;
+        let flow_text = || -> Result<()> {
+            if block || text.contains('\n') {
+                Err(Error::custom(format_args!(
+                    "cannot write a value that needs block style inside the flow collection at `{}`",
+                    path,
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        let (at, insertion) = match (&parent.kind, last) {
+            (Kind::Mapping { flow, entries }, Segment::Key(key)) => {
+                if entries.iter().any(|entry| entry.key.as_ref() == Some(&key)) {
+                    path.push(Segment::Key(key));
+                    return Err(Error::custom(format_args!(
+                        "there is already a value at `{}`",
+                        path,
+                    )));
+                }
+                let key = render(&key)?.0;
+                if *flow {
+                    flow_text()?;
+                    match entries.last() {
+                        Some(last) => (last.value.span.end, format!(", {}: {}", key, text)),
+                        None => (parent.span.end - 1, format!("{}: {}", key, text)),
+                    }
+                } else {
+                    let key_column = column(source, entries[0].key_node.span.start);
+                    let entry = if block {
+                        let indent = key_column + 2;
+                        format!(
+                            "{}:\n{:indent$}{}",
+                            key,
+                            "",
+                            indent_lines(&text, indent),
+                            indent = indent,
+                        )
+                    } else {
+                        format!("{}: {}", key, indent_lines(&text, key_column))
+                    };
+                    let at = last_line_end(source, &entries[entries.len() - 1].value);
+                    (
+                        at,
+                        format!("\n{:indent$}{}", "", entry, indent = key_column),
+                    )
+                }
+            }
+            (Kind::Sequence { flow, items }, Segment::Index(index)) => {
+                if index > items.len() {
+                    return Err(Error::custom(format_args!(
+                        "cannot insert at index {} of the sequence at `{}` of length {}",
+                        index,
+                        path,
+                        items.len(),
+                    )));
+                }
+                if *flow {
+                    flow_text()?;
+                    match items.get(index) {
+                        Some(item) => (item.span.start, format!("{}, ", text)),
+                        None => match items.last() {
+                            Some(last) => (last.span.end, format!(", {}", text)),
+                            None => (parent.span.end - 1, text),
+                        },
+                    }
+                } else {
+                    let dash = dash(source, &items[0])?;
+                    let dash_column = column(source, dash);
+                    let item = indent_lines(&text, dash_column + 2);
+                    match items.get(index) {
+                        Some(item_node) => {
+                            let at = self::dash(source, item_node)?;
+                            let insertion =
+                                format!("- {}\n{:indent$}", item, "", indent = dash_column,);
+                            (at, insertion)
+                        }
+                        None => {
+                            let at = last_line_end(source, &items[items.len() - 1]);
+                            let insertion =
+                                format!("\n{:indent$}- {}", "", item, indent = dash_column);
+                            (at, insertion)
+                        }
+                    }
+                }
+            }
+            (Kind::Mapping { .. }, _) => {
+                return Err(Error::custom(format_args!(
+                    "expected a key to insert into the mapping at `{}`",
+                    path,
+                )));
+            }
+            (Kind::Sequence { .. }, _) => {
+                return Err(Error::custom(format_args!(
+                    "expected an index to insert into the sequence at `{}`",
+                    path,
+                )));
+            }
+            (Kind::Alias, _) => {
+                return Err(Error::custom(format_args!(
+                    "cannot edit the node of the alias at `{}`",
+                    path,
+                )));
+            }
+            (Kind::Scalar, _) => {
+                return Err(Error::custom(format_args!(
+                    "the value at `{}` is not a mapping or sequence",
+                    path,
+                )));
+            }
+        };
+        self.splice(at..at, &insertion)
+    }
+
+    /// Removes the value at the path from its mapping, along with its key, or
+    /// from its sequence. The lines it took up are removed along with any
+    /// comment at the end of its last line.
+    pub fn remove(&mut self, path: &str) -> Result<()> {
+        let mut path: PathBuf = path.parse()?;
+        let last = pop_step(&mut path)?;
+        let parent = self.locate(&path)?.node;
+        let source = &self.source;
+        // The span of every element of the parent, with the one to remove.
+        let (elements, index): (Vec<Range<usize>>, usize) = match (&parent.kind, &last) {
+            (Kind::Mapping { entries, .. }, Segment::Key(key)) => {
+                let index = entries
+                    .iter()
+                    .position(|entry| entry.key.as_ref() == Some(key));
+                let elements = entries
+                    .iter()
+                    .map(|entry| entry.key_node.span.start..entry.value.span.end)
+                    .collect();
+                (elements, index.ok_or_else(|| missing(&path, &last))?)
+            }
+            (Kind::Sequence { flow, items }, Segment::Index(index)) if *index < items.len() => {
+                let mut elements = Vec::new();
+                for item in items {
+                    let start = if *flow {
+                        item.span.start
+                    } else {
+                        dash(source, item)?
+                    };
+                    elements.push(start..item.span.end);
+                }
+                (elements, *index)
+            }
+            _ => return Err(missing(&path, &last)),
+        };
+
+        let removed = if elements.len() == 1 {
+            let empty = match parent.kind {
+                Kind::Mapping { .. } => "{}",
+                _ => "[]",
+            };
+            return self.splice(parent.span.clone(), empty);
+        } else if parent.is_flow() {
+            match elements.get(index + 1) {
+                Some(next) => elements[index].start..next.start,
+                None => elements[index - 1].end..elements[index].end,
+            }
+        } else if own_line(source, elements[index].start) {
+            let end = next_line_start(source, line_end(source, elements[index].end));
+            line_start(source, elements[index].start)..end
+        } else {
+            elements[index].start..elements[index + 1].start
+        };
+        self.splice(removed, "")
+    }
+
+    fn locate(&self, path: &PathBuf) -> Result<Located<'_>> {
+        let mut node = match &self.parsed {
+            Some(parsed) => &parsed.root,
+            None => {
+                return Err(Error::custom(format_args!(
+                    "no value at `{}`, the document is empty",
+                    path,
+                )))
+            }
+        };
+        let mut slot = Slot::Root;
+        let mut walked = PathBuf::new();
+        for segment in path.segments() {
+            if let Kind::Alias = node.kind {
+                return Err(Error::custom(format_args!(
+                    "cannot look up `{}` through the alias at `{}`",
+                    path, walked,
+                )));
+            }
+            let parent = node;
+            node = match (&parent.kind, segment) {
+                (_, Segment::Alias) => continue,
+                (Kind::Mapping { entries, .. }, Segment::Key(key)) => {
+                    let entry = entries
+                        .iter()
+                        .find(|entry| entry.key.as_ref() == Some(key))
+                        .ok_or_else(|| missing(&walked, segment))?;
+                    slot = Slot::MapValue {
+                        key_column: column(&self.source, entry.key_node.span.start),
+                    };
+                    &entry.value
+                }
+                (Kind::Sequence { items, .. }, Segment::Index(index)) => {
+                    slot = Slot::SeqItem;
+                    items.get(*index).ok_or_else(|| missing(&walked, segment))?
+                }
+                _ => return Err(missing(&walked, segment)),
+            };
+            if parent.is_flow() {
+                slot = Slot::Flow;
+            }
+            walked.push(segment.clone());
+        }
+        Ok(Located { node, slot })
+    }
+
+    // Replaces a range of the source text, keeping the document unchanged if
+    // the result does not parse. Line breaks in the replacement are written
+    // the way the document writes them.
+    fn splice(&mut self, range: Range<usize>, replacement: &str) -> Result<()> {
+        let mut source = self.source.clone();
+        if uses_crlf(&source) {
+            source.replace_range(range, &replacement.replace('\n', "\r\n"));
+        } else {
+            source.replace_range(range, replacement);
+        }
+        match Document::parse(source) {
+            Ok(document) => {
+                *self = document;
+                Ok(())
+            }
+            Err(err) => Err(Error::custom(format_args!(
+                "the edit would make the document invalid: {}",
+                err,
+            ))),
+        }
+    }
+}
+
+fn missing(parent: &PathBuf, segment: &Segment) -> Error {
+    let mut path = parent.clone();
+    path.push(segment.clone());
+    Error::custom(format_args!("no value at `{}`", path))
+}
+
+fn pop_step(path: &mut PathBuf) -> Result<Segment> {
+    loop {
+        match path.pop() {
+            Some(Segment::Alias) => continue,
+            Some(segment) => return Ok(segment),
+            None => return Err(Error::custom("expected a path below the root")),
+        }
+    }
+}
+
+// The serialized form of a value without its trailing line break, and
+// whether it is a block sequence or mapping. Those cannot follow a mapping key
+// on the same line, and like block scalars they cannot go inside a flow
+// collection.
+fn render<T>(value: &T) -> Result<(String, bool)>
+where
+    T: ?Sized + Serialize,
+{
+    let mut text = crate::to_string(value)?;
+    if text.ends_with('\n') {
+        text.pop();
+    }
+    let block = match crate::to_value(value)? {
+        Value::Sequence(sequence) => !sequence.is_empty(),
+        Value::Mapping(mapping) => !mapping.is_empty(),
+        _ => false,
+    };
+    Ok((text, block))
+}
+
+// Indents every line but the first by the given number of spaces, leaving
+// empty lines empty.
+fn indent_lines(text: &str, indent: usize) -> String {
+    let mut indented = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            indented.push('\n');
+            if !line.is_empty() {
+                indented.extend(std::iter::repeat(' ').take(indent));
+            }
+        }
+        indented.push_str(line);
+    }
+    indented
+}
+
+fn line_start(source: &str, pos: usize) -> usize {
+    source[..pos].rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+// The position of the line break ending the line that the text before `pos`
+// ends on, or the end of the source if that line is the last. A `\r\n` line
+// break starts at its `\r`.
+fn line_end(source: &str, pos: usize) -> usize {
+    let end = if source[..pos].ends_with('\n') {
+        pos - 1
+    } else {
+        source[pos..]
+            .find('\n')
+            .map_or(source.len(), |newline| pos + newline)
+    };
+    if source[..end].ends_with('\r') && source[end..].starts_with('\n') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+// The start of the line after the line break at `line_end`, or the end of the
+// source if there is none.
+fn next_line_start(source: &str, line_end: usize) -> usize {
+    source[line_end..]
+        .find('\n')
+        .map_or(source.len(), |newline| line_end + newline + 1)
+}
+
+// The position of the line break ending the last line of a node's text. The
+// blank lines after a block scalar with the `+` chomping indicator, like `|+`,
+// are part of its value, and so of the text of a block collection ending in
+// one, even though they are outside of the node's span.
+fn last_line_end(source: &str, node: &Node) -> usize {
+    let end = line_end(source, node.span.end);
+    let last = match &node.kind {
+        Kind::Sequence { flow: false, items } => items.last(),
+        Kind::Mapping {
+            flow: false,
+            entries,
+        } => entries.last().map(|entry| &entry.value),
+        Kind::Scalar if keeps_trailing_lines(source, node) => {
+            let mut end = end;
+            loop {
+                let start = next_line_start(source, end);
+                if start == source.len() {
+                    return end;
+                }
+                let line = source[start..].split('\n').next().unwrap_or_default();
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                if !line.chars().all(|ch| ch == ' ' || ch == '\t') {
+                    return end;
+                }
+                end = start + line.len();
+            }
+        }
+        _ => None,
+    };
+    match last {
+        Some(last) => end.max(last_line_end(source, last)),
+        None => end,
+    }
+}
+
+// Whether the node is a block scalar with the `+` chomping indicator, whose
+// value keeps the blank lines that follow it.
+fn keeps_trailing_lines(source: &str, node: &Node) -> bool {
+    let header = source[node.span.start..].lines().next().unwrap_or_default();
+    header
+        .split_whitespace()
+        .find(|token| !token.starts_with(&['!', '&'][..]))
+        .map_or(false, |indicator| {
+            indicator.starts_with(&['|', '>'][..]) && indicator.contains('+')
+        })
+}
+
+// Whether the document's lines end in `\r\n` rather than `\n`.
+fn uses_crlf(source: &str) -> bool {
+    source
+        .find('\n')
+        .map_or(false, |newline| source[..newline].ends_with('\r'))
+}
+
+fn column(source: &str, pos: usize) -> usize {
+    source[line_start(source, pos)..pos].chars().count()
+}
+
+// Whether only indentation comes before `pos` on its line.
+fn own_line(source: &str, pos: usize) -> bool {
+    source[line_start(source, pos)..pos]
+        .chars()
+        .all(|ch| ch == ' ' || ch == '\t')
+}
+
+// An empty node, the implicit null of `key:` or `-`, is placed right after
+// the indicator and needs a space before any value written there.
+fn preceded_by_indicator(source: &str, pos: usize) -> bool {
+    source[..pos].ends_with(&[':', '-'][..])
+}
+
+// The position of the `-` in front of an item of a block sequence.
+fn dash(source: &str, item: &Node) -> Result<usize> {
+    let before = source[..item.span.start].trim_end_matches(&[' ', '\t', '\r', '\n'][..]);
+    match before.strip_suffix('-') {
+        Some(before) => Ok(before.len()),
+        None => Err(Error::custom(
+            "cannot find the `-` in front of the sequence item",
+        )),
+    }
+}
+
+/// The byte range of the source text taken up by the value at the path, as a
+/// [`Spanned`](crate::Spanned) value would record it.
+///
+/// ```
+/// let yaml = "dependencies:\n  serde: 1.0.100\n";
+/// let span = serde_yaml::span_at_path(yaml, "dependencies.serde").unwrap();
+/// assert_eq!(&yaml[span], "1.0.100");
+/// ```
+pub fn span_at_path(source: &str, path: &str) -> Result<Range<usize>> {
+    source.parse::<Document>()?.span(path)
+}
+
+/// Returns the source text with the value at the path replaced by the
+/// serialized form of `value`, indented to fit where it goes. The rest of the
+/// text is unchanged. See [`Document`] for making several edits.
+///
+/// ```
+/// let yaml = "dependencies:\n  serde: 1.0.100  # pinned\n";
+/// let bumped = serde_yaml::replace_at_path(yaml, "dependencies.serde", "1.0.101").unwrap();
+/// assert_eq!(bumped, "dependencies:\n  serde: 1.0.101  # pinned\n");
+/// ```
+pub fn replace_at_path<T>(source: &str, path: &str, value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut document: Document = source.parse()?;
+    document.replace(path, value)?;
+    Ok(document.into_string())
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    /// Parses a document, rejecting input with more than one document.
+    fn from_str(s: &str) -> Result<Self> {
+        Document::parse(s.to_owned())
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.source)
+    }
+}
+
+impl Debug for Document {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_tuple("Document")
+            .field(&self.source)
+            .finish()
+    }
+}
@@ -0,0 +1,238 @@
+use crate::error::{self, Result};
+use crate::libyaml::error::Mark;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
+/// The character encoding of YAML input.
+///
+/// By default the deserializer detects the encoding from the byte order mark
+/// at the start of the input, or in its absence from the pattern of null bytes
+/// in the first few bytes as described by the YAML specification, and assumes
+/// UTF-8 otherwise. Input that is not UTF-8 is transcoded to UTF-8 before
+/// parsing.
+///
+/// Positions reported for non-UTF-8 input, such as the
+/// [`Location`](crate::Location) of an error or the span of a
+/// [`Spanned`](crate::Spanned) value, still refer to byte offsets in the
+/// original input, counting the byte order mark if there is one. Lines and
+/// columns count characters and are the same whatever the encoding.
+///
+/// ```
+/// use serde_yaml::Spanned;
+/// use std::collections::BTreeMap;
+///
+/// // "k: v\n" in UTF-16LE with a byte order mark.
+/// let mut input = vec![0xFF, 0xFE];
+/// for unit in "k: v\n".encode_utf16() {
+///     input.extend_from_slice(&unit.to_le_bytes());
+/// }
+///
+/// let map: BTreeMap<String, Spanned<String>> = serde_yaml::from_slice(&input).unwrap();
+/// assert_eq!(map["k"].value, "v");
+/// assert_eq!(map["k"].span(), 8..10);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8.
+    Utf8,
+    /// UTF-16, little endian.
+    Utf16Le,
+    /// UTF-16, big endian.
+    Utf16Be,
+    /// UTF-32, little endian.
+    Utf32Le,
+    /// UTF-32, big endian.
+    Utf32Be,
+}
+
+impl Encoding {
+    /// Detects the encoding of the input and the length of its byte order
+    /// mark, per section 5.2 of the YAML 1.2 specification.
+    fn detect(input: &[u8]) -> (Encoding, usize) {
+        match input {
+            [0x00, 0x00, 0xFE, 0xFF, ..] => (Encoding::Utf32Be, 4),
+            [0x00, 0x00, 0x00, _, ..] => (Encoding::Utf32Be, 0),
+            [0xFF, 0xFE, 0x00, 0x00, ..] => (Encoding::Utf32Le, 4),
+            [_, 0x00, 0x00, 0x00, ..] => (Encoding::Utf32Le, 0),
+            [0xFE, 0xFF, ..] => (Encoding::Utf16Be, 2),
+            [0x00, _, ..] => (Encoding::Utf16Be, 0),
+            [0xFF, 0xFE, ..] => (Encoding::Utf16Le, 2),
+            [_, 0x00, ..] => (Encoding::Utf16Le, 0),
+            _ => (Encoding::Utf8, Encoding::Utf8.bom_len(input)),
+        }
+    }
+
+    /// The length of this encoding's byte order mark if the input starts with
+    /// one, otherwise 0.
+    fn bom_len(self, input: &[u8]) -> usize {
+        let bom: &[u8] = match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Utf32Le => &[0xFF, 0xFE, 0x00, 0x00],
+            Encoding::Utf32Be => &[0x00, 0x00, 0xFE, 0xFF],
+        };
+        if input.starts_with(bom) {
+            bom.len()
+        } else {
+            0
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf32Le => "UTF-32LE",
+            Encoding::Utf32Be => "UTF-32BE",
+        })
+    }
+}
+
+/// Maps byte offsets in the UTF-8 text handed to libyaml back to byte offsets
+/// in the original input.
+pub(crate) struct Offsets {
+    /// Runs of characters that have the same width in both encodings, in
+    /// order. Offsets before the first run are shifted by the byte order mark.
+    runs: Vec<Run>,
+    bom_len: usize,
+}
+
+struct Run {
+    utf8: usize,
+    original: usize,
+    utf8_width: usize,
+    original_width: usize,
+}
+
+impl Offsets {
+    fn new(bom_len: usize) -> Self {
+        Offsets {
+            runs: Vec::new(),
+            bom_len,
+        }
+    }
+
+    fn push(&mut self, utf8: usize, original: usize, utf8_width: usize, original_width: usize) {
+        if let Some(last) = self.runs.last() {
+            if last.utf8_width == utf8_width && last.original_width == original_width {
+                return;
+            }
+        }
+        self.runs.push(Run {
+            utf8,
+            original,
+            utf8_width,
+            original_width,
+        });
+    }
+
+    pub fn original(&self, utf8: u64) -> u64 {
+        let utf8 = utf8 as usize;
+        let original = match self.runs.partition_point(|run| run.utf8 <= utf8) {
+            0 => self.bom_len + utf8,
+            n => {
+                let run = &self.runs[n - 1];
+                run.original + (utf8 - run.utf8) / run.utf8_width * run.original_width
+            }
+        };
+        original as u64
+    }
+
+    pub fn original_mark(&self, mark: Mark) -> Mark {
+        Mark::new(self.original(mark.index()), mark.line(), mark.column())
+    }
+}
+
+/// Converts the input to UTF-8 without a byte order mark, the form libyaml is
+/// given, along with the offsets into the original input if they differ.
+pub(crate) fn decode(
+    input: Cow<[u8]>,
+    encoding: Option<Encoding>,
+) -> Result<(Cow<[u8]>, Option<Offsets>)> {
+    let (encoding, bom_len) = match encoding {
+        Some(encoding) => (encoding, encoding.bom_len(&input)),
+        None => Encoding::detect(&input),
+    };
+    if encoding == Encoding::Utf8 {
+        if bom_len == 0 {
+            return Ok((input, None));
+        }
+        let input = match input {
+            Cow::Borrowed(input) => Cow::Borrowed(&input[bom_len..]),
+            Cow::Owned(mut input) => {
+                input.drain(..bom_len);
+                Cow::Owned(input)
+            }
+        };
+        let mut offsets = Offsets::new(bom_len);
+        offsets.push(0, bom_len, 1, 1);
+        return Ok((input, Some(offsets)));
+    }
+
+    let unit_len = match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => 2,
+        _ => 4,
+    };
+    let mut utf8 = String::with_capacity(input.len() / unit_len);
+    let mut offsets = Offsets::new(bom_len);
+    let mut pos = bom_len;
+    let mut line = 0;
+    let mut column = 0;
+    while pos < input.len() {
+        let (ch, width) = match decode_char(&input[pos..], encoding) {
+            Some(decoded) => decoded,
+            None => {
+                let mark = Mark::new(pos as u64, line, column);
+                return Err(error::invalid_encoding(encoding, mark));
+            }
+        };
+        offsets.push(utf8.len(), pos, ch.len_utf8(), width);
+        utf8.push(ch);
+        pos += width;
+        if ch == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += 1;
+        }
+    }
+    Ok((Cow::Owned(utf8.into_bytes()), Some(offsets)))
+}
+
+/// Decodes the character at the start of `input` and returns it with the
+/// number of bytes it takes up.
+fn decode_char(input: &[u8], encoding: Encoding) -> Option<(char, usize)> {
+    let unit = |i: usize| -> Option<u16> {
+        let bytes = [*input.get(i)?, *input.get(i + 1)?];
+        Some(match encoding {
+            Encoding::Utf16Le => u16::from_le_bytes(bytes),
+            _ => u16::from_be_bytes(bytes),
+        })
+    };
+    match encoding {
+        Encoding::Utf8 => unreachable!(),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let first = unit(0)?;
+            match char::decode_utf16([first]).next()? {
+                Ok(ch) => Some((ch, 2)),
+                Err(_) => {
+                    let units = [first, unit(2)?];
+                    let ch = char::decode_utf16(units).next()?.ok()?;
+                    Some((ch, 4))
+                }
+            }
+        }
+        Encoding::Utf32Le | Encoding::Utf32Be => {
+            let bytes: [u8; 4] = input.get(..4)?.try_into().ok()?;
+            let scalar = match encoding {
+                Encoding::Utf32Le => u32::from_le_bytes(bytes),
+                _ => u32::from_be_bytes(bytes),
+            };
+            Some((char::from_u32(scalar)?, 4))
+        }
+    }
+}
@@ -1,9 +1,13 @@
+use crate::encoding::Encoding;
 use crate::libyaml::{emitter, error as libyaml};
-use crate::path::Path;
+use crate::path::{Path, PathBuf};
+use crate::snippet;
 use serde::{de, ser};
 use std::error;
 use std::fmt::{self, Debug, Display};
 use std::io;
+use std::mem;
+use std::ops::Range;
 use std::result;
 use std::string;
 use std::sync::Arc;
@@ -11,6 +15,11 @@ use std::sync::Arc;
 /// An error that happened serializing or deserializing YAML data.
 pub struct Error(Box<ErrorImpl>);
 
+/// Every error found by
+/// [`Deserializer::deserialize_recovering`](crate::Deserializer::deserialize_recovering),
+/// in the order they were found.
+pub struct Errors(Vec<Error>);
+
 /// Alias for a `Result` with the error type `serde_yaml::Error`.
 pub type Result<T> = result::Result<T, Error>;
 
@@ -25,19 +34,43 @@ pub(crate) enum ErrorImpl {
     EndOfStream,
     MoreThanOneDocument,
     RecursionLimitExceeded(libyaml::Mark),
+    AliasExpansionLimitExceeded(libyaml::Mark),
+    DocumentSizeLimitExceeded(libyaml::Mark),
+    DocumentCountLimitExceeded(libyaml::Mark),
     UnknownAnchor(libyaml::Mark),
+    InvalidEncoding(Encoding, libyaml::Mark),
+    /// A duplicated mapping key, the location of its first occurrence, and
+    /// the location and path of the duplicate.
+    DuplicateKey(String, libyaml::Mark, Pos),
+    /// An unknown struct field or enum variant: serde's message, the closest
+    /// expected name if one is close enough to be a likely misspelling, the
+    /// location and path of the unknown name, and the end of it in the input.
+    UnknownName(
+        String,
+        Option<&'static str>,
+        Option<Pos>,
+        Option<libyaml::Mark>,
+    ),
+    /// A message about the node at this path within a `Value` that was not
+    /// read from any input, so has no location.
+    ValueMessage(String, PathBuf),
+    SerializeNestedTag,
+    SerializeAnchorInTag,
+    SerializeDecoratedAlias,
 
     Shared(Arc<ErrorImpl>),
+    /// An error in the document at this index of a multi-document stream.
+    InDocument(usize, Box<ErrorImpl>),
 }
 
 #[derive(Debug)]
 pub(crate) struct Pos {
     mark: libyaml::Mark,
-    path: String,
+    path: PathBuf,
 }
 
 /// The input location that an error occured.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Location {
     index: usize,
     line: usize,
@@ -45,6 +78,15 @@ pub struct Location {
 }
 
 impl Location {
+    /// Create a location from a byte index and a 1-based line and column.
+    pub fn new(index: usize, line: usize, column: usize) -> Self {
+        Location {
+            index,
+            line,
+            column,
+        }
+    }
+
     /// The byte index of the error
     pub fn index(&self) -> usize {
         self.index
@@ -62,7 +104,7 @@ impl Location {
 
     // This is to keep decoupled with the yaml crate
     #[doc(hidden)]
-    fn from_mark(mark: libyaml::Mark) -> Self {
+    pub(crate) fn from_mark(mark: libyaml::Mark) -> Self {
         Location {
             index: mark.index() as usize,
             // `line` and `column` returned from libyaml are 0-indexed but all error messages add +1 to this value
@@ -70,6 +112,46 @@ impl Location {
             column: mark.column() as usize + 1,
         }
     }
+
+    fn to_mark(self) -> libyaml::Mark {
+        libyaml::Mark::new(
+            self.index as u64,
+            self.line.saturating_sub(1) as u64,
+            self.column.saturating_sub(1) as u64,
+        )
+    }
+}
+
+/// A correction for the misspelled name of a struct field or enum variant,
+/// offered by [`Error::suggestion`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    name: &'static str,
+    start: Location,
+    end: Location,
+}
+
+impl Suggestion {
+    /// The expected name closest to the one in the input.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Location of the first character of the misspelled name.
+    pub fn start(&self) -> Location {
+        self.start
+    }
+
+    /// Location just past the last character of the misspelled name.
+    pub fn end(&self) -> Location {
+        self.end
+    }
+
+    /// Byte range of the misspelled name in the input. Replacing it with
+    /// [`name`](Suggestion::name) fixes the error.
+    pub fn span(&self) -> Range<usize> {
+        self.start.index..self.end.index
+    }
 }
 
 impl Error {
@@ -95,9 +177,104 @@ impl Error {
     }
 
     /// Returns the path from the error if one exists
-    pub fn path(&self) -> Option<&String> {
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.0.path()
+    }
+
+    /// For an unknown struct field or enum variant, the expected name that
+    /// is the closest to it, if any is close enough to be a likely
+    /// misspelling, along with where the unknown name is in the input.
+    ///
+    /// For a variant given as a tag, like `!Variant`, the span covers the
+    /// name at the end of the tag as written, or the whole node when a
+    /// `%TAG` directive gives part of the name.
+    ///
+    /// ```
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// #[serde(deny_unknown_fields)]
+    /// struct Server {
+    ///     host: String,
+    ///     timeout: u64,
+    /// }
+    ///
+    /// let yaml = "host: example.com\ntimeuot: 30\n";
+    /// let err = serde_yaml::from_str::<Server>(yaml).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "unknown field `timeuot`, expected `host` or `timeout` at line 2 column 1, did you mean `timeout`?",
+    /// );
+    ///
+    /// let suggestion = err.suggestion().unwrap();
+    /// assert_eq!(suggestion.name(), "timeout");
+    /// assert_eq!(&yaml[suggestion.span()], "timeuot");
+    /// ```
+    pub fn suggestion(&self) -> Option<Suggestion> {
+        self.0.suggestion()
+    }
+
+    /// Renders the error followed by the lines of `source` it refers to, with
+    /// a caret under each position it mentions. `source` is the YAML text the
+    /// error came from. Errors without a location render the same as with
+    /// `Display`.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let yaml = "workers: 4\nreplicas: many\n";
+    /// let err = serde_yaml::from_str::<BTreeMap<String, u32>>(yaml).unwrap_err();
+    ///
+    /// let expected = "\
+    /// replicas: invalid type: string \"many\", expected u32 at line 2 column 11
+    ///   |
+    /// 2 | replicas: many
+    ///   |           ^";
+    /// assert_eq!(err.display_with_source(yaml).to_string(), expected);
+    /// ```
+    pub fn display_with_source<'a>(&'a self, source: &'a str) -> impl Display + 'a {
+        struct WithSource<'a> {
+            error: &'a Error,
+            source: &'a str,
+        }
+
+        impl<'a> Display for WithSource<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let locations = self.error.0.locations();
+                Display::fmt(self.error, f)?;
+                if !locations.is_empty() {
+                    f.write_str("\n")?;
+                    snippet::render(f, self.source, &locations)?;
+                }
+                Ok(())
+            }
+        }
+
+        WithSource {
+            error: self,
+            source,
+        }
+    }
+
+    /// The index, counting from 0, of the document the error happened in,
+    /// for errors from a [`Deserializer`](crate::Deserializer) produced by
+    /// iterating over a multi-document input.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_yaml::Value;
+    /// #
+    /// let input = "---\na: 1\n---\nb: [\n";
+    ///
+    /// for document in serde_yaml::Deserializer::from_str(input) {
+    ///     if let Err(err) = Value::deserialize(document) {
+    ///         assert_eq!(err.document_index(), Some(1));
+    ///     }
+    /// }
+    /// ```
+    pub fn document_index(&self) -> Option<usize> {
         match self.0.as_ref() {
-            ErrorImpl::Message(_, Some(pos)) => Some(&pos.path),
+            ErrorImpl::InDocument(index, _) => Some(*index),
             _ => None,
         }
     }
@@ -123,25 +300,120 @@ pub(crate) fn recursion_limit_exceeded(mark: libyaml::Mark) -> Error {
     Error(Box::new(ErrorImpl::RecursionLimitExceeded(mark)))
 }
 
+pub(crate) fn alias_expansion_limit_exceeded(mark: libyaml::Mark) -> Error {
+    Error(Box::new(ErrorImpl::AliasExpansionLimitExceeded(mark)))
+}
+
+pub(crate) fn document_size_limit_exceeded(mark: libyaml::Mark) -> Error {
+    Error(Box::new(ErrorImpl::DocumentSizeLimitExceeded(mark)))
+}
+
+pub(crate) fn document_count_limit_exceeded(mark: libyaml::Mark) -> Error {
+    Error(Box::new(ErrorImpl::DocumentCountLimitExceeded(mark)))
+}
+
 pub(crate) fn unknown_anchor(mark: libyaml::Mark) -> Error {
     Error(Box::new(ErrorImpl::UnknownAnchor(mark)))
 }
 
+pub(crate) fn invalid_encoding(encoding: Encoding, mark: libyaml::Mark) -> Error {
+    Error(Box::new(ErrorImpl::InvalidEncoding(encoding, mark)))
+}
+
+pub(crate) fn duplicate_key(
+    key: &str,
+    first: libyaml::Mark,
+    second: libyaml::Mark,
+    path: Path,
+) -> Error {
+    let pos = Pos {
+        mark: second,
+        path: path.to_path_buf(),
+    };
+    Error(Box::new(ErrorImpl::DuplicateKey(
+        key.to_owned(),
+        first,
+        pos,
+    )))
+}
+
+/// Gives an error about a node within a `Value` the path to that node.
+pub(crate) fn at_path(mut error: Error, path: PathBuf) -> Error {
+    if let ErrorImpl::Message(msg, None) = error.0.as_mut() {
+        *error.0 = ErrorImpl::ValueMessage(mem::take(msg), path);
+    }
+    error
+}
+
+pub(crate) fn serialize_nested_tag() -> Error {
+    Error(Box::new(ErrorImpl::SerializeNestedTag))
+}
+
+pub(crate) fn serialize_anchor_in_tag() -> Error {
+    Error(Box::new(ErrorImpl::SerializeAnchorInTag))
+}
+
+pub(crate) fn serialize_decorated_alias() -> Error {
+    Error(Box::new(ErrorImpl::SerializeDecoratedAlias))
+}
+
 pub(crate) fn shared(shared: Arc<ErrorImpl>) -> Error {
     Error(Box::new(ErrorImpl::Shared(shared)))
 }
 
+pub(crate) fn in_document(error: Error, index: usize) -> Error {
+    Error(Box::new(ErrorImpl::InDocument(index, error.0)))
+}
+
 pub(crate) fn fix_mark(mut error: Error, mark: libyaml::Mark, path: Path) -> Error {
-    if let ErrorImpl::Message(_, none @ None) = error.0.as_mut() {
+    if let ErrorImpl::Message(_, none @ None) | ErrorImpl::UnknownName(_, _, none @ None, _) =
+        error.0.as_mut()
+    {
         *none = Some(Pos {
             mark,
-            path: path.to_string(),
+            path: path.to_path_buf(),
         });
     }
     error
 }
 
+/// Records where an unknown field or variant name ends in the input, once
+/// [`fix_mark`] has recorded where it starts.
+pub(crate) fn fix_end(mut error: Error, end: libyaml::Mark) -> Error {
+    if let ErrorImpl::UnknownName(_, _, Some(_), none @ None) = error.0.as_mut() {
+        *none = Some(end);
+    }
+    error
+}
+
+pub(crate) fn fix_location(
+    mut error: Error,
+    start: Location,
+    end: Location,
+    path: &PathBuf,
+) -> Error {
+    let pos = Some(Pos {
+        mark: start.to_mark(),
+        path: path.clone(),
+    });
+    match error.0.as_mut() {
+        ErrorImpl::Message(_, none @ None) => *none = pos,
+        ErrorImpl::UnknownName(_, _, none @ None, end_mark) => {
+            *none = pos;
+            *end_mark = Some(end.to_mark());
+        }
+        _ => {}
+    }
+    error
+}
+
 impl Error {
+    /// Whether the error is about a value that does not fit the type being
+    /// deserialized, which leaves the rest of the input fit to deserialize.
+    pub(crate) fn is_recoverable(&self) -> bool {
+        self.0.is_recoverable()
+    }
+
     pub(crate) fn shared(self) -> Arc<ErrorImpl> {
         if let ErrorImpl::Shared(err) = *self.0 {
             err
@@ -151,6 +423,69 @@ impl Error {
     }
 }
 
+// There is always at least one error, so `is_empty` would always be false.
+#[allow(clippy::len_without_is_empty)]
+impl Errors {
+    pub(crate) fn new(errors: Vec<Error>) -> Self {
+        Errors(errors)
+    }
+
+    /// Iterates over the errors.
+    pub fn iter(&self) -> std::slice::Iter<'_, Error> {
+        self.0.iter()
+    }
+
+    /// The number of errors, which is at least 1.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl From<Error> for Errors {
+    fn from(err: Error) -> Self {
+        Errors(vec![err])
+    }
+}
+
+impl IntoIterator for Errors {
+    type Item = Error;
+    type IntoIter = std::vec::IntoIter<Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Errors {
+    type Item = &'a Error;
+    type IntoIter = std::slice::Iter<'a, Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl error::Error for Errors {}
+
+// One error per line.
+impl Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            Display::fmt(err, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(&self.0).finish()
+    }
+}
+
 impl From<libyaml::Error> for Error {
     fn from(err: libyaml::Error) -> Self {
         Error(Box::new(ErrorImpl::Libyaml(err)))
@@ -196,14 +531,148 @@ impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error(Box::new(ErrorImpl::Message(msg.to_string(), None)))
     }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        unknown_name("variant", variant, expected)
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        unknown_name("field", field, expected)
+    }
+}
+
+// Same message as serde's default, which is not reachable once overridden.
+fn unknown_name(kind: &str, name: &str, expected: &'static [&'static str]) -> Error {
+    let mut msg = format!("unknown {} `{}`, ", kind, name);
+    match expected {
+        [] => msg += &format!("there are no {}s", kind),
+        [only] => msg += &format!("expected `{}`", only),
+        [first, second] => msg += &format!("expected `{}` or `{}`", first, second),
+        _ => {
+            msg += "expected one of ";
+            for (i, alt) in expected.iter().enumerate() {
+                if i > 0 {
+                    msg += ", ";
+                }
+                msg += &format!("`{}`", alt);
+            }
+        }
+    }
+    let suggestion = closest(name, expected);
+    Error(Box::new(ErrorImpl::UnknownName(
+        msg, suggestion, None, None,
+    )))
+}
+
+/// The candidate within a third of the name's length in edits from it, if
+/// any, preferring one that differs only in case.
+fn closest(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    if let Some(same) = candidates.iter().find(|c| c.eq_ignore_ascii_case(name)) {
+        return Some(same);
+    }
+    let max_distance = name.chars().count().max(3) / 3;
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of
+/// adjacent characters that turn one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows i-2, i-1 and i of the distance matrix.
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = previous;
+        previous = current;
+    }
+    previous[b.len()]
 }
 
 impl ErrorImpl {
     fn location(&self) -> Option<Location> {
         match self {
-            ErrorImpl::Message(_, Some(pos)) => Some(Location::from_mark(pos.mark)),
+            ErrorImpl::Message(_, Some(pos)) | ErrorImpl::UnknownName(_, _, Some(pos), _) => {
+                Some(Location::from_mark(pos.mark))
+            }
             ErrorImpl::Libyaml(err) => Some(Location::from_mark(err.mark())),
+            ErrorImpl::RecursionLimitExceeded(mark)
+            | ErrorImpl::AliasExpansionLimitExceeded(mark)
+            | ErrorImpl::DocumentSizeLimitExceeded(mark)
+            | ErrorImpl::DocumentCountLimitExceeded(mark)
+            | ErrorImpl::UnknownAnchor(mark)
+            | ErrorImpl::InvalidEncoding(_, mark) => Some(Location::from_mark(*mark)),
+            ErrorImpl::DuplicateKey(_, _, pos) => Some(Location::from_mark(pos.mark)),
             ErrorImpl::Shared(err) => err.location(),
+            ErrorImpl::InDocument(_, err) => err.location(),
+            _ => None,
+        }
+    }
+
+    // Every location the error mentions, which may be more than the one
+    // returned by location().
+    fn locations(&self) -> Vec<Location> {
+        match self {
+            ErrorImpl::Libyaml(err) => {
+                let mut locations = vec![Location::from_mark(err.mark())];
+                locations.extend(err.context_mark().map(Location::from_mark));
+                locations
+            }
+            ErrorImpl::DuplicateKey(_, first, pos) => {
+                vec![Location::from_mark(*first), Location::from_mark(pos.mark)]
+            }
+            ErrorImpl::Shared(err) => err.locations(),
+            ErrorImpl::InDocument(_, err) => err.locations(),
+            _ => self.location().into_iter().collect(),
+        }
+    }
+
+    fn is_recoverable(&self) -> bool {
+        match self {
+            ErrorImpl::Message(..) | ErrorImpl::DuplicateKey(..) | ErrorImpl::UnknownName(..) => {
+                true
+            }
+            ErrorImpl::InDocument(_, err) => err.is_recoverable(),
+            _ => false,
+        }
+    }
+
+    fn path(&self) -> Option<&PathBuf> {
+        match self {
+            ErrorImpl::Message(_, Some(pos))
+            | ErrorImpl::DuplicateKey(_, _, pos)
+            | ErrorImpl::UnknownName(_, _, Some(pos), _) => Some(&pos.path),
+            ErrorImpl::ValueMessage(_, path) => Some(path),
+            ErrorImpl::Shared(err) => err.path(),
+            ErrorImpl::InDocument(_, err) => err.path(),
+            _ => None,
+        }
+    }
+
+    fn suggestion(&self) -> Option<Suggestion> {
+        match self {
+            ErrorImpl::UnknownName(_, Some(name), Some(pos), Some(end)) => Some(Suggestion {
+                name,
+                start: Location::from_mark(pos.mark),
+                end: Location::from_mark(*end),
+            }),
+            ErrorImpl::Shared(err) => err.suggestion(),
+            ErrorImpl::InDocument(_, err) => err.suggestion(),
             _ => None,
         }
     }
@@ -213,6 +682,7 @@ impl ErrorImpl {
             ErrorImpl::Io(err) => Some(err),
             ErrorImpl::FromUtf8(err) => Some(err),
             ErrorImpl::Shared(err) => err.source(),
+            ErrorImpl::InDocument(_, err) => err.source(),
             _ => None,
         }
     }
@@ -221,12 +691,19 @@ impl ErrorImpl {
         match self {
             ErrorImpl::Message(msg, None) => Display::fmt(msg, f),
             ErrorImpl::Message(msg, Some(Pos { mark, path })) => {
-                if path == "." {
+                if path.is_root() {
                     write!(f, "{} at {}", msg, mark)
                 } else {
                     write!(f, "{}: {} at {}", path, msg, mark)
                 }
             }
+            ErrorImpl::ValueMessage(msg, path) => {
+                if path.is_root() {
+                    Display::fmt(msg, f)
+                } else {
+                    write!(f, "{}: {}", path, msg)
+                }
+            }
             ErrorImpl::Libyaml(err) => Display::fmt(err, f),
             ErrorImpl::Io(err) => Display::fmt(err, f),
             ErrorImpl::FromUtf8(err) => Display::fmt(err, f),
@@ -237,8 +714,58 @@ impl ErrorImpl {
             ErrorImpl::RecursionLimitExceeded(mark) => {
                 write!(f, "recursion limit exceeded at {}", mark)
             }
+            ErrorImpl::AliasExpansionLimitExceeded(mark) => {
+                write!(f, "alias expansion limit exceeded at {}", mark)
+            }
+            ErrorImpl::DocumentSizeLimitExceeded(mark) => {
+                write!(f, "document size limit exceeded at {}", mark)
+            }
+            ErrorImpl::DocumentCountLimitExceeded(mark) => {
+                write!(f, "document count limit exceeded at {}", mark)
+            }
             ErrorImpl::UnknownAnchor(mark) => write!(f, "unknown anchor at {}", mark),
+            ErrorImpl::InvalidEncoding(encoding, mark) => {
+                write!(f, "invalid {} input at {}", encoding, mark)
+            }
+            ErrorImpl::DuplicateKey(key, first, Pos { mark, path }) => {
+                if !path.is_root() {
+                    write!(f, "{}: ", path)?;
+                }
+                // Unlike the Display of a Mark, which has no line or column
+                // to show for the start of the input, the first key always
+                // has one.
+                write!(
+                    f,
+                    "duplicate key `{}` at {}, first defined at line {} column {}",
+                    key,
+                    mark,
+                    first.line() + 1,
+                    first.column() + 1,
+                )
+            }
+            ErrorImpl::UnknownName(msg, suggestion, pos, _end) => {
+                match pos {
+                    None => Display::fmt(msg, f)?,
+                    Some(Pos { mark, path }) if path.is_root() => write!(f, "{} at {}", msg, mark)?,
+                    Some(Pos { mark, path }) => write!(f, "{}: {} at {}", path, msg, mark)?,
+                }
+                match suggestion {
+                    Some(name) => write!(f, ", did you mean `{}`?", name),
+                    None => Ok(()),
+                }
+            }
+            ErrorImpl::SerializeNestedTag => {
+                f.write_str("serializing a value with more than one YAML tag is not supported")
+            }
+            ErrorImpl::SerializeAnchorInTag => f.write_str(
+                "serializing an anchored value inside a tagged value is not supported, \
+                 the anchor has to be outside the tag",
+            ),
+            ErrorImpl::SerializeDecoratedAlias => {
+                f.write_str("serializing an alias with an anchor or tag is not supported")
+            }
             ErrorImpl::Shared(err) => err.display(f),
+            ErrorImpl::InDocument(_, err) => err.display(f),
         }
     }
 
@@ -253,8 +780,47 @@ impl ErrorImpl {
             ErrorImpl::RecursionLimitExceeded(mark) => {
                 f.debug_tuple("RecursionLimitExceeded").field(mark).finish()
             }
+            ErrorImpl::AliasExpansionLimitExceeded(mark) => f
+                .debug_tuple("AliasExpansionLimitExceeded")
+                .field(mark)
+                .finish(),
+            ErrorImpl::DocumentSizeLimitExceeded(mark) => f
+                .debug_tuple("DocumentSizeLimitExceeded")
+                .field(mark)
+                .finish(),
+            ErrorImpl::DocumentCountLimitExceeded(mark) => f
+                .debug_tuple("DocumentCountLimitExceeded")
+                .field(mark)
+                .finish(),
             ErrorImpl::UnknownAnchor(mark) => f.debug_tuple("UnknownAnchor").field(mark).finish(),
+            ErrorImpl::InvalidEncoding(encoding, mark) => f
+                .debug_tuple("InvalidEncoding")
+                .field(encoding)
+                .field(mark)
+                .finish(),
+            ErrorImpl::DuplicateKey(key, first, pos) => f
+                .debug_tuple("DuplicateKey")
+                .field(key)
+                .field(first)
+                .field(pos)
+                .finish(),
+            ErrorImpl::UnknownName(msg, suggestion, pos, end) => f
+                .debug_tuple("UnknownName")
+                .field(msg)
+                .field(suggestion)
+                .field(pos)
+                .field(end)
+                .finish(),
+            ErrorImpl::ValueMessage(msg, path) => f
+                .debug_tuple("ValueMessage")
+                .field(msg)
+                .field(path)
+                .finish(),
+            ErrorImpl::SerializeNestedTag => f.debug_tuple("SerializeNestedTag").finish(),
+            ErrorImpl::SerializeAnchorInTag => f.debug_tuple("SerializeAnchorInTag").finish(),
+            ErrorImpl::SerializeDecoratedAlias => f.debug_tuple("SerializeDecoratedAlias").finish(),
             ErrorImpl::Shared(err) => err.debug(f),
+            ErrorImpl::InDocument(_, err) => err.debug(f),
         }
     }
 }
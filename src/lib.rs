@@ -65,7 +65,7 @@
 //! }
 //! ```
 
-#![doc(html_root_url = "https://docs.rs/serde_yaml/0.8.26")]
+#![doc(html_root_url = "https://docs.rs/serde_yaml/0.9.0")]
 #![deny(missing_docs)]
 // Suppressed clippy_pedantic lints
 #![allow(
@@ -108,16 +108,31 @@
     clippy::must_use_candidate,
 )]
 
-pub use crate::de::{from_reader, from_slice, from_str, Deserializer};
-pub use crate::error::{Error, Location, Result};
+pub use crate::de::{
+    from_reader, from_reader_recovering, from_reader_with_ignored, from_slice,
+    from_slice_recovering, from_slice_with_ignored, from_str, from_str_recovering,
+    from_str_with_ignored, Deserializer, DeserializerOptions, DocumentInfo, IgnoredField,
+};
+pub use crate::document::{replace_at_path, span_at_path, Document};
+pub use crate::encoding::Encoding;
+pub use crate::error::{Error, Errors, Location, Result, Suggestion};
 pub use crate::ser::{to_string, to_vec, to_writer, Serializer};
 pub use crate::spanned::Spanned;
+pub use crate::timestamp::Timestamp;
 #[doc(inline)]
-pub use crate::value::{from_value, to_value, Index, Number, Sequence, Value};
+pub use crate::value::{
+    from_spanned_value, from_value, to_value, Index, Number, Sequence, SpannedValue, Value,
+};
 
 #[doc(inline)]
 pub use crate::mapping::Mapping;
 
+#[doc(inline)]
+pub use crate::path::PathBuf;
+
+#[doc(inline)]
+pub use crate::schema::Schema;
+
 /// Entry points for deserializing with pre-existing state.
 ///
 /// These functions are only exposed this way because we don't yet expose a
@@ -127,13 +142,20 @@ pub mod seed {
     pub use super::de::{from_reader_seed, from_slice_seed, from_str_seed};
 }
 
+mod base64;
 mod de;
+mod document;
+mod encoding;
 mod error;
 mod libyaml;
 mod loader;
 pub mod mapping;
 mod number;
-mod path;
+pub mod path;
+pub mod schema;
 mod ser;
+mod snippet;
 mod spanned;
+mod timestamp;
 pub mod value;
+pub mod with;
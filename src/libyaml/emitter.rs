@@ -29,17 +29,30 @@ pub(crate) enum Event<'a> {
     DocumentStart,
     DocumentEnd,
     Scalar(Scalar<'a>),
-    SequenceStart,
+    SequenceStart(Sequence),
     SequenceEnd,
-    MappingStart,
+    MappingStart(Mapping),
     MappingEnd,
+    Alias(String),
 }
 
 pub(crate) struct Scalar<'a> {
+    pub anchor: Option<String>,
+    pub tag: Option<String>,
     pub value: &'a str,
     pub style: ScalarStyle,
 }
 
+pub(crate) struct Sequence {
+    pub anchor: Option<String>,
+    pub tag: Option<String>,
+}
+
+pub(crate) struct Mapping {
+    pub anchor: Option<String>,
+    pub tag: Option<String>,
+}
+
 pub(crate) enum ScalarStyle {
     Any,
     Plain,
@@ -91,13 +104,13 @@ impl<'a> Emitter<'a> {
                     let implicit = true;
                     sys::yaml_document_end_event_initialize(sys_event, implicit)
                 }
-                Event::Scalar(scalar) => {
-                    let anchor = ptr::null();
-                    let tag = ptr::null();
+                Event::Scalar(mut scalar) => {
+                    let anchor = nul_terminated(&mut scalar.anchor);
+                    let tag = nul_terminated(&mut scalar.tag);
                     let value = scalar.value.as_ptr();
                     let length = scalar.value.len() as i32;
-                    let plain_implicit = true;
-                    let quoted_implicit = true;
+                    let plain_implicit = tag.is_null();
+                    let quoted_implicit = tag.is_null();
                     let style = match scalar.style {
                         ScalarStyle::Any => sys::YAML_ANY_SCALAR_STYLE,
                         ScalarStyle::Plain => sys::YAML_PLAIN_SCALAR_STYLE,
@@ -115,26 +128,31 @@ impl<'a> Emitter<'a> {
                         style,
                     )
                 }
-                Event::SequenceStart => {
-                    let anchor = ptr::null();
-                    let tag = ptr::null();
-                    let implicit = true;
+                Event::SequenceStart(mut sequence) => {
+                    let anchor = nul_terminated(&mut sequence.anchor);
+                    let tag = nul_terminated(&mut sequence.tag);
+                    let implicit = tag.is_null();
                     let style = sys::YAML_ANY_SEQUENCE_STYLE;
                     sys::yaml_sequence_start_event_initialize(
                         sys_event, anchor, tag, implicit, style,
                     )
                 }
                 Event::SequenceEnd => sys::yaml_sequence_end_event_initialize(sys_event),
-                Event::MappingStart => {
-                    let anchor = ptr::null();
-                    let tag = ptr::null();
-                    let implicit = true;
+                Event::MappingStart(mut mapping) => {
+                    let anchor = nul_terminated(&mut mapping.anchor);
+                    let tag = nul_terminated(&mut mapping.tag);
+                    let implicit = tag.is_null();
                     let style = sys::YAML_ANY_MAPPING_STYLE;
                     sys::yaml_mapping_start_event_initialize(
                         sys_event, anchor, tag, implicit, style,
                     )
                 }
                 Event::MappingEnd => sys::yaml_mapping_end_event_initialize(sys_event),
+                Event::Alias(name) => {
+                    let mut name = Some(name);
+                    let anchor = nul_terminated(&mut name);
+                    sys::yaml_alias_event_initialize(sys_event, anchor)
+                }
             };
             if initialize_status.fail {
                 return Err(Error::Libyaml(libyaml::Error::emit_error(emitter)));
@@ -171,6 +189,18 @@ impl<'a> Emitter<'a> {
     }
 }
 
+// libyaml copies the anchor and tag while initializing the event, so the
+// returned pointer only needs to live until then.
+fn nul_terminated(string: &mut Option<String>) -> *const u8 {
+    match string {
+        Some(string) => {
+            string.push('\0');
+            string.as_ptr()
+        }
+        None => ptr::null(),
+    }
+}
+
 unsafe fn write_handler(data: *mut c_void, buffer: *mut u8, size: u64) -> i32 {
     let data = data.cast::<EmitterPinned>();
     match io::Write::write_all(
@@ -60,6 +60,20 @@ impl Error {
     pub fn mark(&self) -> Mark {
         self.problem_mark
     }
+
+    /// Position of the construct the parser was in the middle of, for errors
+    /// that mention one.
+    pub fn context_mark(&self) -> Option<Mark> {
+        self.context.map(|_| self.context_mark)
+    }
+
+    /// Rewrites the positions in the error, for input that was transcoded
+    /// before parsing.
+    pub fn map_marks(&mut self, f: impl Fn(Mark) -> Mark) {
+        self.problem_offset = f(Mark::new(self.problem_offset, 0, 0)).index();
+        self.problem_mark = f(self.problem_mark);
+        self.context_mark = f(self.context_mark);
+    }
 }
 
 impl Display for Error {
@@ -116,6 +130,14 @@ pub(crate) struct Mark {
 }
 
 impl Mark {
+    pub fn new(index: u64, line: u64, column: u64) -> Self {
+        let mut sys = unsafe { MaybeUninit::<sys::yaml_mark_t>::zeroed().assume_init() };
+        sys.index = index;
+        sys.line = line;
+        sys.column = column;
+        Mark { sys }
+    }
+
     pub fn index(&self) -> u64 {
         self.sys.index
     }
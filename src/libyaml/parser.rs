@@ -22,8 +22,8 @@ struct ParserPinned<'input> {
 pub(crate) enum Event<'input> {
     StreamStart,
     StreamEnd,
-    DocumentStart,
-    DocumentEnd,
+    DocumentStart(DocumentStart),
+    DocumentEnd(DocumentEnd),
     Alias(Anchor),
     Scalar(Scalar<'input>),
     SequenceStart(SequenceStart),
@@ -32,9 +32,25 @@ pub(crate) enum Event<'input> {
     MappingEnd,
 }
 
+#[derive(Debug)]
+pub(crate) struct DocumentStart {
+    pub version: Option<(u32, u32)>,
+    /// Handle and prefix of each `%TAG` directive.
+    pub tags: Vec<(String, String)>,
+    pub implicit: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct DocumentEnd {
+    pub implicit: bool,
+}
+
 pub(crate) struct Scalar<'input> {
     pub anchor: Option<Anchor>,
     pub tag: Option<Tag>,
+    /// Where the suffix of the tag is written, when it is written out as it
+    /// ends the tag.
+    pub tag_span: Option<Span>,
     pub value: Box<[u8]>,
     pub style: ScalarStyle,
     pub repr: Option<&'input [u8]>,
@@ -43,11 +59,31 @@ pub(crate) struct Scalar<'input> {
 #[derive(Debug)]
 pub(crate) struct SequenceStart {
     pub anchor: Option<Anchor>,
+    pub tag: Option<Tag>,
+    /// Where the suffix of the tag is written, when it is written out as it
+    /// ends the tag.
+    pub tag_span: Option<Span>,
+    /// Whether the sequence is written in flow style, `[a, b]`.
+    pub flow: bool,
 }
 
 #[derive(Debug)]
 pub(crate) struct MappingStart {
     pub anchor: Option<Anchor>,
+    pub tag: Option<Tag>,
+    /// Where the suffix of the tag is written, when it is written out as it
+    /// ends the tag.
+    pub tag_span: Option<Span>,
+    /// Whether the mapping is written in flow style, `{k: v}`.
+    pub flow: bool,
+}
+
+/// Source positions of the first character of an event and of the character
+/// following it.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Span {
+    pub start: Mark,
+    pub end: Mark,
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
@@ -78,7 +114,7 @@ impl<'input> Parser<'input> {
         Parser { pin }
     }
 
-    pub fn next(&mut self) -> Result<(Event<'input>, Mark)> {
+    pub fn next(&mut self) -> Result<(Event<'input>, Span)> {
         let mut event = MaybeUninit::<sys::yaml_event_t>::uninit();
         unsafe {
             let parser = addr_of_mut!((*self.pin.ptr).sys);
@@ -86,12 +122,23 @@ impl<'input> Parser<'input> {
             if sys::yaml_parser_parse(parser, event).fail {
                 return Err(Error::parse_error(parser));
             }
-            let ret = convert_event(&*event, &(*self.pin.ptr).input);
-            let mark = Mark {
-                sys: (*event).start_mark,
+            let input = &(*self.pin.ptr).input;
+            let ret = convert_event(&*event, input);
+            let start = (*event).start_mark;
+            let mut end = (*event).end_mark;
+            if let Event::Scalar(Scalar {
+                style: ScalarStyle::Literal | ScalarStyle::Folded,
+                ..
+            }) = ret
+            {
+                end = trim_block_scalar_end(input, start, end);
+            }
+            let span = Span {
+                start: Mark { sys: start },
+                end: Mark { sys: end },
             };
             sys::yaml_event_delete(event);
-            Ok((ret, mark))
+            Ok((ret, span))
         }
     }
 }
@@ -103,12 +150,32 @@ unsafe fn convert_event<'input>(
     match sys.type_ {
         sys::YAML_STREAM_START_EVENT => Event::StreamStart,
         sys::YAML_STREAM_END_EVENT => Event::StreamEnd,
-        sys::YAML_DOCUMENT_START_EVENT => Event::DocumentStart,
-        sys::YAML_DOCUMENT_END_EVENT => Event::DocumentEnd,
+        sys::YAML_DOCUMENT_START_EVENT => {
+            let data = &sys.data.document_start;
+            let version = data
+                .version_directive
+                .as_ref()
+                .map(|version| (version.major as u32, version.minor as u32));
+            let mut tags = Vec::new();
+            let mut tag = data.tag_directives.start;
+            while tag != data.tag_directives.end {
+                tags.push((string((*tag).handle), string((*tag).prefix)));
+                tag = tag.add(1);
+            }
+            Event::DocumentStart(DocumentStart {
+                version,
+                tags,
+                implicit: data.implicit,
+            })
+        }
+        sys::YAML_DOCUMENT_END_EVENT => Event::DocumentEnd(DocumentEnd {
+            implicit: sys.data.document_end.implicit,
+        }),
         sys::YAML_ALIAS_EVENT => Event::Alias(optional_anchor(sys.data.alias.anchor).unwrap()),
         sys::YAML_SCALAR_EVENT => Event::Scalar(Scalar {
             anchor: optional_anchor(sys.data.scalar.anchor),
             tag: optional_tag(sys.data.scalar.tag),
+            tag_span: tag_suffix_span(input, sys.start_mark, sys.data.scalar.tag),
             value: Box::from(slice::from_raw_parts(
                 sys.data.scalar.value,
                 sys.data.scalar.length as usize,
@@ -129,10 +196,16 @@ unsafe fn convert_event<'input>(
         }),
         sys::YAML_SEQUENCE_START_EVENT => Event::SequenceStart(SequenceStart {
             anchor: optional_anchor(sys.data.sequence_start.anchor),
+            tag: optional_tag(sys.data.sequence_start.tag),
+            tag_span: tag_suffix_span(input, sys.start_mark, sys.data.sequence_start.tag),
+            flow: sys.data.sequence_start.style == sys::YAML_FLOW_SEQUENCE_STYLE,
         }),
         sys::YAML_SEQUENCE_END_EVENT => Event::SequenceEnd,
         sys::YAML_MAPPING_START_EVENT => Event::MappingStart(MappingStart {
             anchor: optional_anchor(sys.data.mapping_start.anchor),
+            tag: optional_tag(sys.data.mapping_start.tag),
+            tag_span: tag_suffix_span(input, sys.start_mark, sys.data.mapping_start.tag),
+            flow: sys.data.mapping_start.style == sys::YAML_FLOW_MAPPING_STYLE,
         }),
         sys::YAML_MAPPING_END_EVENT => Event::MappingEnd,
         sys::YAML_NO_EVENT => unreachable!(),
@@ -140,6 +213,125 @@ unsafe fn convert_event<'input>(
     }
 }
 
+// The end mark of a block scalar is placed after any trailing line breaks and
+// the indentation of the line that follows, none of which belong to the scalar
+// as written. Move it back to just after the last line of the scalar's content.
+fn trim_block_scalar_end(
+    input: &[u8],
+    start: sys::yaml_mark_t,
+    end: sys::yaml_mark_t,
+) -> sys::yaml_mark_t {
+    let start_index = start.index as usize;
+    let mut index = end.index as usize;
+    let mut line = end.line;
+    if index > input.len() {
+        return end;
+    }
+    loop {
+        let mut blank = index;
+        while blank > start_index && matches!(input[blank - 1], b' ' | b'\t') {
+            blank -= 1;
+        }
+        if blank > start_index && input[blank - 1] == b'\n' {
+            blank -= 1;
+            if blank > start_index && input[blank - 1] == b'\r' {
+                blank -= 1;
+            }
+        } else if blank > start_index && input[blank - 1] == b'\r' {
+            blank -= 1;
+        } else {
+            break;
+        }
+        index = blank;
+        line -= 1;
+    }
+    if index == end.index as usize {
+        return end;
+    }
+    let line_start = input[..index]
+        .iter()
+        .rposition(|&b| b == b'\n' || b == b'\r')
+        .map_or(0, |i| i + 1);
+    let column = input[line_start..index]
+        .iter()
+        .filter(|&&b| b & 0xC0 != 0x80)
+        .count();
+    let mut mark = end;
+    mark.index = index as u64;
+    mark.line = line;
+    mark.column = column as u64;
+    mark
+}
+
+// Finds where the suffix of a node's tag is written: the part after the handle
+// of `!suffix`, `!!suffix` or `!handle!suffix`, or what is inside the brackets
+// of a verbatim `!<tag>`. The properties of a node, its anchor and tag in
+// either order, come first in it. A suffix that doesn't end the resolved tag
+// as written, such as one with %-escapes, is not found.
+unsafe fn tag_suffix_span(input: &[u8], start: sys::yaml_mark_t, tag: *const u8) -> Option<Span> {
+    let ptr = NonNull::new(tag as *mut i8)?;
+    let tag = CStr::from_ptr(ptr).to_bytes();
+    let is_break = |b: u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let mut index = start.index as usize;
+    loop {
+        match *input.get(index)? {
+            b'!' => break,
+            b'&' => {
+                while !is_break(*input.get(index)?) {
+                    index += 1;
+                }
+            }
+            b'#' => {
+                while !matches!(*input.get(index)?, b'\r' | b'\n') {
+                    index += 1;
+                }
+            }
+            b if is_break(b) => index += 1,
+            _ => return None,
+        }
+    }
+    let (begin, end) = if input.get(index + 1) == Some(&b'<') {
+        let begin = index + 2;
+        let end = begin + input[begin..].iter().position(|&b| b == b'>')?;
+        (begin, end)
+    } else {
+        let rest = &input[index + 1..];
+        let handle = rest
+            .iter()
+            .position(|&b| !(b.is_ascii_alphanumeric() || b == b'-'))
+            .filter(|&i| rest[i] == b'!');
+        let begin = index + 1 + handle.map_or(0, |i| i + 1);
+        let end = begin
+            + input[begin..]
+                .iter()
+                .position(|&b| is_break(b) || matches!(b, b',' | b'[' | b']' | b'{' | b'}'))
+                .unwrap_or(input.len() - begin);
+        (begin, end)
+    };
+    let written = &input[begin..end];
+    if written.contains(&b'%') || !tag.ends_with(written) {
+        return None;
+    }
+    let chars = |bytes: &[u8]| bytes.iter().filter(|&&b| b & 0xC0 != 0x80).count() as u64;
+    let skipped = &input[start.index as usize..begin];
+    let mut mark = start;
+    mark.index = begin as u64;
+    match skipped.iter().rposition(|&b| b == b'\n' || b == b'\r') {
+        Some(i) => {
+            mark.line += skipped.iter().filter(|&&b| b == b'\n').count() as u64;
+            mark.column = chars(&skipped[i + 1..]);
+        }
+        None => mark.column += chars(skipped),
+    }
+    let mut end_mark = mark;
+    end_mark.index = end as u64;
+    end_mark.column += chars(written);
+    Some(Span {
+        start: Mark { sys: mark },
+        end: Mark { sys: end_mark },
+    })
+}
+
 unsafe fn optional_anchor(anchor: *const u8) -> Option<Anchor> {
     let ptr = NonNull::new(anchor as *mut i8)?;
     let cstr = CStr::from_ptr(ptr);
@@ -152,11 +344,17 @@ unsafe fn optional_tag(tag: *const u8) -> Option<Tag> {
     Some(Tag(Box::from(cstr.to_bytes())))
 }
 
+unsafe fn string(ptr: *const u8) -> String {
+    let cstr = CStr::from_ptr(NonNull::new_unchecked(ptr as *mut i8));
+    String::from_utf8_lossy(cstr.to_bytes()).into_owned()
+}
+
 impl<'input> Debug for Scalar<'input> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         let Scalar {
             anchor,
             tag,
+            tag_span: _,
             value,
             style,
             repr: _,
@@ -180,6 +378,12 @@ impl<'input> Debug for Scalar<'input> {
     }
 }
 
+impl Anchor {
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.0).into_owned()
+    }
+}
+
 impl Debug for Anchor {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         cstr::debug_lossy(&self.0, formatter)
@@ -10,6 +10,14 @@ impl Tag {
     pub const BOOL: &'static str = "tag:yaml.org,2002:bool";
     pub const INT: &'static str = "tag:yaml.org,2002:int";
     pub const FLOAT: &'static str = "tag:yaml.org,2002:float";
+    pub const MERGE: &'static str = "tag:yaml.org,2002:merge";
+    pub const BINARY: &'static str = "tag:yaml.org,2002:binary";
+    pub const TIMESTAMP: &'static str = "tag:yaml.org,2002:timestamp";
+    pub const SET: &'static str = "tag:yaml.org,2002:set";
+    pub const OMAP: &'static str = "tag:yaml.org,2002:omap";
+    pub const PAIRS: &'static str = "tag:yaml.org,2002:pairs";
+    /// Prefix of the tags in the YAML core schema, which `!!` stands for.
+    pub const CORE_PREFIX: &'static str = "tag:yaml.org,2002:";
 }
 
 impl PartialEq<str> for Tag {
@@ -1,26 +1,45 @@
-use crate::de::{Event, Progress};
+use crate::de::{self, DeserializerOptions, DocumentInfo, Event, Progress};
+use crate::encoding::{self, Encoding, Offsets};
 use crate::error::{self, Error, ErrorImpl, Result};
-use crate::libyaml::error::Mark;
-use crate::libyaml::parser::{Event as YamlEvent, Parser};
+use crate::libyaml::parser::{Event as YamlEvent, Parser, Span};
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
 pub(crate) struct Loader<'input> {
     parser: Option<Parser<'input>>,
+    /// Present if the input was transcoded or had its byte order mark removed
+    /// before parsing, to report positions in the original input.
+    offsets: Option<Offsets>,
+    options: DeserializerOptions,
+    /// Number of documents started so far.
+    documents: usize,
 }
 
 pub(crate) struct Document<'input> {
-    pub events: Vec<(Event<'input>, Mark)>,
+    /// Events paired with the span of source text they were parsed from. For
+    /// the start event of a sequence or mapping the span covers the entire
+    /// collection.
+    pub events: Vec<(Event<'input>, Span)>,
     pub error: Option<Arc<ErrorImpl>>,
+    pub info: DocumentInfo,
     /// Map from alias id to index in events.
     pub aliases: BTreeMap<usize, usize>,
+    /// Map from index in events of an anchored node to the anchor's name.
+    pub anchors: BTreeMap<usize, String>,
+    /// Indices in events of the mappings that have a `<<` merge key.
+    pub merges: BTreeSet<usize>,
 }
 
 impl<'input> Loader<'input> {
-    pub fn new(progress: Progress<'input>) -> Result<Self> {
+    pub fn new(progress: Progress<'input>, options: DeserializerOptions) -> Result<Self> {
+        // A string is already known to be UTF-8.
+        let mut encoding = options.encoding;
         let input = match progress {
-            Progress::Str(s) => Cow::Borrowed(s.as_bytes()),
+            Progress::Str(s) => {
+                encoding = encoding.or(Some(Encoding::Utf8));
+                Cow::Borrowed(s.as_bytes())
+            }
             Progress::Slice(bytes) => Cow::Borrowed(bytes),
             Progress::Read(mut rdr) => {
                 let mut buffer = Vec::new();
@@ -31,8 +50,12 @@ impl<'input> Loader<'input> {
             Progress::Fail(err) => return Err(error::shared(err)),
         };
 
+        let (input, offsets) = encoding::decode(input, encoding)?;
         Ok(Loader {
             parser: Some(Parser::new(input)),
+            offsets,
+            options,
+            documents: 0,
         })
     }
 
@@ -46,60 +69,150 @@ impl<'input> Loader<'input> {
         let mut document = Document {
             events: Vec::new(),
             error: None,
+            info: DocumentInfo {
+                index: self.documents,
+                ..DocumentInfo::default()
+            },
             aliases: BTreeMap::new(),
+            anchors: BTreeMap::new(),
+            merges: BTreeSet::new(),
         };
+        // Indices of the start events of the collections currently open, and
+        // how many nodes each one holds so far.
+        let mut collections: Vec<(usize, usize)> = Vec::new();
+        // Byte offset at which the current document started.
+        let mut document_start = None;
 
         loop {
-            let (event, mark) = match parser.next() {
-                Ok((event, mark)) => (event, mark),
-                Err(err) => {
+            let (event, mut span) = match parser.next() {
+                Ok((event, mut span)) => {
+                    if let Some(offsets) = &self.offsets {
+                        span.start = offsets.original_mark(span.start);
+                        span.end = offsets.original_mark(span.end);
+                    }
+                    (event, span)
+                }
+                Err(mut err) => {
+                    if let Some(offsets) = &self.offsets {
+                        err.map_marks(|mark| offsets.original_mark(mark));
+                    }
                     document.error = Some(Error::from(err).shared());
+                    self.parser = None;
                     return Some(document);
                 }
             };
+            if let (Some(max), Some(start)) = (self.options.max_document_bytes, document_start) {
+                if span.end.index() - start > max as u64 {
+                    let err = error::document_size_limit_exceeded(span.start);
+                    document.error = Some(err.shared());
+                    self.parser = None;
+                    return Some(document);
+                }
+            }
             let event = match event {
                 YamlEvent::StreamStart => continue,
                 YamlEvent::StreamEnd => {
                     self.parser = None;
                     return None;
                 }
-                YamlEvent::DocumentStart => continue,
-                YamlEvent::DocumentEnd => return Some(document),
+                YamlEvent::DocumentStart(start) => {
+                    document.info.version = start.version;
+                    document.info.tags = start.tags;
+                    document.info.explicit_start = !start.implicit;
+                    self.documents += 1;
+                    if let Some(max) = self.options.max_documents {
+                        if self.documents > max {
+                            let err = error::document_count_limit_exceeded(span.start);
+                            document.error = Some(err.shared());
+                            self.parser = None;
+                            return Some(document);
+                        }
+                    }
+                    document_start = Some(span.start.index());
+                    continue;
+                }
+                YamlEvent::DocumentEnd(end) => {
+                    document.info.explicit_end = !end.implicit;
+                    return Some(document);
+                }
                 YamlEvent::Alias(alias) => match anchors.get(&alias) {
                     Some(id) => Event::Alias(*id),
                     None => {
-                        document.error = Some(error::unknown_anchor(mark).shared());
+                        document.error = Some(error::unknown_anchor(span.start).shared());
                         return Some(document);
                     }
                 },
                 YamlEvent::Scalar(mut scalar) => {
                     if let Some(anchor) = scalar.anchor.take() {
                         let id = anchors.len();
+                        let pos = document.events.len();
+                        document.anchors.insert(pos, anchor.to_string_lossy());
                         anchors.insert(anchor, id);
-                        document.aliases.insert(id, document.events.len());
+                        document.aliases.insert(id, pos);
                     }
                     Event::Scalar(scalar)
                 }
-                YamlEvent::SequenceStart(sequence_start) => {
-                    if let Some(anchor) = sequence_start.anchor {
+                YamlEvent::SequenceStart(mut sequence_start) => {
+                    if let Some(anchor) = sequence_start.anchor.take() {
                         let id = anchors.len();
+                        let pos = document.events.len();
+                        document.anchors.insert(pos, anchor.to_string_lossy());
                         anchors.insert(anchor, id);
-                        document.aliases.insert(id, document.events.len());
+                        document.aliases.insert(id, pos);
                     }
-                    Event::SequenceStart
+                    Event::SequenceStart(sequence_start)
                 }
-                YamlEvent::SequenceEnd => Event::SequenceEnd,
-                YamlEvent::MappingStart(mapping_start) => {
-                    if let Some(anchor) = mapping_start.anchor {
+                YamlEvent::SequenceEnd => {
+                    let start = collections.pop().map(|(start, _nodes)| start);
+                    document.end_collection(start, &mut span);
+                    Event::SequenceEnd
+                }
+                YamlEvent::MappingStart(mut mapping_start) => {
+                    if let Some(anchor) = mapping_start.anchor.take() {
                         let id = anchors.len();
+                        let pos = document.events.len();
+                        document.anchors.insert(pos, anchor.to_string_lossy());
                         anchors.insert(anchor, id);
-                        document.aliases.insert(id, document.events.len());
+                        document.aliases.insert(id, pos);
                     }
-                    Event::MappingStart
+                    Event::MappingStart(mapping_start)
+                }
+                YamlEvent::MappingEnd => {
+                    let start = collections.pop().map(|(start, _nodes)| start);
+                    document.end_collection(start, &mut span);
+                    Event::MappingEnd
                 }
-                YamlEvent::MappingEnd => Event::MappingEnd,
             };
-            document.events.push((event, mark));
+            if let Some((start, nodes)) = collections.last_mut() {
+                if !matches!(event, Event::SequenceEnd | Event::MappingEnd) {
+                    let in_mapping = matches!(document.events[*start].0, Event::MappingStart(_));
+                    if in_mapping && *nodes % 2 == 0 && de::is_merge_key(&event) {
+                        document.merges.insert(*start);
+                    }
+                    *nodes += 1;
+                }
+            }
+            if let Event::SequenceStart(_) | Event::MappingStart(_) = event {
+                collections.push((document.events.len(), 0));
+            }
+            document.events.push((event, span));
+        }
+    }
+}
+
+impl<'input> Document<'input> {
+    // Libyaml places the end of a block collection at the token following it,
+    // which may be several lines of comments and blank lines further on. The
+    // collection really ends where its last entry ends. Flow collections end
+    // after their closing bracket and are left as reported.
+    fn end_collection(&mut self, start: Option<usize>, span: &mut Span) {
+        if span.start.index() == span.end.index() {
+            if let Some((_, last)) = self.events.last() {
+                span.end = last.end;
+            }
+        }
+        if let Some(start) = start {
+            self.events[start].1.end = span.end;
         }
     }
 }
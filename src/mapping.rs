@@ -10,6 +10,11 @@ use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::ops::{Index, IndexMut};
 
+/// The name of the private newtype struct through which [`Mapping`] asks the
+/// YAML deserializer to reject duplicate keys. Other deserializers visit the
+/// newtype struct's content as usual.
+pub(crate) const NAME: &str = "$__serde_yaml_private_Mapping";
+
 /// A YAML mapping in which the keys and values are both `serde_yaml::Value`.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Mapping {
@@ -176,6 +181,14 @@ impl PartialOrd for Mapping {
                 (Value::String(_), _) => Ordering::Less,
                 (_, Value::String(_)) => Ordering::Greater,
 
+                (Value::Binary(a), Value::Binary(b)) => a.cmp(b),
+                (Value::Binary(_), _) => Ordering::Less,
+                (_, Value::Binary(_)) => Ordering::Greater,
+
+                (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+                (Value::Timestamp(_), _) => Ordering::Less,
+                (_, Value::Timestamp(_)) => Ordering::Greater,
+
                 (Value::Sequence(a), Value::Sequence(b)) => iter_cmp_by(a, b, total_cmp),
                 (Value::Sequence(_), _) => Ordering::Less,
                 (_, Value::Sequence(_)) => Ordering::Greater,
@@ -185,6 +198,24 @@ impl PartialOrd for Mapping {
                         total_cmp(ak, bk).then_with(|| total_cmp(av, bv))
                     })
                 }
+                (Value::Mapping(_), _) => Ordering::Less,
+                (_, Value::Mapping(_)) => Ordering::Greater,
+
+                (Value::Tagged(a), Value::Tagged(b)) => a
+                    .tag
+                    .cmp(&b.tag)
+                    .then_with(|| total_cmp(&a.value, &b.value)),
+                (Value::Tagged(_), _) => Ordering::Less,
+                (_, Value::Tagged(_)) => Ordering::Greater,
+
+                (Value::Anchored(a), Value::Anchored(b)) => a
+                    .anchor
+                    .cmp(&b.anchor)
+                    .then_with(|| total_cmp(&a.value, &b.value)),
+                (Value::Anchored(_), _) => Ordering::Less,
+                (_, Value::Anchored(_)) => Ordering::Greater,
+
+                (Value::Alias(a), Value::Alias(b)) => a.cmp(b),
             }
         }
 
@@ -505,8 +536,15 @@ impl<'de> Deserialize<'de> for Mapping {
                 }
                 Ok(values)
             }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_map(self)
+            }
         }
 
-        deserializer.deserialize_map(Visitor)
+        deserializer.deserialize_newtype_struct(NAME, Visitor)
     }
 }
@@ -1,26 +1,106 @@
+use crate::schema::{Resolved, Schema};
 use crate::Error;
+#[cfg(feature = "arbitrary_precision")]
+use serde::de::{self, MapAccess};
 use serde::de::{Unexpected, Visitor};
 use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
 use std::hash::{Hash, Hasher};
+#[cfg(feature = "arbitrary_precision")]
+use std::iter;
+use std::num::FpCategory;
+
+/// Name of the newtype struct containing the digits of an arbitrary-precision
+/// number when serializing, and the key of the single-entry map in which the
+/// deserializer hands such a number to `Value` and `Number`.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) const NAME: &str = "$__serde_yaml_private_number";
 
 /// Represents a YAML number, whether integer or floating point.
-#[derive(Clone, PartialEq, PartialOrd)]
+///
+/// With the `arbitrary_precision` feature, a number that does not fit in an
+/// `i64`, `u64` or `f64` without being rounded keeps all of its digits, and is
+/// written back out with exactly those digits. This covers integers beyond
+/// 64 bits, which convert to and from `i128` and `u128` losslessly, and
+/// decimals with more digits than an `f64` holds. Such numbers compare equal
+/// only if they have the same digits.
+///
+/// ```
+/// # #[cfg(feature = "arbitrary_precision")]
+/// # fn main() -> serde_yaml::Result<()> {
+/// let v: serde_yaml::Value = serde_yaml::from_str(r#"
+/// id: 170141183460469231731687303715884105727
+/// ratio: 0.1000000000000000055511151231257827
+/// "#)?;
+///
+/// assert_eq!(v["id"].as_i64(), None);
+/// let id: i128 = serde_yaml::from_value(v["id"].clone())?;
+/// assert_eq!(id, i128::MAX);
+/// assert_eq!(v["ratio"].as_f64(), Some(0.1));
+///
+/// let yaml = serde_yaml::to_string(&v)?;
+/// assert_eq!(yaml, "id: 170141183460469231731687303715884105727\nratio: 0.1000000000000000055511151231257827\n");
+/// # Ok(())
+/// # }
+/// #
+/// # #[cfg(not(feature = "arbitrary_precision"))]
+/// # fn main() {}
+/// ```
+///
+/// Without the feature, integers beyond 64 bits can be deserialized into
+/// `i128` and `u128` but not into a `Number`, and decimals are rounded to the
+/// nearest `f64`.
+///
+/// **Caution:** with the feature, a decimal with more digits than an `f64`
+/// holds reaches `Deserialize` impls as a private map that only `Value` and
+/// `Number` understand. Deserializing it directly into an `f64` still works,
+/// but an untagged enum buffers the map first, so a variant like `F(f64)` or
+/// `S(String)` no longer matches it. Use a `Number` variant to accept such
+/// decimals in an untagged enum.
+///
+/// A `Number` read from YAML remembers how it was written, so that `0x1F`,
+/// `0o755`, `1e3` and `+12` are written back out the same way rather than as
+/// `31`, `493`, `1000.0` and `12`, as long as the serializer's schema reads
+/// them back as the same number. The way a number is written does not take
+/// part in comparisons or hashing, and numbers built in code are written in
+/// canonical form.
+///
+/// ```
+/// # fn main() -> serde_yaml::Result<()> {
+/// let v: serde_yaml::Value = serde_yaml::from_str("mode: 0o755\nmask: 0x1F\n")?;
+///
+/// assert_eq!(v["mode"], 493);
+/// assert_eq!(v["mask"], serde_yaml::Value::from(31));
+///
+/// let yaml = serde_yaml::to_string(&v)?;
+/// assert_eq!(yaml, "mode: 0o755\nmask: 0x1F\n");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
 pub struct Number {
     n: N,
+    /// The number as written in the YAML it was read from, if that differs
+    /// from how the serializer would write it.
+    repr: Option<Box<str>>,
 }
 
 // "N" is a prefix of "NegInt"... this is a false positive.
 // https://github.com/Manishearth/rust-clippy/issues/1241
 #[allow(clippy::enum_variant_names)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 enum N {
     PosInt(u64),
     /// Always less than zero.
     NegInt(i64),
     /// May be infinite or NaN.
     Float(f64),
+    /// A number which none of the variants above holds exactly, as decimal
+    /// digits with an optional '-', fraction and exponent.
+    #[cfg(feature = "arbitrary_precision")]
+    Arbitrary(Box<str>),
 }
 
 impl Number {
@@ -56,6 +136,8 @@ impl Number {
             N::PosInt(v) => v <= i64::max_value() as u64,
             N::NegInt(_) => true,
             N::Float(_) => false,
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => false,
         }
     }
 
@@ -87,6 +169,8 @@ impl Number {
         match self.n {
             N::PosInt(_) => true,
             N::NegInt(_) | N::Float(_) => false,
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => false,
         }
     }
 
@@ -119,6 +203,8 @@ impl Number {
         match self.n {
             N::Float(_) => true,
             N::PosInt(_) | N::NegInt(_) => false,
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => !is_integer(digits),
         }
     }
 
@@ -152,6 +238,8 @@ impl Number {
             }
             N::NegInt(n) => Some(n),
             N::Float(_) => None,
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => None,
         }
     }
 
@@ -177,6 +265,8 @@ impl Number {
         match self.n {
             N::PosInt(n) => Some(n),
             N::NegInt(_) | N::Float(_) => None,
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => None,
         }
     }
 
@@ -216,6 +306,69 @@ impl Number {
             N::PosInt(n) => Some(n as f64),
             N::NegInt(n) => Some(n as f64),
             N::Float(n) => Some(n),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => digits.parse().ok(),
+        }
+    }
+
+    /// If the `Number` is an integer, represent it as i128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # fn main() -> serde_yaml::Result<()> {
+    /// let v: serde_yaml::Value = serde_yaml::from_str(r#"
+    /// a: -170141183460469231731687303715884105728
+    /// b: 170141183460469231731687303715884105728
+    /// c: 256.0
+    /// "#)?;
+    ///
+    /// let number = |key: &str| match &v[key] {
+    ///     serde_yaml::Value::Number(n) => n.clone(),
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!(number("a").as_i128(), Some(i128::MIN));
+    /// assert_eq!(number("b").as_i128(), None);
+    /// assert_eq!(number("c").as_i128(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "arbitrary_precision")]
+    pub fn as_i128(&self) -> Option<i128> {
+        match self.n {
+            N::PosInt(n) => Some(i128::from(n)),
+            N::NegInt(n) => Some(i128::from(n)),
+            N::Float(_) => None,
+            N::Arbitrary(ref digits) => digits.parse().ok(),
+        }
+    }
+
+    /// If the `Number` is an integer, represent it as u128 if possible.
+    /// Returns None otherwise.
+    ///
+    /// ```
+    /// # fn main() -> serde_yaml::Result<()> {
+    /// let v: serde_yaml::Value = serde_yaml::from_str(r#"
+    /// a: 340282366920938463463374607431768211455
+    /// b: -64
+    /// c: 256.0
+    /// "#)?;
+    ///
+    /// let number = |key: &str| match &v[key] {
+    ///     serde_yaml::Value::Number(n) => n.clone(),
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!(number("a").as_u128(), Some(u128::MAX));
+    /// assert_eq!(number("b").as_u128(), None);
+    /// assert_eq!(number("c").as_u128(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "arbitrary_precision")]
+    pub fn as_u128(&self) -> Option<u128> {
+        match self.n {
+            N::PosInt(n) => Some(u128::from(n)),
+            N::NegInt(_) | N::Float(_) => None,
+            N::Arbitrary(ref digits) => digits.parse().ok(),
         }
     }
 
@@ -239,6 +392,8 @@ impl Number {
         match self.n {
             N::PosInt(_) | N::NegInt(_) => false,
             N::Float(f) => f.is_nan(),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => false,
         }
     }
 
@@ -263,6 +418,8 @@ impl Number {
         match self.n {
             N::PosInt(_) | N::NegInt(_) => false,
             N::Float(f) => f.is_infinite(),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => false,
         }
     }
 
@@ -286,6 +443,8 @@ impl Number {
         match self.n {
             N::PosInt(_) | N::NegInt(_) => true,
             N::Float(f) => f.is_finite(),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => true,
         }
     }
 }
@@ -304,6 +463,8 @@ impl fmt::Display for Number {
                 }
             }
             N::Float(f) => Display::fmt(&f, formatter),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => formatter.write_str(digits),
         }
     }
 }
@@ -314,12 +475,26 @@ impl Debug for Number {
     }
 }
 
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.n == other.n
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        self.n.partial_cmp(&other.n)
+    }
+}
+
 impl PartialEq for N {
     fn eq(&self, other: &N) -> bool {
-        match (*self, *other) {
+        match (self, other) {
             (N::PosInt(a), N::PosInt(b)) => a == b,
             (N::NegInt(a), N::NegInt(b)) => a == b,
-            (N::Float(a), N::Float(b)) => {
+            #[cfg(feature = "arbitrary_precision")]
+            (N::Arbitrary(a), N::Arbitrary(b)) => a == b,
+            (&N::Float(a), &N::Float(b)) => {
                 if a.is_nan() && b.is_nan() {
                     // YAML only has one NaN;
                     // the bit representation isn't preserved
@@ -335,8 +510,8 @@ impl PartialEq for N {
 
 impl PartialOrd for N {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (*self, *other) {
-            (N::Float(a), N::Float(b)) => {
+        match (self, other) {
+            (&N::Float(a), &N::Float(b)) => {
                 if a.is_nan() && b.is_nan() {
                     // YAML only has one NaN
                     Some(Ordering::Equal)
@@ -351,13 +526,15 @@ impl PartialOrd for N {
 
 impl N {
     fn total_cmp(&self, other: &Self) -> Ordering {
-        match (*self, *other) {
-            (N::PosInt(a), N::PosInt(b)) => a.cmp(&b),
-            (N::NegInt(a), N::NegInt(b)) => a.cmp(&b),
+        match (self, other) {
+            #[cfg(feature = "arbitrary_precision")]
+            (N::Arbitrary(_), _) | (_, N::Arbitrary(_)) => self.arbitrary_cmp(other),
+            (N::PosInt(a), N::PosInt(b)) => a.cmp(b),
+            (N::NegInt(a), N::NegInt(b)) => a.cmp(b),
             // negint is always less than zero
             (N::NegInt(_), N::PosInt(_)) => Ordering::Less,
             (N::PosInt(_), N::NegInt(_)) => Ordering::Greater,
-            (N::Float(a), N::Float(b)) => a.partial_cmp(&b).unwrap_or_else(|| {
+            (N::Float(a), N::Float(b)) => a.partial_cmp(b).unwrap_or_else(|| {
                 // arbitrarily sort the NaN last
                 if !a.is_nan() {
                     Ordering::Less
@@ -375,10 +552,296 @@ impl N {
     }
 }
 
+#[cfg(feature = "arbitrary_precision")]
+impl N {
+    /// Orders numbers of which at least one has arbitrary precision. Like
+    /// `total_cmp`, integers sort below floats.
+    fn arbitrary_cmp(&self, other: &Self) -> Ordering {
+        match (self.integer_digits(), other.integer_digits()) {
+            (Some(a), Some(b)) => cmp_integer_digits(&a, &b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => {
+                let a = N::Float(self.to_f64());
+                let b = N::Float(other.to_f64());
+                a.total_cmp(&b)
+                    .then_with(|| self.arbitrary_digits().cmp(&other.arbitrary_digits()))
+            }
+        }
+    }
+
+    fn integer_digits(&self) -> Option<String> {
+        match self {
+            N::PosInt(u) => Some(u.to_string()),
+            N::NegInt(i) => Some(i.to_string()),
+            N::Float(_) => None,
+            N::Arbitrary(digits) if is_integer(digits) => Some(digits.to_string()),
+            N::Arbitrary(_) => None,
+        }
+    }
+
+    fn arbitrary_digits(&self) -> Option<&str> {
+        match self {
+            N::Arbitrary(digits) => Some(digits),
+            _ => None,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            N::PosInt(u) => *u as f64,
+            N::NegInt(i) => *i as f64,
+            N::Float(f) => *f,
+            N::Arbitrary(digits) => digits.parse().unwrap_or(f64::NAN),
+        }
+    }
+}
+
+/// Compares integers written in decimal without leading zeros.
+#[cfg(feature = "arbitrary_precision")]
+fn cmp_integer_digits(a: &str, b: &str) -> Ordering {
+    match (a.strip_prefix('-'), b.strip_prefix('-')) {
+        (Some(a), Some(b)) => cmp_magnitude(b, a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => cmp_magnitude(a, b),
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+fn cmp_magnitude(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(feature = "arbitrary_precision")]
+fn is_integer(digits: &str) -> bool {
+    !digits.contains(['.', 'e'])
+}
+
 impl Number {
     pub(crate) fn total_cmp(&self, other: &Self) -> Ordering {
         self.n.total_cmp(&other.n)
     }
+
+    /// Builds the number that a scalar written as `text` resolved to, which
+    /// remembers `text` unless it is written the way the serializer would
+    /// write the number anyway. Returns None if `resolved` is not a number,
+    /// or is an integer that only `i128` or `u128` holds and the crate is
+    /// built without arbitrary precision.
+    #[cfg_attr(not(feature = "arbitrary_precision"), allow(unused_variables))]
+    pub(crate) fn from_scalar(text: &str, resolved: &Resolved) -> Option<Number> {
+        let mut number = match *resolved {
+            #[cfg(feature = "arbitrary_precision")]
+            Resolved::PosInt(int) => Number::from(int),
+            #[cfg(feature = "arbitrary_precision")]
+            Resolved::NegInt(int) => Number::from(int),
+            #[cfg(not(feature = "arbitrary_precision"))]
+            Resolved::PosInt(int) => Number::from(u64::try_from(int).ok()?),
+            #[cfg(not(feature = "arbitrary_precision"))]
+            Resolved::NegInt(int) => Number::from(i64::try_from(int).ok()?),
+            #[cfg(feature = "arbitrary_precision")]
+            Resolved::Float(float) => {
+                Number::from_decimal(text).unwrap_or_else(|| Number::from(float))
+            }
+            #[cfg(not(feature = "arbitrary_precision"))]
+            Resolved::Float(float) => Number::from(float),
+            _ => return None,
+        };
+        if !number.is_canonical(text) {
+            number.repr = Some(Box::from(text));
+        }
+        Some(number)
+    }
+
+    /// Whether `text` is how the serializer writes this number.
+    fn is_canonical(&self, text: &str) -> bool {
+        match self.n {
+            N::PosInt(u) => itoa::Buffer::new().format(u) == text,
+            N::NegInt(i) => itoa::Buffer::new().format(i) == text,
+            N::Float(f) => match f.classify() {
+                FpCategory::Infinite if f.is_sign_positive() => text == ".inf",
+                FpCategory::Infinite => text == "-.inf",
+                FpCategory::Nan => text == ".nan",
+                _ => ryu::Buffer::new().format_finite(f) == text,
+            },
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => **digits == *text,
+        }
+    }
+}
+
+thread_local! {
+    /// The number that is being handed to a visitor or serializer as a plain
+    /// `u64`, `i64` or `f64`, so that `Value` and `Number` can pick up how it
+    /// was written. Serde's visitor and serializer methods have no room for
+    /// anything but the value.
+    static LENT: Cell<Option<Number>> = Cell::new(None);
+}
+
+/// Runs `f`, in which `number` is handed to a visitor or serializer, such that
+/// [`take_lent`] can recover how it was written.
+pub(crate) fn lend<R>(number: &Number, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Number>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LENT.with(|lent| lent.set(self.0.take()));
+        }
+    }
+
+    let lent = number.repr.as_ref().map(|_| number.clone());
+    let _restore = Restore(LENT.with(|cell| cell.replace(lent)));
+    f()
+}
+
+/// Returns the number being lent by [`lend`] if it has the same value as
+/// `number`, which is what a visitor or serializer received, and otherwise
+/// `number` itself.
+pub(crate) fn take_lent(number: Number) -> Number {
+    match LENT.with(Cell::take) {
+        Some(lent) if lent == number => lent,
+        _ => number,
+    }
+}
+
+/// Returns how the number being lent by [`lend`] was written, if the
+/// serializer is writing it as `canonical` and `schema` reads it back as the
+/// same number.
+pub(crate) fn take_lent_repr(canonical: &str, schema: &Schema) -> Option<Box<str>> {
+    let mut lent = LENT.with(Cell::take)?;
+    let repr = lent.repr.take()?;
+    if !lent.is_canonical(canonical) {
+        return None;
+    }
+    match Number::from_scalar(&repr, &schema.resolve(&repr)) {
+        Some(number) if number == lent => Some(repr),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl Number {
+    /// Reads a number written in decimal, such as a plain scalar that the
+    /// schema resolved to a float, keeping all of its digits unless a `u64`,
+    /// `i64` or `f64` holds it exactly. Returns None if `text` is not written
+    /// in decimal, like `.inf` or `0x1F`.
+    pub(crate) fn from_decimal(text: &str) -> Option<Number> {
+        let decimal = Decimal::parse(text)?;
+        let digits = decimal.to_string();
+        if decimal.fraction.is_empty() && decimal.exponent.is_none() {
+            if let Ok(int) = digits.parse::<i128>() {
+                return Some(int.into());
+            }
+            if let Ok(int) = digits.parse::<u128>() {
+                return Some(int.into());
+            }
+        } else {
+            let float: f64 = digits.parse().ok()?;
+            if float.is_finite() {
+                let shortest = ryu::Buffer::new().format_finite(float).to_owned();
+                if Decimal::parse(&shortest)?.value() == decimal.value() {
+                    return Some(float.into());
+                }
+            }
+        }
+        Some(Number {
+            n: N::Arbitrary(digits.into_boxed_str()),
+            repr: None,
+        })
+    }
+}
+
+/// The parts of a number written in decimal, without its underscores.
+#[cfg(feature = "arbitrary_precision")]
+struct Decimal {
+    negative: bool,
+    integer: String,
+    fraction: String,
+    exponent: Option<String>,
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl Decimal {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.replace('_', "");
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+            Some(e) => (&unsigned[..e], Some(&unsigned[e + 1..])),
+            None => (unsigned, None),
+        };
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+            None => (mantissa, ""),
+        };
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if integer.is_empty() && fraction.is_empty()
+            || !all_digits(integer)
+            || !all_digits(fraction)
+        {
+            return None;
+        }
+        let exponent = match exponent {
+            Some(exponent) => {
+                let unsigned = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                if unsigned.is_empty() || !all_digits(unsigned) {
+                    return None;
+                }
+                Some(exponent.strip_prefix('+').unwrap_or(exponent).to_owned())
+            }
+            None => None,
+        };
+        Some(Decimal {
+            negative,
+            integer: integer.to_owned(),
+            fraction: fraction.to_owned(),
+            exponent,
+        })
+    }
+
+    /// The sign, the significant digits and the power of ten by which the
+    /// digits after a leading decimal point are multiplied, so that numbers
+    /// with the same value have the same triple. None if the exponent is out
+    /// of range.
+    fn value(&self) -> Option<(bool, String, i64)> {
+        let exponent: i64 = match &self.exponent {
+            Some(exponent) => exponent.parse().ok()?,
+            None => 0,
+        };
+        let digits = format!("{}{}", self.integer, self.fraction);
+        let significant = digits.trim_start_matches('0');
+        let leading_zeros = (digits.len() - significant.len()) as i64;
+        let significant = significant.trim_end_matches('0');
+        if significant.is_empty() {
+            return Some((self.negative, String::new(), 0));
+        }
+        let point = (self.integer.len() as i64)
+            .checked_add(exponent)?
+            .checked_sub(leading_zeros)?;
+        Some((self.negative, significant.to_owned(), point))
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl Display for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            formatter.write_str("-")?;
+        }
+        match self.integer.trim_start_matches('0') {
+            "" => formatter.write_str("0")?,
+            integer => formatter.write_str(integer)?,
+        }
+        if !self.fraction.is_empty() {
+            write!(formatter, ".{}", self.fraction)?;
+        }
+        if let Some(exponent) = &self.exponent {
+            write!(formatter, "e{}", exponent)?;
+        }
+        Ok(())
+    }
 }
 
 impl Serialize for Number {
@@ -387,11 +850,23 @@ impl Serialize for Number {
     where
         S: Serializer,
     {
-        match self.n {
+        lend(self, || match self.n {
             N::PosInt(i) => serializer.serialize_u64(i),
             N::NegInt(i) => serializer.serialize_i64(i),
             N::Float(f) => serializer.serialize_f64(f),
-        }
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => {
+                if let Ok(int) = digits.parse::<u128>() {
+                    serializer.serialize_u128(int)
+                } else if let Ok(int) = digits.parse::<i128>() {
+                    serializer.serialize_i128(int)
+                } else {
+                    // The YAML serializer writes the digits as a plain scalar.
+                    // Other serializers see a string.
+                    serializer.serialize_newtype_struct(NAME, &**digits)
+                }
+            }
+        })
     }
 }
 
@@ -412,17 +887,43 @@ impl<'de> Deserialize<'de> for Number {
 
             #[inline]
             fn visit_i64<E>(self, value: i64) -> Result<Number, E> {
-                Ok(value.into())
+                Ok(take_lent(value.into()))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            #[inline]
+            fn visit_i128<E>(self, value: i128) -> Result<Number, E> {
+                Ok(take_lent(value.into()))
             }
 
             #[inline]
             fn visit_u64<E>(self, value: u64) -> Result<Number, E> {
-                Ok(value.into())
+                Ok(take_lent(value.into()))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            #[inline]
+            fn visit_u128<E>(self, value: u128) -> Result<Number, E> {
+                Ok(take_lent(value.into()))
             }
 
             #[inline]
             fn visit_f64<E>(self, value: f64) -> Result<Number, E> {
-                Ok(value.into())
+                Ok(take_lent(value.into()))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_map<A>(self, mut map: A) -> Result<Number, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                if map.next_key::<String>()?.as_deref() != Some(NAME) {
+                    return Err(de::Error::invalid_type(Unexpected::Map, &self));
+                }
+                let digits: String = map.next_value()?;
+                Number::from_decimal(&digits)
+                    .map(take_lent)
+                    .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&digits), &self))
             }
         }
 
@@ -438,15 +939,27 @@ impl<'de> Deserializer<'de> for Number {
     where
         V: Visitor<'de>,
     {
-        match self.n {
-            N::PosInt(i) => visitor.visit_u64(i),
-            N::NegInt(i) => visitor.visit_i64(i),
-            N::Float(f) => visitor.visit_f64(f),
-        }
+        (&self).deserialize_any(visitor)
+    }
+
+    #[inline]
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    #[inline]
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        (&self).deserialize_f64(visitor)
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
         bytes byte_buf option unit unit_struct newtype_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     }
@@ -460,20 +973,76 @@ impl<'de, 'a> Deserializer<'de> for &'a Number {
     where
         V: Visitor<'de>,
     {
-        match self.n {
+        lend(self, || match self.n {
             N::PosInt(i) => visitor.visit_u64(i),
             N::NegInt(i) => visitor.visit_i64(i),
             N::Float(f) => visitor.visit_f64(f),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => visit_arbitrary(visitor, digits),
+        })
+    }
+
+    #[inline]
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    /// Rounds an arbitrary-precision number to the nearest `f64`, which
+    /// `deserialize_any` would hand over as a map.
+    #[inline]
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.n {
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(_) => visitor.visit_f64(self.n.to_f64()),
+            _ => self.deserialize_any(visitor),
         }
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
         bytes byte_buf option unit unit_struct newtype_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     }
 }
 
+/// Hands an arbitrary-precision number to a visitor as an `i128` or `u128`
+/// if it is one, and otherwise as a map from the private key [`NAME`] to its
+/// digits.
+#[cfg(feature = "arbitrary_precision")]
+fn visit_arbitrary<'de, V>(visitor: V, digits: &str) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    if let Ok(int) = digits.parse::<u128>() {
+        return visitor.visit_u128(int);
+    }
+    if let Ok(int) = digits.parse::<i128>() {
+        return visitor.visit_i128(int);
+    }
+    let mut map = de::value::MapDeserializer::<_, Error>::new(iter::once((NAME, digits)));
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+/// Recovers the digits from the content of a [`NAME`] newtype struct.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) fn digits_of<T>(content: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    match crate::value::to_value(content)? {
+        crate::Value::String(digits) => Ok(digits),
+        _ => Err(de::Error::custom("expected the digits of a number")),
+    }
+}
+
 macro_rules! from_signed {
     ($($signed_ty:ident)*) => {
         $(
@@ -482,9 +1051,9 @@ macro_rules! from_signed {
                 #[allow(clippy::cast_sign_loss)]
                 fn from(i: $signed_ty) -> Self {
                     if i < 0 {
-                        Number { n: N::NegInt(i as i64) }
+                        Number { n: N::NegInt(i as i64), repr: None }
                     } else {
-                        Number { n: N::PosInt(i as u64) }
+                        Number { n: N::PosInt(i as u64), repr: None }
                     }
                 }
             }
@@ -498,7 +1067,7 @@ macro_rules! from_unsigned {
             impl From<$unsigned_ty> for Number {
                 #[inline]
                 fn from(u: $unsigned_ty) -> Self {
-                    Number { n: N::PosInt(u as u64) }
+                    Number { n: N::PosInt(u as u64), repr: None }
                 }
             }
         )*
@@ -511,7 +1080,7 @@ macro_rules! from_float {
             impl From<$float_ty> for Number {
                 #[inline]
                 fn from(f: $float_ty) -> Self {
-                    Number { n: N::Float(f as f64) }
+                    Number { n: N::Float(f as f64), repr: None }
                 }
             }
         )*
@@ -522,6 +1091,36 @@ from_signed!(i8 i16 i32 i64 isize);
 from_unsigned!(u8 u16 u32 u64 usize);
 from_float!(f32 f64);
 
+#[cfg(feature = "arbitrary_precision")]
+impl From<i128> for Number {
+    fn from(i: i128) -> Self {
+        if let Ok(i) = i64::try_from(i) {
+            Number::from(i)
+        } else if let Ok(u) = u64::try_from(i) {
+            Number::from(u)
+        } else {
+            Number {
+                n: N::Arbitrary(i.to_string().into_boxed_str()),
+                repr: None,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl From<u128> for Number {
+    fn from(u: u128) -> Self {
+        if let Ok(u) = u64::try_from(u) {
+            Number::from(u)
+        } else {
+            Number {
+                n: N::Arbitrary(u.to_string().into_boxed_str()),
+                repr: None,
+            }
+        }
+    }
+}
+
 // This is fine, because we don't _really_ implement hash for floats
 // all other hash functions should work as expected
 #[allow(clippy::derive_hash_xor_eq)]
@@ -534,6 +1133,8 @@ impl Hash for Number {
             }
             N::PosInt(u) => u.hash(state),
             N::NegInt(i) => i.hash(state),
+            #[cfg(feature = "arbitrary_precision")]
+            N::Arbitrary(ref digits) => digits.hash(state),
         }
     }
 }
@@ -543,5 +1144,7 @@ pub(crate) fn unexpected(number: &Number) -> Unexpected {
         N::PosInt(u) => Unexpected::Unsigned(u),
         N::NegInt(i) => Unexpected::Signed(i),
         N::Float(f) => Unexpected::Float(f),
+        #[cfg(feature = "arbitrary_precision")]
+        N::Arbitrary(ref digits) => Unexpected::Other(digits),
     }
 }
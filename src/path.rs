@@ -1,34 +1,339 @@
-use std::fmt::{self, Display};
+//! Paths to values in a YAML document.
+
+use crate::error::Error;
+use serde::de::{self, Deserialize, Deserializer, Error as _};
+use serde::ser::{Serialize, Serializer};
+use std::fmt::{self, Display, Write as _};
+use std::str::FromStr;
 
 /// Path to the current value in the input, like `dependencies.serde.typo1`.
 #[derive(Copy, Clone)]
-pub enum Path<'a> {
+pub(crate) enum Path<'a> {
     Root,
-    Seq { parent: &'a Path<'a>, index: usize },
-    Map { parent: &'a Path<'a>, key: &'a str },
-    Alias { parent: &'a Path<'a> },
-    Unknown { parent: &'a Path<'a> },
+    Seq {
+        parent: &'a Path<'a>,
+        index: usize,
+    },
+    Map {
+        parent: &'a Path<'a>,
+        key: &'a str,
+    },
+    /// A mapping key whose text is only worked out if the path is rendered.
+    LazyMap {
+        parent: &'a Path<'a>,
+        key: &'a dyn Display,
+    },
+    Alias {
+        parent: &'a Path<'a>,
+    },
+    Unknown {
+        parent: &'a Path<'a>,
+    },
+}
+
+impl<'a> Path<'a> {
+    pub fn to_path_buf(self) -> PathBuf {
+        let mut segments = Vec::new();
+        let mut path = &self;
+        loop {
+            path = match path {
+                Path::Root => break,
+                Path::Seq { parent, index } => {
+                    segments.push(Segment::Index(*index));
+                    parent
+                }
+                Path::Map { parent, key } => {
+                    segments.push(Segment::Key((*key).to_owned()));
+                    parent
+                }
+                Path::LazyMap { parent, key } => {
+                    segments.push(Segment::Key(key.to_string()));
+                    parent
+                }
+                Path::Alias { parent } => {
+                    segments.push(Segment::Alias);
+                    parent
+                }
+                Path::Unknown { parent } => {
+                    segments.push(Segment::Unknown);
+                    parent
+                }
+            };
+        }
+        segments.reverse();
+        PathBuf { segments }
+    }
 }
 
 impl<'a> Display for Path<'a> {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        struct Parent<'a>(&'a Path<'a>);
+        Display::fmt(&self.to_path_buf(), formatter)
+    }
+}
+
+/// Path from the root of a document to one of its values, like
+/// `dependencies.serde.version` or `items[3].name`.
+///
+/// Errors report the path of the value they are about through
+/// [`Error::path`](crate::Error::path), and every [`Spanned`](crate::Spanned)
+/// value records its own.
+///
+/// A path is displayed as its mapping keys joined by `.`, with sequence
+/// indices in brackets. Keys that are empty, are `?`, or contain any of
+/// `.[]"\` are written in double quotes with `"` and `\` escaped by a
+/// backslash, so that the displayed form can be parsed back into the same
+/// path. The path of the root is `.`.
+///
+/// ```
+/// use serde_yaml::path::{PathBuf, Segment};
+///
+/// let mut path = PathBuf::new();
+/// path.push(Segment::Key("servers".to_owned()));
+/// path.push(Segment::Index(0));
+/// path.push(Segment::Key("example.com".to_owned()));
+///
+/// assert_eq!(path.to_string(), r#"servers[0]."example.com""#);
+/// assert_eq!(path.to_json_pointer().unwrap(), "/servers/0/example.com");
+/// assert_eq!(path, r#"servers[0]."example.com""#.parse::<PathBuf>().unwrap());
+/// ```
+#[derive(Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathBuf {
+    segments: Vec<Segment>,
+}
+
+/// One step of a [`PathBuf`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Segment {
+    /// The value of the mapping entry with this key. Keys that are not
+    /// strings are rendered as they appear in the document, like `404` or
+    /// `true`, with sequence and mapping keys written in flow style, like
+    /// `[0, 0]`, and cut short with `...` if long. Alias keys are rendered as
+    /// `*name` rather than followed.
+    Key(String),
+    /// The element at this index of a sequence.
+    Index(usize),
+    /// An alias, followed to the node of its anchor. Aliases do not appear
+    /// in the displayed path.
+    Alias,
+    /// The value of a mapping entry whose key could not be rendered as text.
+    /// Displayed as `?`.
+    Unknown,
+}
 
-        impl<'a> Display for Parent<'a> {
-            fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-                match self.0 {
-                    Path::Root => Ok(()),
-                    path => write!(formatter, "{}.", path),
+impl PathBuf {
+    /// The path of the root of a document.
+    pub fn new() -> Self {
+        PathBuf::default()
+    }
+
+    /// The steps from the root of the document to the value.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Extends the path by one step.
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    /// Removes the last step of the path, if any.
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+
+    /// Whether the path refers to the root of the document.
+    pub fn is_root(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| *segment == Segment::Alias)
+    }
+
+    /// Renders the path as a JSON Pointer (RFC 6901), like `/items/3/name`.
+    /// Returns `None` if the path goes through a [`Segment::Unknown`] key.
+    pub fn to_json_pointer(&self) -> Option<String> {
+        let mut pointer = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Key(key) => {
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+                Segment::Index(index) => {
+                    let _ = write!(pointer, "/{}", index);
                 }
+                Segment::Alias => {}
+                Segment::Unknown => return None,
             }
         }
+        Some(pointer)
+    }
+}
 
-        match self {
-            Path::Root => formatter.write_str("."),
-            Path::Seq { parent, index } => write!(formatter, "{}[{}]", parent, index),
-            Path::Map { parent, key } => write!(formatter, "{}{}", Parent(parent), key),
-            Path::Alias { parent } => write!(formatter, "{}", parent),
-            Path::Unknown { parent } => write!(formatter, "{}?", Parent(parent)),
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty() || key == "?" || key.contains(&['.', '[', ']', '"', '\\'][..])
+}
+
+impl Display for PathBuf {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let mut empty = true;
+        for segment in &self.segments {
+            match segment {
+                Segment::Key(key) => {
+                    if !empty {
+                        formatter.write_char('.')?;
+                    }
+                    if needs_quotes(key) {
+                        formatter.write_char('"')?;
+                        for ch in key.chars() {
+                            if ch == '"' || ch == '\\' {
+                                formatter.write_char('\\')?;
+                            }
+                            formatter.write_char(ch)?;
+                        }
+                        formatter.write_char('"')?;
+                    } else {
+                        formatter.write_str(key)?;
+                    }
+                }
+                Segment::Index(index) => {
+                    if empty {
+                        formatter.write_char('.')?;
+                    }
+                    write!(formatter, "[{}]", index)?;
+                }
+                Segment::Alias => continue,
+                Segment::Unknown => {
+                    if !empty {
+                        formatter.write_char('.')?;
+                    }
+                    formatter.write_char('?')?;
+                }
+            }
+            empty = false;
+        }
+        if empty {
+            formatter.write_char('.')?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PathBuf {
+    type Err = Error;
+
+    /// Parses a path in the form it is displayed in.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid =
+            |reason: &str| Error::custom(format_args!("invalid path `{}`: {}", s, reason));
+
+        let mut path = PathBuf::new();
+        if s == "." {
+            return Ok(path);
+        }
+        let mut rest = match s.strip_prefix('.') {
+            Some(rest) if rest.starts_with('[') => rest,
+            _ => s,
+        };
+        loop {
+            if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("unclosed `[`"))?;
+                let index = after[..end]
+                    .parse()
+                    .map_err(|_| invalid("expected an index between `[` and `]`"))?;
+                path.push(Segment::Index(index));
+                rest = &after[end + 1..];
+            } else {
+                if !path.segments.is_empty() {
+                    rest = rest
+                        .strip_prefix('.')
+                        .ok_or_else(|| invalid("expected `.` or `[`"))?;
+                }
+                if let Some(after) = rest.strip_prefix('"') {
+                    let mut key = String::new();
+                    let mut chars = after.char_indices();
+                    let end = loop {
+                        match chars.next() {
+                            Some((i, '"')) => break i + 1,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, ch)) => key.push(ch),
+                                None => return Err(invalid("unterminated quoted key")),
+                            },
+                            Some((_, ch)) => key.push(ch),
+                            None => return Err(invalid("unterminated quoted key")),
+                        }
+                    };
+                    path.push(Segment::Key(key));
+                    rest = &after[end..];
+                } else {
+                    let end = rest.find(&['.', '['][..]).unwrap_or(rest.len());
+                    let key = &rest[..end];
+                    if key.is_empty() {
+                        return Err(invalid("expected a key"));
+                    }
+                    if key.contains(&[']', '"', '\\'][..]) {
+                        return Err(invalid("unquoted key contains `]`, `\"` or `\\`"));
+                    }
+                    path.push(if key == "?" {
+                        Segment::Unknown
+                    } else {
+                        Segment::Key(key.to_owned())
+                    });
+                    rest = &rest[end..];
+                }
+            }
+            if rest.is_empty() {
+                return Ok(path);
+            }
+        }
+    }
+}
+
+// Compares the displayed form of the path to a string, without rendering it
+// into a String first.
+impl PartialEq<str> for PathBuf {
+    fn eq(&self, other: &str) -> bool {
+        struct Compare<'a> {
+            rest: &'a str,
+        }
+
+        impl<'a> fmt::Write for Compare<'a> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                match self.rest.strip_prefix(s) {
+                    Some(rest) => {
+                        self.rest = rest;
+                        Ok(())
+                    }
+                    None => Err(fmt::Error),
+                }
+            }
         }
+
+        let mut compare = Compare { rest: other };
+        write!(compare, "{}", self).is_ok() && compare.rest.is_empty()
+    }
+}
+
+impl<'a> PartialEq<&'a str> for PathBuf {
+    fn eq(&self, other: &&'a str) -> bool {
+        *self == **other
+    }
+}
+
+impl Serialize for PathBuf {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathBuf {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let path = String::deserialize(deserializer)?;
+        path.parse().map_err(de::Error::custom)
     }
 }
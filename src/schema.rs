@@ -0,0 +1,545 @@
+//! Rules for deciding the type of plain scalars.
+
+use crate::Timestamp;
+use std::num::ParseIntError;
+
+/// The rules that decide whether a plain scalar, one that is neither quoted
+/// nor tagged, is a null, a boolean, an integer, a float or a string.
+///
+/// The deserializer uses the schema from its
+/// [`DeserializerOptions`](crate::DeserializerOptions), and the serializer
+/// uses its own to decide which strings must be quoted so that they are not
+/// read back as something else.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_yaml::{Deserializer, DeserializerOptions, Schema, Value};
+///
+/// let yaml = "[yes, 0777, 1_000, 1:30]";
+///
+/// let value = Value::deserialize(Deserializer::from_str(yaml)).unwrap();
+/// assert_eq!(value, serde_yaml::from_str::<Value>("['yes', '0777', '1_000', '1:30']").unwrap());
+///
+/// let options = DeserializerOptions::new().schema(Schema::Yaml11);
+/// let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+/// assert_eq!(value, serde_yaml::from_str::<Value>("[true, 511, 1000, 90]").unwrap());
+/// ```
+///
+/// More schemas may be added in later releases, so matches on a `Schema`
+/// need a wildcard arm.
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum Schema {
+    /// Every plain scalar is a string.
+    Failsafe,
+    /// The YAML 1.2 JSON schema: only `null`, `true`, `false` and numbers
+    /// spelled as in JSON. An empty scalar is also null.
+    Json,
+    /// The rules of earlier releases, and the default: the YAML 1.2 core
+    /// schema without its title case and uppercase spellings. Nulls are `~`,
+    /// `null` or empty and booleans are `true` or `false`, so `True` and
+    /// `NULL` are strings. Numbers are read as in the core schema.
+    Compat,
+    /// The YAML 1.2 core schema. Nulls are `~`, `null`, `Null`, `NULL` or
+    /// empty, booleans are `true` or `false` in lowercase, title case or
+    /// uppercase, and integers may be written in hexadecimal (`0x`), octal
+    /// (`0o`) or binary (`0b`).
+    Core,
+    /// The YAML 1.1 rules followed by PyYAML and Ruby's Psych. In addition to
+    /// the core spellings, booleans include `yes`, `no`, `on` and `off`,
+    /// a leading `0` makes an integer octal, digits may be separated by `_`,
+    /// integers and floats may be written in base 60 like `1:30:00`, and
+    /// dates like `2001-12-14` are timestamps.
+    Yaml11,
+    /// A user-defined resolver. It is consulted first for every plain scalar,
+    /// and scalars for which it returns None are resolved by `fallback`.
+    ///
+    /// ```
+    /// use serde_yaml::schema::{Resolved, Schema};
+    /// use serde_yaml::{Deserializer, DeserializerOptions, Value};
+    /// use serde::Deserialize;
+    ///
+    /// fn resolve(scalar: &str) -> Option<Resolved> {
+    ///     match scalar {
+    ///         "enabled" => Some(Resolved::Bool(true)),
+    ///         "disabled" => Some(Resolved::Bool(false)),
+    ///         _ => None,
+    ///     }
+    /// }
+    ///
+    /// let schema = Schema::Custom {
+    ///     resolve,
+    ///     fallback: &Schema::Core,
+    /// };
+    /// assert_eq!(schema.resolve("disabled"), Resolved::Bool(false));
+    /// assert_eq!(schema.resolve("~"), Resolved::Null);
+    ///
+    /// let options = DeserializerOptions::new().schema(schema);
+    /// let de = Deserializer::from_str("[enabled, 1]").with_options(options);
+    /// let value = Value::deserialize(de).unwrap();
+    /// assert_eq!(value, serde_yaml::from_str::<Value>("[true, 1]").unwrap());
+    /// ```
+    Custom {
+        /// Decides the type of a plain scalar, or returns None to leave it to
+        /// the fallback schema.
+        resolve: fn(&str) -> Option<Resolved>,
+        /// The schema for everything `resolve` does not decide, and for
+        /// scalars with an explicit tag like `!!int`.
+        fallback: &'static Schema,
+    },
+}
+
+/// The type, and value, that a [`Schema`] gives a plain scalar.
+///
+/// Like `Schema`, this may gain variants in later releases.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Resolved {
+    /// A null.
+    Null,
+    /// A boolean.
+    Bool(bool),
+    /// An integer written without a minus sign.
+    PosInt(u128),
+    /// An integer written with a minus sign.
+    NegInt(i128),
+    /// A float.
+    Float(f64),
+    /// A timestamp.
+    Timestamp(Timestamp),
+    /// Anything else is a string.
+    String,
+}
+
+impl Schema {
+    /// Decides the type of a plain scalar.
+    ///
+    /// ```
+    /// use serde_yaml::schema::{Resolved, Schema};
+    ///
+    /// assert_eq!(Schema::Core.resolve("True"), Resolved::Bool(true));
+    /// assert_eq!(Schema::Compat.resolve("True"), Resolved::String);
+    /// assert_eq!(Schema::Json.resolve("True"), Resolved::String);
+    /// assert_eq!(Schema::Yaml11.resolve("off"), Resolved::Bool(false));
+    /// assert_eq!(Schema::Yaml11.resolve("-0x_1F"), Resolved::NegInt(-31));
+    /// assert_eq!(Schema::Failsafe.resolve("1"), Resolved::String);
+    /// ```
+    pub fn resolve(&self, scalar: &str) -> Resolved {
+        if let Schema::Custom { resolve, fallback } = self {
+            return match resolve(scalar) {
+                Some(resolved) => resolved,
+                None => fallback.resolve(scalar),
+            };
+        }
+        if self.parse_null(scalar) {
+            return Resolved::Null;
+        }
+        if let Some(boolean) = self.parse_bool(scalar) {
+            return Resolved::Bool(boolean);
+        }
+        if let Some(int) = self.parse_int_resolved(scalar) {
+            return int;
+        }
+        if let Schema::Compat | Schema::Core = self {
+            if digits_but_not_number(scalar) {
+                return Resolved::String;
+            }
+        }
+        if let Some(float) = self.parse_f64(scalar) {
+            return Resolved::Float(float);
+        }
+        match self.parse_timestamp(scalar) {
+            Some(timestamp) => Resolved::Timestamp(timestamp),
+            None => Resolved::String,
+        }
+    }
+
+    /// The schema that reads scalars carrying an explicit tag like `!!int`,
+    /// whose type is not in question even under the failsafe schema.
+    pub(crate) fn for_tags(&self) -> &Schema {
+        match self {
+            Schema::Failsafe => &Schema::Core,
+            Schema::Custom { fallback, .. } => fallback.for_tags(),
+            schema => schema,
+        }
+    }
+
+    pub(crate) fn parse_null(&self, scalar: &str) -> bool {
+        match self {
+            Schema::Failsafe => false,
+            Schema::Json => scalar.is_empty() || scalar == "null",
+            Schema::Compat => matches!(scalar, "" | "~" | "null"),
+            Schema::Core | Schema::Yaml11 => {
+                matches!(scalar, "" | "~" | "null" | "Null" | "NULL")
+            }
+            Schema::Custom { resolve, fallback } => match resolve(scalar) {
+                Some(resolved) => resolved == Resolved::Null,
+                None => fallback.parse_null(scalar),
+            },
+        }
+    }
+
+    pub(crate) fn parse_bool(&self, scalar: &str) -> Option<bool> {
+        match self {
+            Schema::Failsafe => None,
+            Schema::Json | Schema::Compat => match scalar {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            },
+            Schema::Core => match scalar {
+                "true" | "True" | "TRUE" => Some(true),
+                "false" | "False" | "FALSE" => Some(false),
+                _ => None,
+            },
+            Schema::Yaml11 => match scalar {
+                "true" | "True" | "TRUE" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => Some(true),
+                "false" | "False" | "FALSE" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => {
+                    Some(false)
+                }
+                _ => None,
+            },
+            Schema::Custom { resolve, fallback } => match resolve(scalar) {
+                Some(Resolved::Bool(boolean)) => Some(boolean),
+                Some(_) => None,
+                None => fallback.parse_bool(scalar),
+            },
+        }
+    }
+
+    /// Parses an integer that fits in `T`.
+    pub(crate) fn parse_int<T>(&self, scalar: &str) -> Option<T>
+    where
+        T: TryFrom<u128> + TryFrom<i128>,
+    {
+        match self.parse_int_resolved(scalar)? {
+            Resolved::PosInt(int) => T::try_from(int).ok(),
+            Resolved::NegInt(int) => T::try_from(int).ok(),
+            _ => None,
+        }
+    }
+
+    fn parse_int_resolved(&self, scalar: &str) -> Option<Resolved> {
+        match self {
+            Schema::Failsafe => None,
+            Schema::Json => {
+                let unsigned = scalar.strip_prefix('-').unwrap_or(scalar);
+                if unsigned.is_empty()
+                    || !unsigned.bytes().all(|b| b.is_ascii_digit())
+                    || unsigned.len() > 1 && unsigned.starts_with('0')
+                {
+                    return None;
+                }
+                resolve_int(scalar.len() > unsigned.len(), parse_digits(unsigned, 10)?)
+            }
+            Schema::Compat | Schema::Core => {
+                if let Some(int) = parse_unsigned_int(scalar, u128::from_str_radix) {
+                    Some(Resolved::PosInt(int))
+                } else {
+                    parse_negative_int(scalar, i128::from_str_radix).map(Resolved::NegInt)
+                }
+            }
+            Schema::Yaml11 => {
+                let (negative, unsigned) = split_sign(scalar);
+                if !unsigned.starts_with(|ch: char| ch.is_ascii_digit()) {
+                    return None;
+                }
+                let magnitude = if let Some(binary) = unsigned.strip_prefix("0b") {
+                    parse_digits(binary, 2)?
+                } else if let Some(hex) = unsigned.strip_prefix("0x") {
+                    parse_digits(hex, 16)?
+                } else if unsigned.contains(':') {
+                    parse_sexagesimal(unsigned)?
+                } else if let Some(octal) = unsigned.strip_prefix('0').filter(|s| !s.is_empty()) {
+                    parse_digits(octal, 8)?
+                } else {
+                    parse_digits(unsigned, 10)?
+                };
+                resolve_int(negative, magnitude)
+            }
+            Schema::Custom { resolve, fallback } => match resolve(scalar) {
+                Some(int @ (Resolved::PosInt(_) | Resolved::NegInt(_))) => Some(int),
+                Some(_) => None,
+                None => fallback.parse_int_resolved(scalar),
+            },
+        }
+    }
+
+    fn parse_timestamp(&self, scalar: &str) -> Option<Timestamp> {
+        match self {
+            Schema::Failsafe | Schema::Json | Schema::Compat | Schema::Core => None,
+            Schema::Yaml11 => scalar.parse().ok(),
+            Schema::Custom { resolve, fallback } => match resolve(scalar) {
+                Some(Resolved::Timestamp(timestamp)) => Some(timestamp),
+                Some(_) => None,
+                None => fallback.parse_timestamp(scalar),
+            },
+        }
+    }
+
+    /// Parses a float, or an integer read as a float.
+    pub(crate) fn parse_f64(&self, scalar: &str) -> Option<f64> {
+        match self {
+            Schema::Failsafe => None,
+            Schema::Json => {
+                let unsigned = scalar.strip_prefix('-').unwrap_or(scalar);
+                let mantissa_end = unsigned.find(['e', 'E']).unwrap_or(unsigned.len());
+                let (mantissa, exponent) = unsigned.split_at(mantissa_end);
+                let (int, fraction) = match mantissa.find('.') {
+                    Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+                    None => (mantissa, ""),
+                };
+                let exponent = exponent
+                    .get(1..)
+                    .map(|exp| exp.trim_start_matches(['+', '-']));
+                if int.is_empty()
+                    || int.len() > 1 && int.starts_with('0')
+                    || !int.bytes().all(|b| b.is_ascii_digit())
+                    || !fraction.bytes().all(|b| b.is_ascii_digit())
+                    || exponent.map_or(false, |exp| {
+                        exp.is_empty() || !exp.bytes().all(|b| b.is_ascii_digit())
+                    })
+                {
+                    return None;
+                }
+                scalar.parse().ok().filter(|float: &f64| float.is_finite())
+            }
+            Schema::Compat | Schema::Core => parse_f64(scalar),
+            Schema::Yaml11 => {
+                parse_yaml11_f64(scalar).or_else(|| match self.parse_int_resolved(scalar)? {
+                    Resolved::PosInt(int) => Some(int as f64),
+                    Resolved::NegInt(int) => Some(int as f64),
+                    _ => None,
+                })
+            }
+            Schema::Custom { resolve, fallback } => match resolve(scalar) {
+                Some(Resolved::Float(float)) => Some(float),
+                Some(Resolved::PosInt(int)) => Some(int as f64),
+                Some(Resolved::NegInt(int)) => Some(int as f64),
+                Some(_) => None,
+                None => fallback.parse_f64(scalar),
+            },
+        }
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::Compat
+    }
+}
+
+fn split_sign(scalar: &str) -> (bool, &str) {
+    if let Some(unsigned) = scalar.strip_prefix('-') {
+        (true, unsigned)
+    } else {
+        (false, scalar.strip_prefix('+').unwrap_or(scalar))
+    }
+}
+
+fn resolve_int(negative: bool, magnitude: u128) -> Option<Resolved> {
+    if negative {
+        if magnitude == i128::MIN.unsigned_abs() {
+            Some(Resolved::NegInt(i128::MIN))
+        } else {
+            i128::try_from(magnitude)
+                .ok()
+                .map(|int| Resolved::NegInt(-int))
+        }
+    } else {
+        Some(Resolved::PosInt(magnitude))
+    }
+}
+
+// Digits in the given radix, optionally separated by underscores.
+fn parse_digits(digits: &str, radix: u32) -> Option<u128> {
+    if !digits
+        .bytes()
+        .all(|b| b == b'_' || (b as char).is_digit(radix))
+    {
+        return None;
+    }
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return None;
+    }
+    u128::from_str_radix(&digits, radix).ok()
+}
+
+// Base 60 like `1:30:00`, in which the first part is a decimal number without
+// leading zeros and each following part is from 0 to 59.
+fn parse_sexagesimal(scalar: &str) -> Option<u128> {
+    let mut parts = scalar.split(':');
+    let first = parts.next()?;
+    if first.starts_with(['0', '_']) {
+        return None;
+    }
+    let mut value = parse_digits(first, 10)?;
+    for part in parts {
+        value = value
+            .checked_mul(60)?
+            .checked_add(parse_base60_digit(part)?)?;
+    }
+    Some(value)
+}
+
+fn parse_base60_digit(part: &str) -> Option<u128> {
+    if part.is_empty() || part.len() > 2 || !part.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digit = part.parse().ok()?;
+    if digit < 60 {
+        Some(digit)
+    } else {
+        None
+    }
+}
+
+fn parse_yaml11_f64(scalar: &str) -> Option<f64> {
+    if let ".nan" | ".NaN" | ".NAN" = scalar {
+        return Some(f64::NAN);
+    }
+    let (negative, unsigned) = split_sign(scalar);
+    let sign = if negative { -1.0 } else { 1.0 };
+    if let ".inf" | ".Inf" | ".INF" = unsigned {
+        return Some(sign * f64::INFINITY);
+    }
+    let dot = unsigned.find('.')?;
+    let (int, fraction) = (&unsigned[..dot], &unsigned[dot + 1..]);
+    if int.contains(':') {
+        if !fraction.bytes().all(|b| b == b'_' || b.is_ascii_digit()) {
+            return None;
+        }
+        let mut parts = int.split(':');
+        let first = parts.next()?;
+        if !first.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return None;
+        }
+        let mut value = parse_digits(first, 10)? as f64;
+        for part in parts {
+            value = value * 60.0 + parse_base60_digit(part)? as f64;
+        }
+        let fraction = format!("0.{}", fraction.replace('_', ""));
+        return Some(sign * (value + fraction.parse::<f64>().ok()?));
+    }
+    let (fraction, exponent) = match fraction.find(['e', 'E']) {
+        Some(e) => (&fraction[..e], Some(&fraction[e + 1..])),
+        None => (fraction, None),
+    };
+    let digits = |s: &str| s.bytes().all(|b| b == b'_' || b.is_ascii_digit());
+    let has_digits = if int.is_empty() {
+        fraction.starts_with(|ch: char| ch.is_ascii_digit())
+    } else {
+        int.starts_with(|ch: char| ch.is_ascii_digit())
+    };
+    if !has_digits || !digits(int) || !digits(fraction) {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        match exponent.strip_prefix(['+', '-']) {
+            Some(exp) if !exp.is_empty() && exp.bytes().all(|b| b.is_ascii_digit()) => {}
+            _ => return None,
+        }
+    }
+    let float: f64 = unsigned.replace('_', "").parse().ok()?;
+    Some(sign * float)
+}
+
+fn parse_unsigned_int<T>(
+    scalar: &str,
+    from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
+) -> Option<T> {
+    let unpositive = scalar.strip_prefix('+').unwrap_or(scalar);
+    if let Some(rest) = unpositive.strip_prefix("0x") {
+        if rest.starts_with(['+', '-']) {
+            return None;
+        }
+        if let Ok(int) = from_str_radix(rest, 16) {
+            return Some(int);
+        }
+    }
+    if let Some(rest) = unpositive.strip_prefix("0o") {
+        if rest.starts_with(['+', '-']) {
+            return None;
+        }
+        if let Ok(int) = from_str_radix(rest, 8) {
+            return Some(int);
+        }
+    }
+    if let Some(rest) = unpositive.strip_prefix("0b") {
+        if rest.starts_with(['+', '-']) {
+            return None;
+        }
+        if let Ok(int) = from_str_radix(rest, 2) {
+            return Some(int);
+        }
+    }
+    if unpositive.starts_with(['+', '-']) {
+        return None;
+    }
+    if digits_but_not_number(scalar) {
+        return None;
+    }
+    from_str_radix(unpositive, 10).ok()
+}
+
+fn parse_negative_int<T>(
+    scalar: &str,
+    from_str_radix: fn(&str, radix: u32) -> Result<T, ParseIntError>,
+) -> Option<T> {
+    if let Some(rest) = scalar.strip_prefix("-0x") {
+        let negative = format!("-{}", rest);
+        if let Ok(int) = from_str_radix(&negative, 16) {
+            return Some(int);
+        }
+    }
+    if let Some(rest) = scalar.strip_prefix("-0o") {
+        let negative = format!("-{}", rest);
+        if let Ok(int) = from_str_radix(&negative, 8) {
+            return Some(int);
+        }
+    }
+    if let Some(rest) = scalar.strip_prefix("-0b") {
+        let negative = format!("-{}", rest);
+        if let Ok(int) = from_str_radix(&negative, 2) {
+            return Some(int);
+        }
+    }
+    if digits_but_not_number(scalar) {
+        return None;
+    }
+    from_str_radix(scalar, 10).ok()
+}
+
+fn parse_f64(scalar: &str) -> Option<f64> {
+    let unpositive = if let Some(unpositive) = scalar.strip_prefix('+') {
+        if unpositive.starts_with(['+', '-']) {
+            return None;
+        }
+        unpositive
+    } else {
+        scalar
+    };
+    if let ".inf" | ".Inf" | ".INF" = unpositive {
+        return Some(f64::INFINITY);
+    }
+    if let "-.inf" | "-.Inf" | "-.INF" = scalar {
+        return Some(f64::NEG_INFINITY);
+    }
+    if let ".nan" | ".NaN" | ".NAN" = scalar {
+        return Some(f64::NAN);
+    }
+    if let Ok(float) = unpositive.parse::<f64>() {
+        if float.is_finite() {
+            return Some(float);
+        }
+    }
+    None
+}
+
+fn digits_but_not_number(scalar: &str) -> bool {
+    // Leading zero(s) followed by numeric characters is a string according to
+    // the YAML 1.2 spec. https://yaml.org/spec/1.2/spec.html#id2761292
+    let scalar = scalar.strip_prefix(['-', '+']).unwrap_or(scalar);
+    scalar.len() > 1 && scalar.starts_with('0') && scalar[1..].bytes().all(|b| b.is_ascii_digit())
+}
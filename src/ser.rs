@@ -2,10 +2,15 @@
 //!
 //! This module provides YAML serialization with the type `Serializer`.
 
-use crate::libyaml;
-use crate::libyaml::emitter::{Emitter, Event, Scalar, ScalarStyle};
+use crate::base64;
+use crate::libyaml::emitter::{Emitter, Event, Mapping, Scalar, ScalarStyle, Sequence};
+use crate::libyaml::tag::Tag;
+use crate::number;
+use crate::schema::{Resolved, Schema};
+use crate::timestamp;
+use crate::value::anchored;
+use crate::value::tagged::{self, MaybeTag};
 use crate::{error, Error};
-use serde::de::Visitor;
 use serde::ser::{self, Serializer as _};
 use std::fmt;
 use std::io;
@@ -42,10 +47,27 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 /// ```
 pub struct Serializer<W> {
     depth: usize,
+    state: State,
+    /// Anchor to write on the next node, from an anchored value.
+    anchor: Option<String>,
+    schema: Schema,
     emitter: Emitter<'static>,
     writer: PhantomData<W>,
 }
 
+// A map with a single entry whose key is written by `collect_str` as a string
+// beginning with '!' is serialized as a tagged value rather than as a map, and
+// one whose key is an anchor in a private newtype struct as an anchored value.
+// The mapping start is held back until the first key shows which one it is.
+enum State {
+    NothingInParticular,
+    CheckForTag,
+    CheckForDuplicateTag,
+    FoundTag(String),
+    FoundAnchor,
+    AlreadyTagged,
+}
+
 impl<W> Serializer<W>
 where
     W: io::Write,
@@ -59,11 +81,33 @@ where
         emitter.emit(Event::StreamStart).unwrap();
         Serializer {
             depth: 0,
+            state: State::NothingInParticular,
+            anchor: None,
+            schema: Schema::Compat,
             emitter,
             writer: PhantomData,
         }
     }
 
+    /// Sets the schema of the YAML that is being written, which decides the
+    /// strings that need quotes so that a reader using the same schema does
+    /// not take them for a null, bool or number. The default is
+    /// [`Schema::Compat`].
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use serde_yaml::{Schema, Serializer};
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut ser = Serializer::new(&mut buffer).with_schema(Schema::Yaml11);
+    /// ["yes", "0777", "plain"].serialize(&mut ser).unwrap();
+    /// assert_eq!(buffer, b"- 'yes'\n- '0777'\n- plain\n");
+    /// ```
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
     /// Calls [`.flush()`](io::Write::flush) on the underlying `io::Write`
     /// object.
     pub fn flush(&mut self) -> Result<()> {
@@ -79,15 +123,37 @@ where
         Ok(*unsafe { Box::from_raw(Box::into_raw(writer).cast::<W>()) })
     }
 
-    fn emit_scalar(&mut self, scalar: Scalar) -> Result<()> {
+    fn emit_scalar(&mut self, mut scalar: Scalar) -> Result<()> {
+        self.flush_mapping_start()?;
+        if let Some(tag) = self.take_tag() {
+            scalar.tag = Some(tag);
+        }
+        scalar.anchor = self.take_anchor();
         self.value_start()?;
         self.emitter.emit(Event::Scalar(scalar))?;
         self.value_end()
     }
 
+    // Writes a number the way it was written in the YAML it was read from, if
+    // it is a `Number` that remembers that and the schema reads it back as the
+    // same number, and otherwise as `canonical`.
+    fn emit_number(&mut self, canonical: &str) -> Result<()> {
+        let repr = number::take_lent_repr(canonical, &self.schema);
+        self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
+            value: repr.as_deref().unwrap_or(canonical),
+            style: ScalarStyle::Plain,
+        })
+    }
+
     fn emit_sequence_start(&mut self) -> Result<()> {
+        self.flush_mapping_start()?;
         self.value_start()?;
-        self.emitter.emit(Event::SequenceStart)?;
+        let tag = self.take_tag();
+        let anchor = self.take_anchor();
+        self.emitter
+            .emit(Event::SequenceStart(Sequence { anchor, tag }))?;
         Ok(())
     }
 
@@ -97,8 +163,12 @@ where
     }
 
     fn emit_mapping_start(&mut self) -> Result<()> {
+        self.flush_mapping_start()?;
         self.value_start()?;
-        self.emitter.emit(Event::MappingStart)?;
+        let tag = self.take_tag();
+        let anchor = self.take_anchor();
+        self.emitter
+            .emit(Event::MappingStart(Mapping { anchor, tag }))?;
         Ok(())
     }
 
@@ -107,6 +177,48 @@ where
         self.value_end()
     }
 
+    fn take_tag(&mut self) -> Option<String> {
+        let state = mem::replace(&mut self.state, State::NothingInParticular);
+        if let State::FoundTag(mut tag) = state {
+            if let Some(core) = tag.strip_prefix("!!") {
+                tag = format!("{}{}", Tag::CORE_PREFIX, core);
+            } else if !tag.starts_with('!') {
+                tag.insert(0, '!');
+            }
+            Some(tag)
+        } else {
+            self.state = state;
+            None
+        }
+    }
+
+    fn take_anchor(&mut self) -> Option<String> {
+        if let State::FoundAnchor = self.state {
+            self.state = State::NothingInParticular;
+        }
+        self.anchor.take()
+    }
+
+    fn emit_alias(&mut self, name: String) -> Result<()> {
+        self.flush_mapping_start()?;
+        if self.take_tag().is_some() || self.take_anchor().is_some() {
+            return Err(error::serialize_decorated_alias());
+        }
+        self.value_start()?;
+        self.emitter.emit(Event::Alias(name))?;
+        self.value_end()
+    }
+
+    fn flush_mapping_start(&mut self) -> Result<()> {
+        if let State::CheckForTag = self.state {
+            self.state = State::NothingInParticular;
+            self.emit_mapping_start()?;
+        } else if let State::CheckForDuplicateTag = self.state {
+            self.state = State::NothingInParticular;
+        }
+        Ok(())
+    }
+
     fn value_start(&mut self) -> Result<()> {
         if self.depth == 0 {
             self.emitter.emit(Event::DocumentStart)?;
@@ -141,6 +253,8 @@ where
 
     fn serialize_bool(self, v: bool) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: if v { "true" } else { "false" },
             style: ScalarStyle::Plain,
         })
@@ -148,6 +262,8 @@ where
 
     fn serialize_i8(self, v: i8) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
@@ -155,6 +271,8 @@ where
 
     fn serialize_i16(self, v: i16) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
@@ -162,27 +280,25 @@ where
 
     fn serialize_i32(self, v: i32) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.emit_scalar(Scalar {
-            value: itoa::Buffer::new().format(v),
-            style: ScalarStyle::Plain,
-        })
+        self.emit_number(itoa::Buffer::new().format(v))
     }
 
     fn serialize_i128(self, v: i128) -> Result<()> {
-        self.emit_scalar(Scalar {
-            value: itoa::Buffer::new().format(v),
-            style: ScalarStyle::Plain,
-        })
+        self.emit_number(itoa::Buffer::new().format(v))
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
@@ -190,6 +306,8 @@ where
 
     fn serialize_u16(self, v: u16) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
@@ -197,28 +315,26 @@ where
 
     fn serialize_u32(self, v: u32) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: itoa::Buffer::new().format(v),
             style: ScalarStyle::Plain,
         })
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.emit_scalar(Scalar {
-            value: itoa::Buffer::new().format(v),
-            style: ScalarStyle::Plain,
-        })
+        self.emit_number(itoa::Buffer::new().format(v))
     }
 
     fn serialize_u128(self, v: u128) -> Result<()> {
-        self.emit_scalar(Scalar {
-            value: itoa::Buffer::new().format(v),
-            style: ScalarStyle::Plain,
-        })
+        self.emit_number(itoa::Buffer::new().format(v))
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
         let mut buffer = ryu::Buffer::new();
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: match v.classify() {
                 num::FpCategory::Infinite if v.is_sign_positive() => ".inf",
                 num::FpCategory::Infinite => "-.inf",
@@ -231,88 +347,56 @@ where
 
     fn serialize_f64(self, v: f64) -> Result<()> {
         let mut buffer = ryu::Buffer::new();
-        self.emit_scalar(Scalar {
-            value: match v.classify() {
-                num::FpCategory::Infinite if v.is_sign_positive() => ".inf",
-                num::FpCategory::Infinite => "-.inf",
-                num::FpCategory::Nan => ".nan",
-                _ => buffer.format_finite(v),
-            },
-            style: ScalarStyle::Plain,
+        self.emit_number(match v.classify() {
+            num::FpCategory::Infinite if v.is_sign_positive() => ".inf",
+            num::FpCategory::Infinite => "-.inf",
+            num::FpCategory::Nan => ".nan",
+            _ => buffer.format_finite(v),
         })
     }
 
     fn serialize_char(self, value: char) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: value.encode_utf8(&mut [0u8; 4]),
             style: ScalarStyle::Any,
         })
     }
 
     fn serialize_str(self, value: &str) -> Result<()> {
-        struct InferScalarStyle;
-
-        impl<'de> Visitor<'de> for InferScalarStyle {
-            type Value = ScalarStyle;
-
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("I wonder")
-            }
-
-            fn visit_bool<E>(self, _v: bool) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_i128<E>(self, _v: i128) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_u128<E>(self, _v: u128) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-
-            fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::Any)
-            }
-
-            fn visit_unit<E>(self) -> Result<Self::Value, E> {
-                Ok(ScalarStyle::SingleQuoted)
-            }
-        }
-
+        // Quote strings that would otherwise be read back as a null, bool or
+        // number under the serializer's schema.
         let style = if value.contains('\n') {
             ScalarStyle::Literal
+        } else if self.schema.resolve(value) == Resolved::String {
+            ScalarStyle::Any
         } else {
-            let result = crate::de::visit_untagged_scalar(
-                InferScalarStyle,
-                value,
-                None,
-                libyaml::parser::ScalarStyle::Plain,
-            );
-            result.unwrap_or(ScalarStyle::Any)
+            ScalarStyle::SingleQuoted
         };
 
-        self.emit_scalar(Scalar { value, style })
+        self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
+            value,
+            style,
+        })
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
-        self.collect_seq(value)
+        let encoded = base64::encode(value);
+        self.emit_scalar(Scalar {
+            anchor: None,
+            tag: Some(Tag::BINARY.to_owned()),
+            value: &encoded,
+            style: ScalarStyle::Literal,
+        })
     }
 
     fn serialize_unit(self) -> Result<()> {
         self.emit_scalar(Scalar {
+            anchor: None,
+            tag: None,
             value: "null",
             style: ScalarStyle::Plain,
         })
@@ -331,11 +415,45 @@ where
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + ser::Serialize,
     {
-        value.serialize(self)
+        match name {
+            anchored::ANCHOR => match self.state {
+                State::CheckForTag => {
+                    self.anchor = Some(anchored::name_of('&', value)?);
+                    self.state = State::FoundAnchor;
+                    Ok(())
+                }
+                State::CheckForDuplicateTag => Err(error::serialize_anchor_in_tag()),
+                _ => value.serialize(self),
+            },
+            anchored::ALIAS => {
+                let name = anchored::name_of('*', value)?;
+                self.emit_alias(name)
+            }
+            timestamp::NAME => {
+                // Tag the timestamp unless the schema reads it back as one.
+                let text = timestamp::text_of(value)?;
+                let tag = match self.schema.resolve(&text) {
+                    Resolved::Timestamp(_) => None,
+                    _ => Some(Tag::TIMESTAMP.to_owned()),
+                };
+                self.emit_scalar(Scalar {
+                    anchor: None,
+                    tag,
+                    value: &text,
+                    style: ScalarStyle::Plain,
+                })
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            number::NAME => {
+                let digits = number::digits_of(value)?;
+                self.emit_number(&digits)
+            }
+            _ => value.serialize(self),
+        }
     }
 
     fn serialize_newtype_variant<T>(
@@ -397,8 +515,17 @@ where
         Ok(self)
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.emit_mapping_start()?;
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        if len == Some(1) {
+            self.state = if let State::FoundTag(_) = self.state {
+                self.emit_mapping_start()?;
+                State::CheckForDuplicateTag
+            } else {
+                State::CheckForTag
+            };
+        } else {
+            self.emit_mapping_start()?;
+        }
         Ok(self)
     }
 
@@ -419,6 +546,29 @@ where
         self.emit_mapping_start()?;
         Ok(self)
     }
+
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + fmt::Display,
+    {
+        let string = if let State::CheckForTag | State::CheckForDuplicateTag = self.state {
+            match tagged::check_for_tag(value) {
+                MaybeTag::NotTag(string) => string,
+                MaybeTag::Tag(string) => {
+                    return if let State::CheckForDuplicateTag = self.state {
+                        Err(error::serialize_nested_tag())
+                    } else {
+                        self.state = State::FoundTag(string);
+                        Ok(())
+                    };
+                }
+            }
+        } else {
+            value.to_string()
+        };
+
+        self.serialize_str(&string)
+    }
 }
 
 impl<'a, W> ser::SerializeSeq for &'a mut Serializer<W>
@@ -510,6 +660,7 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
+        self.flush_mapping_start()?;
         key.serialize(&mut **self)
     }
 
@@ -520,8 +671,29 @@ where
         value.serialize(&mut **self)
     }
 
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<()>
+    where
+        K: ?Sized + ser::Serialize,
+        V: ?Sized + ser::Serialize,
+    {
+        key.serialize(&mut **self)?;
+        let tagged = matches!(self.state, State::FoundTag(_) | State::FoundAnchor);
+        value.serialize(&mut **self)?;
+        if tagged {
+            self.state = State::AlreadyTagged;
+        }
+        Ok(())
+    }
+
     fn end(self) -> Result<()> {
-        self.emit_mapping_end()
+        if let State::CheckForTag = self.state {
+            self.emit_mapping_start()?;
+        }
+        if !matches!(self.state, State::AlreadyTagged) {
+            self.emit_mapping_end()?;
+        }
+        self.state = State::NothingInParticular;
+        Ok(())
     }
 }
 
@@ -0,0 +1,137 @@
+use crate::error::Location;
+use std::fmt::{self, Write as _};
+
+/// Number of columns a tab is expanded to.
+const TAB_WIDTH: usize = 4;
+
+/// Lines between two marked lines beyond which the lines in between are
+/// elided.
+const MAX_CONTEXT_LINES: usize = 3;
+
+/// Writes the lines of `source` that contain the locations, with a caret
+/// under each location and the lines in between for context, preceded by a
+/// gutter of line numbers:
+///
+/// ```text
+///   |
+/// 2 | b: [1, 2
+///   |    ^
+/// 3 |
+///   | ^
+/// ```
+pub(crate) fn render(
+    formatter: &mut fmt::Formatter,
+    source: &str,
+    locations: &[Location],
+) -> fmt::Result {
+    let mut locations = locations.to_vec();
+    locations.sort_by_key(|location| (location.line(), location.column()));
+    locations.dedup_by_key(|location| (location.line(), location.column()));
+    let last_line = match locations.last() {
+        Some(location) => location.line(),
+        None => return Ok(()),
+    };
+    let lines: Vec<&str> = source
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let gutter = last_line.to_string().len();
+
+    write!(formatter, "{:gutter$} |", "", gutter = gutter)?;
+    let mut previous: Option<usize> = None;
+    let mut rest = &locations[..];
+    while let Some(first) = rest.first() {
+        let line = first.line();
+        let on_line = rest.iter().take_while(|l| l.line() == line).count();
+        let (here, after) = rest.split_at(on_line);
+        rest = after;
+
+        if let Some(previous) = previous {
+            if line - previous > MAX_CONTEXT_LINES + 1 {
+                formatter.write_str("\n...")?;
+            } else {
+                for context in previous + 1..line {
+                    write_line(formatter, &lines, context, gutter)?;
+                }
+            }
+        }
+        write_line(formatter, &lines, line, gutter)?;
+
+        let text = line_text(&lines, line);
+        let mut carets = String::new();
+        let mut width = 0;
+        for location in here {
+            let column = display_width(text.chars().take(location.column() - 1))
+                + (location.column() - 1).saturating_sub(text.chars().count());
+            while width < column {
+                carets.push(' ');
+                width += 1;
+            }
+            carets.push('^');
+            width += 1;
+        }
+        write!(formatter, "\n{:gutter$} | {}", "", carets, gutter = gutter)?;
+        previous = Some(line);
+    }
+    Ok(())
+}
+
+fn line_text<'a>(lines: &[&'a str], line: usize) -> &'a str {
+    lines.get(line - 1).copied().unwrap_or("")
+}
+
+fn write_line(
+    formatter: &mut fmt::Formatter,
+    lines: &[&str],
+    line: usize,
+    gutter: usize,
+) -> fmt::Result {
+    write!(formatter, "\n{:>gutter$} |", line, gutter = gutter)?;
+    let text = line_text(lines, line);
+    if text.is_empty() {
+        return Ok(());
+    }
+    formatter.write_char(' ')?;
+    for ch in text.chars() {
+        if ch == '\t' {
+            write!(formatter, "{:1$}", "", TAB_WIDTH)?;
+        } else {
+            formatter.write_char(ch)?;
+        }
+    }
+    Ok(())
+}
+
+fn display_width(chars: impl Iterator<Item = char>) -> usize {
+    chars.map(char_width).sum()
+}
+
+// The number of terminal columns a character takes up: 2 for East Asian wide
+// and fullwidth characters and for emoji, 0 for combining marks and other
+// zero-width characters, 1 for everything else.
+fn char_width(ch: char) -> usize {
+    match ch {
+        '\t' => TAB_WIDTH,
+        '\u{0300}'..='\u{036F}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{FEFF}' => 0,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}' => 2,
+        _ => 1,
+    }
+}
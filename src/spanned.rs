@@ -1,26 +1,110 @@
 #![allow(missing_docs)]
 
+use crate::path::{PathBuf, Segment};
+use crate::Location;
 use serde::{
-    de::{Error, MapAccess},
-    Deserialize, Deserializer, Serialize, Serializer,
+    de::{value::SeqDeserializer, Error, IntoDeserializer, MapAccess, SeqAccess},
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::{
     borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
     fmt::{self, Formatter},
+    hash::{Hash, Hasher},
     marker::PhantomData,
+    vec,
 };
 
 pub(crate) const NAME: &str = "$__serde_private_Spanned";
 pub(crate) const START: &str = "$__serde_private_start";
-pub(crate) const LENGTH: &str = "$__serde_private_length";
+pub(crate) const END: &str = "$__serde_private_end";
 pub(crate) const PATH: &str = "$__serde_private_path";
 pub(crate) const VALUE: &str = "$__serde_private_value";
 
-pub(crate) const FIELDS: &[&str] = &[START, LENGTH, PATH, VALUE];
+pub(crate) const FIELDS: &[&str] = &[START, END, PATH, VALUE];
+
+/// Deserializer handing a location to the visitor below as an `(index, line,
+/// column)` tuple. Line 0 stands for a location whose line and column are not
+/// known.
+pub(crate) fn location_deserializer<E>(
+    location: Location,
+) -> SeqDeserializer<vec::IntoIter<usize>, E>
+where
+    E: Error,
+{
+    let parts = vec![location.index(), location.line(), location.column()];
+    SeqDeserializer::new(parts.into_iter())
+}
+
+/// Deserializer handing a path to the visitor below as a sequence of its
+/// segments: a string for a key, an integer for an index, unit for an alias
+/// and none for a key that could not be rendered.
+pub(crate) fn path_deserializer<E>(
+    path: &PathBuf,
+) -> SeqDeserializer<vec::IntoIter<SegmentDeserializer<E>>, E>
+where
+    E: Error,
+{
+    let segments: Vec<_> = path
+        .segments()
+        .iter()
+        .map(|segment| SegmentDeserializer {
+            segment: segment.clone(),
+            marker: PhantomData,
+        })
+        .collect();
+    SeqDeserializer::new(segments.into_iter())
+}
+
+pub(crate) struct SegmentDeserializer<E> {
+    segment: Segment,
+    marker: PhantomData<E>,
+}
+
+impl<'de, E> Deserializer<'de> for SegmentDeserializer<E>
+where
+    E: Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.segment {
+            Segment::Key(key) => visitor.visit_string(key),
+            Segment::Index(index) => visitor.visit_u64(index as u64),
+            Segment::Alias => visitor.visit_unit(),
+            Segment::Unknown => visitor.visit_none(),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, E> IntoDeserializer<'de, E> for SegmentDeserializer<E>
+where
+    E: Error,
+{
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
 
 /// An wrapper which records the location of an item as byte indices into the
 /// source text.
 ///
+/// The span covers the item exactly as it was written, including the quotes
+/// around a quoted scalar, the `|` or `>` header of a block scalar, and any
+/// `!tag` or `&anchor` in front of the item. Trailing line breaks after a
+/// block scalar or block collection are not included.
+///
 /// # Examples
 ///
 /// Primitive values can be wrapped with [`Spanned<T>`] to get their location,
@@ -41,6 +125,12 @@ pub(crate) const FIELDS: &[&str] = &[START, LENGTH, PATH, VALUE];
 /// assert_eq!(doc.name.value, "Document");
 /// assert_eq!(doc.name.start, 6);
 /// assert_eq!(doc.name.len, "Document".len());
+///
+/// // Line and column numbers start at 1, as in error messages.
+/// let start = doc.name.start_location.unwrap();
+/// assert_eq!(start.line(), 1);
+/// assert_eq!(start.column(), 7);
+/// assert_eq!(doc.name.end_location.unwrap().column(), 15);
 /// # Ok(())
 /// # }
 /// ```
@@ -67,7 +157,7 @@ pub(crate) const FIELDS: &[&str] = &[START, LENGTH, PATH, VALUE];
 /// # }
 /// ```
 ///
-/// Note that a map item starts after first key.
+/// The span of a mapping covers all of its entries.
 ///
 /// ```rust
 /// # use serde_yaml::Spanned;
@@ -86,22 +176,75 @@ pub(crate) const FIELDS: &[&str] = &[START, LENGTH, PATH, VALUE];
 /// let doc: Document = serde_yaml::from_str(yaml)?;
 ///
 /// let spanned_text = &yaml[doc.nested.span()];
-/// assert_eq!(spanned_text, ": 1\n  second: 2");
+/// assert_eq!(spanned_text, "first: 1\n  second: 2");
 /// # Ok(())
 /// # }
 /// ```
 ///
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Map keys can be spanned too. A [`Spanned<T>`] compares, orders and hashes
+/// by its value alone, so entries can still be looked up without knowing
+/// where the key was written.
+///
+/// ```rust
+/// # use serde_yaml::Spanned;
+/// # use std::collections::HashMap;
+/// # fn main() -> Result<(), serde_yaml::Error> {
+/// let yaml = "first: 1\nsecond: 2";
+/// let map: HashMap<Spanned<String>, u32> = serde_yaml::from_str(yaml)?;
+///
+/// let (key, value) = map.get_key_value("second").unwrap();
+/// assert_eq!(*value, 2);
+/// assert_eq!(&yaml[key.span()], "second");
+/// assert_eq!(key.start_location.unwrap().line(), 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Debug, Clone)]
 pub struct Spanned<T> {
     pub value: T,
     pub start: usize,
-    pub path: String,
+    pub path: PathBuf,
     pub len: usize,
+    /// Location of the first character of the value. Always present when
+    /// deserialized from YAML, but None if made by [`Spanned::new`].
+    pub start_location: Option<Location>,
+    /// Location of the character following the value. Always present when
+    /// deserialized from YAML, but None if made by [`Spanned::new`].
+    pub end_location: Option<Location>,
 }
 
 impl<T> Spanned<T> {
-    pub const fn new(start: usize, len: usize, path: String, value: T) -> Self {
-        Spanned { value, start, len, path }
+    /// A value spanning `len` bytes from byte index `start`, at a path like
+    /// `a.b[3].c`. A path that does not parse as a [`PathBuf`] is taken as a
+    /// single key.
+    pub fn new(start: usize, len: usize, path: String, value: T) -> Self {
+        let path = path.parse().unwrap_or_else(|_| {
+            let mut buf = PathBuf::new();
+            buf.push(Segment::Key(path));
+            buf
+        });
+        Spanned {
+            value,
+            start,
+            path,
+            len,
+            start_location: None,
+            end_location: None,
+        }
+    }
+
+    /// A value spanning from `start` up to `end`, with their lines and
+    /// columns.
+    pub fn with_locations(start: Location, end: Location, path: PathBuf, value: T) -> Self {
+        Spanned {
+            value,
+            start: start.index(),
+            path,
+            len: end.index() - start.index(),
+            start_location: Some(start),
+            end_location: Some(end),
+        }
     }
 
     /// The value's location in source as an inclusive range.
@@ -118,6 +261,32 @@ impl<T> Spanned<T> {
     }
 }
 
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: PartialOrd> PartialOrd for Spanned<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord> Ord for Spanned<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
 impl<T, Q> AsRef<Q> for Spanned<T>
 where
     T: AsRef<Q>,
@@ -142,6 +311,12 @@ impl<T> Borrow<T> for Spanned<T> {
     }
 }
 
+impl Borrow<str> for Spanned<String> {
+    fn borrow(&self) -> &str {
+        &self.value
+    }
+}
+
 impl<T> BorrowMut<T> for Spanned<T> {
     fn borrow_mut(&mut self) -> &mut T {
         &mut self.value
@@ -180,7 +355,7 @@ where
             return Err(Error::custom("spanned start key not found"));
         }
 
-        let start: usize = visitor.next_value()?;
+        let (start, start_line, start_column) = visitor.next_value()?;
 
         if visitor.next_key()? != Some(VALUE) {
             return Err(Error::custom("spanned value key not found"));
@@ -188,19 +363,104 @@ where
 
         let value: T = visitor.next_value()?;
 
-        if visitor.next_key()? != Some(LENGTH) {
-            return Err(Error::custom("spanned length key not found"));
+        if visitor.next_key()? != Some(END) {
+            return Err(Error::custom("spanned end key not found"));
         }
 
-        let length: usize = visitor.next_value()?;
+        let (end, end_line, end_column) = visitor.next_value()?;
 
         if visitor.next_key()? != Some(PATH) {
-            return Err(Error::custom("spanned length key not found"));
+            return Err(Error::custom("spanned path key not found"));
         }
 
-        let path: String = visitor.next_value()?;
+        let PathRepr(path) = visitor.next_value()?;
+
+        if start_line == 0 || end_line == 0 {
+            return Ok(Spanned {
+                value,
+                start,
+                path,
+                len: end - start,
+                start_location: None,
+                end_location: None,
+            });
+        }
+        let start = Location::new(start, start_line, start_column);
+        let end = Location::new(end, end_line, end_column);
+        Ok(Spanned::with_locations(start, end, path, value))
+    }
+}
+
+/// The path of a spanned value, from the segments handed over by
+/// [`path_deserializer`], or parsed from a string.
+struct PathRepr(PathBuf);
 
-        Ok(Spanned::new(start, length, path, value))
+impl<'de> Deserialize<'de> for PathRepr {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        de.deserialize_any(PathVisitor).map(PathRepr)
+    }
+}
+
+struct PathVisitor;
+
+impl<'de> serde::de::Visitor<'de> for PathVisitor {
+    type Value = PathBuf;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a path")
+    }
+
+    fn visit_str<E: Error>(self, path: &str) -> Result<PathBuf, E> {
+        path.parse().map_err(E::custom)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<PathBuf, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut path = PathBuf::new();
+        while let Some(SegmentRepr(segment)) = seq.next_element()? {
+            path.push(segment);
+        }
+        Ok(path)
+    }
+}
+
+struct SegmentRepr(Segment);
+
+impl<'de> Deserialize<'de> for SegmentRepr {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        de.deserialize_any(SegmentVisitor).map(SegmentRepr)
+    }
+}
+
+struct SegmentVisitor;
+
+impl<'de> serde::de::Visitor<'de> for SegmentVisitor {
+    type Value = Segment;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a path segment")
+    }
+
+    fn visit_str<E: Error>(self, key: &str) -> Result<Segment, E> {
+        Ok(Segment::Key(key.to_owned()))
+    }
+
+    fn visit_string<E: Error>(self, key: String) -> Result<Segment, E> {
+        Ok(Segment::Key(key))
+    }
+
+    fn visit_u64<E: Error>(self, index: u64) -> Result<Segment, E> {
+        Ok(Segment::Index(index as usize))
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Segment, E> {
+        Ok(Segment::Alias)
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Segment, E> {
+        Ok(Segment::Unknown)
     }
 }
 
@@ -208,12 +468,54 @@ where
 mod tests {
     use super::*;
 
+    /// Build the expected `Spanned` for `len` bytes of `src` starting at
+    /// `start`, counting lines and columns independently of libyaml.
+    fn at<T>(src: &str, start: usize, len: usize, path: String, value: T) -> Spanned<T> {
+        let location = |index: usize| {
+            let before = &src[..index];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            Location::new(
+                index,
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        };
+        let path = path.parse().unwrap();
+        Spanned::with_locations(location(start), location(start + len), path, value)
+    }
+
+    // Equality ignores spans, but the Debug representation includes them.
+    fn assert_spans_eq<T: fmt::Debug>(got: &T, expected: &T) {
+        assert_eq!(format!("{:?}", got), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn new_without_locations() {
+        let spanned = Spanned::new(6, 8, String::from("a.b[3]"), "Document");
+        assert_eq!(spanned.span(), 6..14);
+        assert_eq!(spanned.path, "a.b[3]");
+        assert_eq!(spanned.start_location, None);
+        assert_eq!(spanned.end_location, None);
+
+        let value = Spanned::new(
+            0,
+            2,
+            String::from("[unclosed"),
+            crate::value::SpannedNode::Null,
+        );
+        assert_eq!(
+            value.path.segments(),
+            [Segment::Key(String::from("[unclosed"))]
+        );
+        let got: Spanned<()> = crate::from_spanned_value(value).unwrap();
+        assert_eq!(got.span(), 0..2);
+        assert_eq!(got.start_location, None);
+    }
+
     #[test]
     fn serializing_a_spanned_t_is_a_noop() {
-        let value = Spanned::new(0, 0, String::from(".[0]"), "Hello, World!");
-        let should_be = r#"---
-"Hello, World!"
-"#;
+        let value = at("", 0, 0, String::from(".[0]"), "Hello, World!");
+        let should_be = crate::to_string("Hello, World!").unwrap();
 
         let got = crate::to_string(&value).unwrap();
 
@@ -223,24 +525,25 @@ mod tests {
     #[test]
     fn deserialize_spanned_item() {
         let src = "42";
-        let should_be = Spanned::new(0, src.len(), String::from("."), 42);
+        let should_be = at(src, 0, src.len(), String::from("."), 42);
 
         let got: Spanned<i32> = crate::from_str(src).unwrap();
 
-        assert_eq!(got, should_be);
+        assert_spans_eq(&got, &should_be);
     }
 
     #[test]
     fn deserialize_sequence() {
         let src = " [1, 22, 333]";
-        let should_be = Spanned::new(
+        let should_be = at(
+            src,
             1,
             src.len() - 1,
             String::from("."),
             vec![
-                Spanned::new(2, 1, String::from(".[0]"), 1),
-                Spanned::new(5, 2, String::from(".[1]"), 22),
-                Spanned::new(9, 3, String::from(".[2]"), 333),
+                at(src, 2, 1, String::from(".[0]"), 1),
+                at(src, 5, 2, String::from(".[1]"), 22),
+                at(src, 9, 3, String::from(".[2]"), 333),
             ],
         );
 
@@ -252,7 +555,7 @@ mod tests {
         assert_eq!("22", &src[got.value[1].span()]);
         assert_eq!("333", &src[got.value[2].span()]);
         assert_eq!("[1, 22, 333]", &src[got.span()]);
-        assert_eq!(got, should_be);
+        assert_spans_eq(&got, &should_be);
     }
 
     #[test]
@@ -269,40 +572,53 @@ mod tests {
 - name: second
   values: [4, 5]"#;
         let items = vec![
-            Spanned::new(
-                7,
-                59,
+            at(
+                src,
+                3,
+                31,
                 String::from(".[0]"),
                 Item {
-                    name: Spanned::new(9, 5, String::from(".[0].name"), String::from("first")),
-                    values: Spanned::new(
+                    name: at(src, 9, 5, String::from(".[0].name"), String::from("first")),
+                    values: at(
+                        src,
                         25,
                         9,
                         String::from(".[0].values"),
                         vec![
-                            Spanned::new(26, 1, String::from(".[0].values[0]"), 1),
-                            Spanned::new(29, 1, String::from(".[0].values[1]"), 2),
-                            Spanned::new(32, 1, String::from(".[0].values[2]"), 3),
+                            at(src, 26, 1, String::from(".[0].values[0]"), 1),
+                            at(src, 29, 1, String::from(".[0].values[1]"), 2),
+                            at(src, 32, 1, String::from(".[0].values[2]"), 3),
                         ],
                     ),
                 },
             ),
-            Spanned::new(
-                41,
-                23,
+            at(
+                src,
+                37,
+                29,
                 String::from(".[1]"),
                 Item {
-                    name: Spanned::new(43, 6, String::from(".[1].name"), String::from("second")),
-                    values: Spanned::new(
+                    name: at(
+                        src,
+                        43,
+                        6,
+                        String::from(".[1].name"),
+                        String::from("second"),
+                    ),
+                    values: at(
+                        src,
                         60,
                         6,
                         String::from(".[1].values"),
-                        vec![Spanned::new(61, 1, String::from(".[1].values[0]"), 4), Spanned::new(64, 1, String::from(".[1].values[1]"), 5)],
+                        vec![
+                            at(src, 61, 1, String::from(".[1].values[0]"), 4),
+                            at(src, 64, 1, String::from(".[1].values[1]"), 5),
+                        ],
                     ),
                 },
             ),
         ];
-        let should_be = Spanned::new(1, src.len(), String::from("."), items);
+        let should_be = at(src, 1, src.len() - 1, String::from("."), items);
 
         let got: Spanned<Vec<Spanned<Item>>> = crate::from_str(src).unwrap();
 
@@ -311,7 +627,52 @@ mod tests {
         let second_values = &got.value[1].value.values;
         assert_eq!("[4, 5]", &src[second_values.span()]);
 
-        assert_eq!(got, should_be);
+        assert_spans_eq(&got, &should_be);
+    }
+
+    #[test]
+    fn tricky_scalars() {
+        let corpus = [
+            "plain",
+            "plain\n  over\n  lines",
+            r#""double \"quoted\" é\n""#,
+            "'single ''quoted'''",
+            "\"multi\n  line\"",
+            "|\n  literal\n  block\n",
+            "|+\n  keep\n\n",
+            ">-\n  folded\n\n  block\n\n\n",
+            "!custom tagged",
+            "&anchor anchored",
+            "!!str 123",
+            "\"\"",
+            "ünïcödé",
+        ];
+
+        for scalar in &corpus {
+            let src = format!("- {}\n- after\n", scalar);
+            let got: Vec<Spanned<crate::Value>> = crate::from_str(&src).unwrap();
+            assert_eq!(&src[got[0].span()], scalar.trim_end(), "{:?}", scalar);
+            assert_eq!(&src[got[1].span()], "after", "{:?}", scalar);
+
+            let expected = at(&src, got[0].start, got[0].len, String::from("."), ());
+            assert_eq!(
+                got[0].start_location, expected.start_location,
+                "{:?}",
+                scalar
+            );
+            assert_eq!(got[0].end_location, expected.end_location, "{:?}", scalar);
+        }
+    }
+
+    #[test]
+    fn deserialize_block_collections() {
+        let src = "outer:\n  - a: 1\n    b: [2]\n  - c\n\n# trailing comment\nnext: 3\n";
+
+        let got: std::collections::BTreeMap<String, Spanned<crate::Value>> =
+            crate::from_str(src).unwrap();
+
+        assert_eq!(&src[got["outer"].span()], "- a: 1\n    b: [2]\n  - c");
+        assert_eq!(&src[got["next"].span()], "3");
     }
 
     #[test]
@@ -327,20 +688,21 @@ mod tests {
 
         let src = "nested:\n  value: Hello, World!";
         let should_be = Document {
-            nested: Spanned {
-                start: src.rfind(":").unwrap(),
-                len: ": Hello, World!".len(),
-                value: Nested {
+            nested: at(
+                src,
+                src.find("value").unwrap(),
+                "value: Hello, World!".len(),
+                String::from("nested"),
+                Nested {
                     value: String::from("Hello, World!"),
                 },
-                path: String::from("nested"),
-            },
+            ),
         };
 
         let got: Document = crate::from_str(src).unwrap();
 
         assert_eq!(got.nested.end(), src.len() - 1);
-        assert_eq!(got, should_be);
+        assert_spans_eq(&got, &should_be);
     }
 
     #[test]
@@ -357,20 +719,72 @@ mod tests {
 
         let src = "nested: {}";
         let should_be = Document {
-            nested: Spanned {
-                start: 8,
-                len: 2,
-                value: Nested {
+            nested: at(
+                src,
+                8,
+                2,
+                String::from("nested"),
+                Nested {
                     value: String::new(),
                 },
-                path: String::from("nested"),
-            },
+            ),
         };
 
         let got: Document = crate::from_str(src).unwrap();
 
-        assert_eq!(got, should_be);
+        assert_spans_eq(&got, &should_be);
         let spanned_text = &src[got.nested.span()];
         assert_eq!(spanned_text, "{}");
     }
+
+    #[test]
+    fn spanned_map_keys() {
+        let src = "first: 1\n\"second\": 2\n? [third]\n: 3\n";
+
+        let got: std::collections::HashMap<Spanned<crate::Value>, Spanned<i32>> =
+            crate::from_str(src).unwrap();
+        let mut keys: Vec<&str> = got.keys().map(|key| &src[key.span()]).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["\"second\"", "[third]", "first"]);
+
+        let src = "first: 1\nsecond:   2\n";
+        let got: std::collections::HashMap<Spanned<String>, Spanned<i32>> =
+            crate::from_str(src).unwrap();
+        let (key, value) = got.get_key_value("second").unwrap();
+        let expected = at(src, 9, 6, String::from("."), String::from("second"));
+        assert_spans_eq(key, &expected);
+        assert_eq!(value.start, 19);
+        assert_eq!(got["second"].value, 2);
+    }
+
+    #[test]
+    fn spanned_merged_keys() {
+        let src = "base: &base\n  inherited: 1\nderived:\n  <<: *base\n  own: 2\n";
+
+        let got: std::collections::BTreeMap<
+            String,
+            std::collections::BTreeMap<Spanned<String>, i32>,
+        > = crate::from_str(src).unwrap();
+        let spans: Vec<&str> = got["derived"].keys().map(|key| &src[key.span()]).collect();
+        assert_eq!(spans, ["inherited", "own"]);
+        assert_eq!(
+            got["derived"].get_key_value("inherited").unwrap().0.start,
+            14
+        );
+    }
+
+    #[test]
+    fn path_through_alias() {
+        let src = "a: &x [1]\nb: *x\n";
+
+        let got: std::collections::BTreeMap<String, Vec<Spanned<i32>>> =
+            crate::from_str(src).unwrap();
+        let expected = [
+            Segment::Key(String::from("b")),
+            Segment::Alias,
+            Segment::Index(0),
+        ];
+        assert_eq!(got["b"][0].path.segments(), expected);
+        assert_eq!(got["b"][0].path, "b[0]");
+    }
 }
@@ -0,0 +1,375 @@
+use crate::value::{to_value, Value};
+use crate::Error;
+use serde::de::{self, MapAccess, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Debug, Display};
+use std::iter;
+use std::str::FromStr;
+
+/// Name of the newtype struct containing a timestamp's text when serializing,
+/// and the key of the single-entry map in which the deserializer hands a
+/// timestamp to `Value` and `Timestamp`.
+pub(crate) const NAME: &str = "$__serde_yaml_private_timestamp";
+
+/// A point in time written in YAML's `!!timestamp` syntax, either a date like
+/// `2001-12-14` or a date and time like `2001-12-14t21:59:43.10-05:00`.
+///
+/// A `Timestamp` deserializes from a scalar tagged `!!timestamp` and from any
+/// string in timestamp syntax. In a [`Value`](crate::Value), scalars tagged
+/// `!!timestamp` are always timestamps, and untagged ones are under the
+/// [`Schema::Yaml11`](crate::Schema::Yaml11) schema.
+///
+/// Timestamps are written in canonical form, with `T` between date and time,
+/// two digits for every field but the year, no trailing zeros in the fraction
+/// of a second, and `Z` for UTC. They are tagged `!!timestamp` unless the
+/// serializer's schema reads them back as timestamps anyway.
+///
+/// ```
+/// use serde_yaml::Timestamp;
+///
+/// let timestamp: Timestamp = serde_yaml::from_str("2001-12-14 21:59:43.10 -5").unwrap();
+/// assert_eq!((timestamp.year(), timestamp.month(), timestamp.day()), (2001, 12, 14));
+/// assert_eq!(timestamp.nanosecond(), 100_000_000);
+/// assert_eq!(timestamp.offset_minutes(), Some(-300));
+/// assert_eq!(timestamp.to_string(), "2001-12-14T21:59:43.1-05:00");
+///
+/// let date: Timestamp = "2002-12-14".parse().unwrap();
+/// assert!(!date.has_time());
+/// assert_eq!(serde_yaml::to_string(&date).unwrap(), "!!timestamp 2002-12-14\n");
+///
+/// assert!("2002-02-30".parse::<Timestamp>().is_err());
+/// ```
+///
+/// Two timestamps are equal if they are written the same way in canonical
+/// form, so the same instant in different time zones compares unequal, and
+/// ordering compares the fields in order from the year down to the offset.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    year: u16,
+    month: u8,
+    day: u8,
+    time: Option<Time>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Time {
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+    /// Offset from UTC in minutes, or None if no time zone was given, which
+    /// YAML takes to mean UTC.
+    offset: Option<i16>,
+}
+
+impl Timestamp {
+    /// The year, from 0 to 9999.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1 to 31.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Whether the timestamp has a time of day, as opposed to being only a
+    /// date.
+    pub fn has_time(&self) -> bool {
+        self.time.is_some()
+    }
+
+    /// The hour, from 0 to 23. Zero for a date.
+    pub fn hour(&self) -> u8 {
+        self.time.map_or(0, |time| time.hour)
+    }
+
+    /// The minute, from 0 to 59. Zero for a date.
+    pub fn minute(&self) -> u8 {
+        self.time.map_or(0, |time| time.minute)
+    }
+
+    /// The second, from 0 to 59. Zero for a date.
+    pub fn second(&self) -> u8 {
+        self.time.map_or(0, |time| time.second)
+    }
+
+    /// The fraction of the second in nanoseconds. Digits of the fraction
+    /// beyond nanoseconds are dropped.
+    pub fn nanosecond(&self) -> u32 {
+        self.time.map_or(0, |time| time.nanosecond)
+    }
+
+    /// The time zone's offset from UTC in minutes, or None if the timestamp
+    /// has no time zone, which YAML takes to mean UTC.
+    pub fn offset_minutes(&self) -> Option<i16> {
+        self.time.and_then(|time| time.offset)
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut cursor = Cursor(s.as_bytes());
+        let year = cursor.digits(4, 4)?;
+        cursor.expect(b'-')?;
+        let (month, month_digits) = cursor.digits_counted(1, 2)?;
+        cursor.expect(b'-')?;
+        let (day, day_digits) = cursor.digits_counted(1, 2)?;
+        let date = Timestamp {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            time: None,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        if cursor.0.is_empty() {
+            // Only the full form allows single digit months and days.
+            return if month_digits == 2 && day_digits == 2 {
+                Some(date)
+            } else {
+                None
+            };
+        }
+
+        let separated = cursor.skip_blanks() > 0
+            || cursor.expect(b'T').is_some()
+            || cursor.expect(b't').is_some();
+        if !separated {
+            return None;
+        }
+        let hour = cursor.digits(1, 2)?;
+        cursor.expect(b':')?;
+        let minute = cursor.digits(2, 2)?;
+        cursor.expect(b':')?;
+        let second = cursor.digits(2, 2)?;
+        let mut nanosecond = 0;
+        if cursor.expect(b'.').is_some() {
+            let (fraction, digits) = cursor.digits_counted(0, usize::MAX)?;
+            nanosecond = fraction;
+            if digits < 9 {
+                nanosecond *= 10u32.pow(9 - digits as u32);
+            }
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        cursor.skip_blanks();
+        let offset = if cursor.0.is_empty() {
+            None
+        } else if cursor.expect(b'Z').is_some() {
+            Some(0)
+        } else {
+            let negative = match cursor.0.first()? {
+                b'+' => false,
+                b'-' => true,
+                _ => return None,
+            };
+            cursor.0 = &cursor.0[1..];
+            let hours = cursor.digits(1, 2)?;
+            let minutes = match cursor.expect(b':') {
+                Some(()) => cursor.digits(2, 2)?,
+                None => 0,
+            };
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = (hours * 60 + minutes) as i16;
+            Some(if negative { -offset } else { offset })
+        };
+        if !cursor.0.is_empty() {
+            return None;
+        }
+
+        Some(Timestamp {
+            time: Some(Time {
+                hour: hour as u8,
+                minute: minute as u8,
+                second: second as u8,
+                nanosecond,
+                offset,
+            }),
+            ..date
+        })
+    }
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        let rest = self.0.strip_prefix(&[byte])?;
+        self.0 = rest;
+        Some(())
+    }
+
+    fn skip_blanks(&mut self) -> usize {
+        let blanks = self
+            .0
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        self.0 = &self.0[blanks..];
+        blanks
+    }
+
+    fn digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        self.digits_counted(min, max).map(|(value, _digits)| value)
+    }
+
+    /// Parses between `min` and `max` decimal digits, of which only the first
+    /// 9 count towards the value. Returns the value and the number of digits.
+    fn digits_counted(&mut self, min: usize, max: usize) -> Option<(u32, usize)> {
+        let digits = self
+            .0
+            .iter()
+            .take(max)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits < min {
+            return None;
+        }
+        let value = self.0[..digits.min(9)]
+            .iter()
+            .fold(0, |value, digit| value * 10 + u32::from(digit - b'0'));
+        self.0 = &self.0[digits..];
+        Some((value, digits))
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    /// Parses a timestamp in any of the forms YAML allows.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Timestamp::parse(s)
+            .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(s), &"a timestamp"))
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:04}-{:02}-{:02}",
+            self.year, self.month, self.day
+        )?;
+        let time = match self.time {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+        write!(
+            formatter,
+            "T{:02}:{:02}:{:02}",
+            time.hour, time.minute, time.second
+        )?;
+        if time.nanosecond != 0 {
+            let fraction = format!("{:09}", time.nanosecond);
+            write!(formatter, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        match time.offset {
+            None => Ok(()),
+            Some(0) => formatter.write_str("Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                write!(formatter, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+            }
+        }
+    }
+}
+
+impl Debug for Timestamp {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Timestamp({})", self)
+    }
+}
+
+/// A timestamp is serialized as its canonical text in a private newtype
+/// struct, which the YAML serializer writes as a `!!timestamp` scalar. Other
+/// serializers see the string.
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(NAME, &self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TimestampVisitor)
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a timestamp")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        Timestamp::parse(s).ok_or_else(|| E::invalid_value(Unexpected::Str(s), &self))
+    }
+
+    // Reached through `deserialize_any`, for example when the timestamp is
+    // inside an untagged enum.
+    fn visit_map<A>(self, mut map: A) -> Result<Timestamp, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        if map.next_key::<String>()?.as_deref() != Some(NAME) {
+            return Err(de::Error::invalid_type(Unexpected::Map, &self));
+        }
+        let text: String = map.next_value()?;
+        self.visit_str(&text)
+    }
+}
+
+/// Recovers the text from the content of a [`NAME`] newtype struct.
+pub(crate) fn text_of<T>(content: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    match to_value(content)? {
+        Value::String(text) => Ok(text),
+        _ => Err(de::Error::custom("expected the text of a timestamp")),
+    }
+}
+
+/// Hands a timestamp to the visitor of `deserialize_any` as a map from the
+/// private key [`NAME`] to its canonical text.
+pub(crate) fn visit<'de, V, E>(visitor: V, timestamp: &Timestamp) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    let mut map = de::value::MapDeserializer::new(iter::once((NAME, timestamp.to_string())));
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
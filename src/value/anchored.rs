@@ -0,0 +1,103 @@
+use crate::value::{to_value, Value};
+use crate::Error;
+use serde::ser::{Error as _, Serialize, SerializeMap, Serializer};
+
+/// Name of the newtype struct written as the key of a single-entry map to
+/// mark the map as an anchored value, the `&anchor` being the struct's
+/// content. Also the key under which the deserializer hands the anchor's name
+/// to `Value`.
+pub(crate) const ANCHOR: &str = "$__serde_yaml_private_anchor";
+/// Key under which the deserializer hands the anchored node to `Value`.
+pub(crate) const VALUE: &str = "$__serde_yaml_private_value";
+/// Name of the newtype struct containing an `*alias`. Also the key under which
+/// the deserializer hands the alias's name to `Value`.
+pub(crate) const ALIAS: &str = "$__serde_yaml_private_alias";
+
+/// A value with YAML's `&anchor` syntax, which later `*alias` nodes in the
+/// same document refer to.
+///
+/// Anchors and aliases are only kept in a [`Value`] when deserializing with
+/// [`DeserializerOptions::preserve_anchors`](crate::DeserializerOptions::preserve_anchors),
+/// and are written back out when the value is serialized.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_yaml::value::AnchoredValue;
+/// use serde_yaml::{Deserializer, DeserializerOptions, Value};
+///
+/// let yaml = "base: &defaults {retries: 3}\nservice: *defaults\n";
+///
+/// let options = DeserializerOptions::new().preserve_anchors(true);
+/// let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+///
+/// let anchored = match &value["base"] {
+///     Value::Anchored(anchored) => anchored,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(anchored.anchor, "defaults");
+/// assert_eq!(anchored.value["retries"], 3);
+/// assert_eq!(value["service"], Value::Alias("defaults".to_owned()));
+///
+/// assert_eq!(serde_yaml::to_string(&value).unwrap(), "base: &defaults\n  retries: 3\nservice: *defaults\n");
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Hash, Debug)]
+pub struct AnchoredValue {
+    /// The name of the anchor, without the leading `&`.
+    pub anchor: String,
+    #[allow(missing_docs)]
+    pub value: Value,
+}
+
+/// An anchored value is serialized as a map with a single entry whose key is
+/// the anchor in a private newtype struct, which the YAML serializer writes as
+/// `&anchor` in front of the entry's value. Other serializers see a map from
+/// `"&anchor"` to the value.
+impl Serialize for AnchoredValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&SerializeAnchor(&self.anchor), &self.value)?;
+        map.end()
+    }
+}
+
+struct SerializeAnchor<'a>(&'a str);
+
+impl<'a> Serialize for SerializeAnchor<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(ANCHOR, &format!("&{}", self.0))
+    }
+}
+
+/// An alias is serialized as its name in a private newtype struct, which the
+/// YAML serializer writes as `*alias`. Other serializers see the string
+/// `"*alias"`.
+pub(crate) fn serialize_alias<S>(name: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(ALIAS, &format!("*{}", name))
+}
+
+/// Recovers the name from the content of an [`ANCHOR`] or [`ALIAS`] newtype
+/// struct, which is the name preceded by `sigil`.
+pub(crate) fn name_of<T>(sigil: char, content: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    match to_value(content)? {
+        Value::String(string) => match string.strip_prefix(sigil) {
+            Some(name) if !name.is_empty() => Ok(name.to_owned()),
+            _ => Err(Error::custom(format_args!(
+                "expected `{}` followed by a name",
+                sigil
+            ))),
+        },
+        _ => Err(Error::custom("expected the name of an anchor or alias")),
+    }
+}
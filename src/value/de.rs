@@ -1,3 +1,9 @@
+use crate::timestamp;
+use crate::value::anchored::{self, AnchoredValue};
+use crate::value::tagged::TagStringVisitor;
+use crate::value::TaggedValue;
+#[cfg(feature = "arbitrary_precision")]
+use crate::Number;
 use crate::{number, Error, Mapping, Sequence, Value};
 use serde::de::{
     self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, Error as SError, Expected,
@@ -5,6 +11,7 @@ use serde::de::{
 };
 use serde::forward_to_deserialize_any;
 use std::fmt;
+use std::mem;
 use std::vec;
 
 impl<'de> Deserialize<'de> for Value {
@@ -32,21 +39,37 @@ impl<'de> Deserialize<'de> for Value {
             where
                 E: SError,
             {
-                Ok(Value::Number(i.into()))
+                Ok(Value::Number(number::take_lent(i.into())))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_i128<E>(self, i: i128) -> Result<Value, E>
+            where
+                E: SError,
+            {
+                Ok(Value::Number(number::take_lent(i.into())))
             }
 
             fn visit_u64<E>(self, u: u64) -> Result<Value, E>
             where
                 E: SError,
             {
-                Ok(Value::Number(u.into()))
+                Ok(Value::Number(number::take_lent(u.into())))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_u128<E>(self, u: u128) -> Result<Value, E>
+            where
+                E: SError,
+            {
+                Ok(Value::Number(number::take_lent(u.into())))
             }
 
             fn visit_f64<E>(self, f: f64) -> Result<Value, E>
             where
                 E: SError,
             {
-                Ok(Value::Number(f.into()))
+                Ok(Value::Number(number::take_lent(f.into())))
             }
 
             fn visit_str<E>(self, s: &str) -> Result<Value, E>
@@ -63,6 +86,20 @@ impl<'de> Deserialize<'de> for Value {
                 Ok(Value::String(s))
             }
 
+            fn visit_bytes<E>(self, b: &[u8]) -> Result<Value, E>
+            where
+                E: SError,
+            {
+                Ok(Value::Binary(b.to_owned()))
+            }
+
+            fn visit_byte_buf<E>(self, b: Vec<u8>) -> Result<Value, E>
+            where
+                E: SError,
+            {
+                Ok(Value::Binary(b))
+            }
+
             fn visit_unit<E>(self) -> Result<Value, E>
             where
                 E: SError,
@@ -103,15 +140,68 @@ impl<'de> Deserialize<'de> for Value {
             {
                 let mut values = Mapping::new();
 
-                while let Some((key, value)) = visitor.next_entry()? {
+                // The deserializer hands over timestamps, arbitrary-precision
+                // numbers, and anchors and aliases when asked to preserve
+                // them, as maps with private keys.
+                let mut first = true;
+                while let Some(key) = visitor.next_key::<Value>()? {
+                    if mem::take(&mut first) {
+                        match key.as_str() {
+                            Some(anchored::ANCHOR) => {
+                                let anchor = visitor.next_value()?;
+                                if visitor.next_key::<String>()?.as_deref() != Some(anchored::VALUE)
+                                {
+                                    return Err(SError::custom("expected an anchored value"));
+                                }
+                                let value = visitor.next_value()?;
+                                return Ok(Value::Anchored(Box::new(AnchoredValue {
+                                    anchor,
+                                    value,
+                                })));
+                            }
+                            Some(anchored::ALIAS) => {
+                                return Ok(Value::Alias(visitor.next_value()?))
+                            }
+                            Some(timestamp::NAME) => {
+                                let text: String = visitor.next_value()?;
+                                return text.parse().map(Value::Timestamp).map_err(SError::custom);
+                            }
+                            #[cfg(feature = "arbitrary_precision")]
+                            Some(number::NAME) => {
+                                let digits: String = visitor.next_value()?;
+                                return match Number::from_decimal(&digits) {
+                                    Some(number) => Ok(Value::Number(number::take_lent(number))),
+                                    None => Err(SError::custom("expected the digits of a number")),
+                                };
+                            }
+                            _ => {}
+                        }
+                    }
+                    let value = visitor.next_value()?;
                     values.insert(key, value);
                 }
 
                 Ok(Value::Mapping(values))
             }
+
+            fn visit_enum<A>(self, data: A) -> Result<Value, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, contents) = data.variant_seed(TagStringVisitor)?;
+                let value = contents.newtype_variant()?;
+                Ok(Value::Tagged(Box::new(TaggedValue { tag, value })))
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        deserializer.deserialize_newtype_struct(super::NAME, ValueVisitor)
     }
 }
 
@@ -120,9 +210,9 @@ impl Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::Number(n) => n.deserialize_any(visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 }
@@ -131,8 +221,25 @@ fn visit_sequence<'de, V>(sequence: Sequence, visitor: V) -> Result<V::Value, Er
 where
     V: Visitor<'de>,
 {
-    let len = sequence.len();
+    visit_elements(SeqDeserializer::new(sequence), visitor)
+}
+
+// Visits the elements of a `!!pairs` or `!!omap` sequence, each of which may
+// be a pair written as a mapping with a single entry.
+fn visit_pairs<'de, V>(sequence: Sequence, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
     let mut deserializer = SeqDeserializer::new(sequence);
+    deserializer.pairs = true;
+    visit_elements(deserializer, visitor)
+}
+
+fn visit_elements<'de, V>(mut deserializer: SeqDeserializer, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = deserializer.iter.len();
     let seq = visitor.visit_seq(&mut deserializer)?;
     let remaining = deserializer.iter.len();
     if remaining == 0 {
@@ -169,8 +276,13 @@ impl<'de> Deserializer<'de> for Value {
             Value::Bool(v) => visitor.visit_bool(v),
             Value::Number(n) => n.deserialize_any(visitor),
             Value::String(v) => visitor.visit_string(v),
+            Value::Binary(v) => visitor.visit_byte_buf(v),
+            Value::Timestamp(v) => timestamp::visit(visitor, &v),
             Value::Sequence(v) => visit_sequence(v, visitor),
             Value::Mapping(v) => visit_mapping(v, visitor),
+            Value::Tagged(tagged) => visitor.visit_enum(*tagged),
+            Value::Anchored(anchored) => anchored.value.deserialize_any(visitor),
+            Value::Alias(name) => Err(unresolved_alias(&name)),
         }
     }
 
@@ -178,9 +290,9 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::Bool(v) => visitor.visit_bool(v),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -258,14 +370,17 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_number(visitor)
+        self.deserialize_f64(visitor)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_number(visitor)
+        match self.untag() {
+            Value::Number(n) => n.deserialize_f64(visitor),
+            other => Err(other.invalid_type(&visitor)),
+        }
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
@@ -286,9 +401,10 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::String(v) => visitor.visit_string(v),
-            _ => Err(self.invalid_type(&visitor)),
+            Value::Timestamp(v) => visitor.visit_string(v.to_string()),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -303,10 +419,11 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::String(v) => visitor.visit_string(v),
+            Value::Binary(v) => visitor.visit_byte_buf(v),
             Value::Sequence(v) => visit_sequence(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -316,6 +433,8 @@ impl<'de> Deserializer<'de> for Value {
     {
         match self {
             Value::Null => visitor.visit_none(),
+            Value::Anchored(anchored) => anchored.value.deserialize_option(visitor),
+            Value::Alias(name) => Err(unresolved_alias(&name)),
             _ => visitor.visit_some(self),
         }
     }
@@ -324,9 +443,9 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::Null => visitor.visit_unit(),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -352,9 +471,21 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        let set = matches!(&self, Value::Tagged(tagged) if tagged.tag == "!!set");
+        let pairs = matches!(
+            &self,
+            Value::Tagged(tagged) if tagged.tag == "!!pairs" || tagged.tag == "!!omap"
+        );
+        match self.untag() {
+            Value::Sequence(v) if pairs => visit_pairs(v, visitor),
             Value::Sequence(v) => visit_sequence(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            Value::Binary(v) => {
+                de::value::SeqDeserializer::new(v.into_iter()).deserialize_any(visitor)
+            }
+            Value::Mapping(v) if set => {
+                visit_sequence(v.into_iter().map(|(key, _)| key).collect(), visitor)
+            }
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -381,9 +512,9 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::Mapping(v) => visit_mapping(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
@@ -396,17 +527,17 @@ impl<'de> Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        match self {
+        match self.untag() {
             Value::Sequence(v) => visit_sequence(v, visitor),
             Value::Mapping(v) => visit_mapping(v, visitor),
-            _ => Err(self.invalid_type(&visitor)),
+            other => Err(other.invalid_type(&visitor)),
         }
     }
 
     fn deserialize_enum<V>(
         self,
-        _name: &str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Error>
     where
@@ -434,6 +565,11 @@ impl<'de> Deserializer<'de> for Value {
                 (variant, Some(value))
             }
             Value::String(variant) => (Value::String(variant), None),
+            Value::Tagged(tagged) => return visitor.visit_enum(*tagged),
+            Value::Anchored(anchored) => {
+                return anchored.value.deserialize_enum(name, variants, visitor);
+            }
+            Value::Alias(alias) => return Err(unresolved_alias(&alias)),
             other => {
                 return Err(Error::invalid_type(other.unexpected(), &"string or map"));
             }
@@ -540,14 +676,17 @@ impl<'de> VariantAccess<'de> for VariantDeserializer {
     }
 }
 
-struct SeqDeserializer {
+pub(crate) struct SeqDeserializer {
     iter: vec::IntoIter<Value>,
+    /// Whether the elements are those of a `!!pairs` or `!!omap` sequence.
+    pairs: bool,
 }
 
 impl SeqDeserializer {
-    fn new(vec: Vec<Value>) -> Self {
+    pub(crate) fn new(vec: Vec<Value>) -> Self {
         SeqDeserializer {
             iter: vec.into_iter(),
+            pairs: false,
         }
     }
 }
@@ -597,6 +736,7 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
+            Some(value) if self.pairs => seed.deserialize(PairDeserializer(value)).map(Some),
             Some(value) => seed.deserialize(value).map(Some),
             None => Ok(None),
         }
@@ -610,13 +750,66 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
     }
 }
 
-struct MapDeserializer {
+macro_rules! forward_to_value {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.0.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+/// Deserializes an element of a `!!pairs` or `!!omap` sequence, which also
+/// reads a mapping with a single entry as a pair.
+struct PairDeserializer(Value);
+
+impl<'de> Deserializer<'de> for PairDeserializer {
+    type Error = Error;
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Mapping(v) if len == 2 => {
+                let entries = v.len();
+                let mut iter = v.into_iter();
+                match (iter.next(), iter.next()) {
+                    (Some((key, value)), None) => visit_sequence(vec![key, value], visitor),
+                    _ => Err(Error::invalid_length(entries, &"map containing 1 entry")),
+                }
+            }
+            other => other.deserialize_tuple(len, visitor),
+        }
+    }
+
+    forward_to_value! {
+        deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16()
+        deserialize_i32() deserialize_i64() deserialize_i128() deserialize_u8()
+        deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+        deserialize_f32() deserialize_f64() deserialize_char() deserialize_str()
+        deserialize_string() deserialize_bytes() deserialize_byte_buf()
+        deserialize_option() deserialize_unit() deserialize_seq() deserialize_map()
+        deserialize_identifier() deserialize_ignored_any()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    }
+}
+
+pub(crate) struct MapDeserializer {
     iter: <Mapping as IntoIterator>::IntoIter,
     value: Option<Value>,
 }
 
 impl MapDeserializer {
-    fn new(map: Mapping) -> Self {
+    pub(crate) fn new(map: Mapping) -> Self {
         MapDeserializer {
             iter: map.into_iter(),
             value: None,
@@ -690,18 +883,36 @@ impl Value {
     where
         E: de::Error,
     {
-        de::Error::invalid_type(self.unexpected(), exp)
+        match self {
+            Value::Alias(name) => unresolved_alias(name),
+            _ => de::Error::invalid_type(self.unexpected(), exp),
+        }
     }
 
     #[cold]
-    fn unexpected(&self) -> Unexpected {
+    pub(crate) fn unexpected(&self) -> Unexpected {
         match self {
             Value::Null => Unexpected::Unit,
             Value::Bool(b) => Unexpected::Bool(*b),
             Value::Number(n) => number::unexpected(n),
             Value::String(s) => Unexpected::Str(s),
+            Value::Binary(b) => Unexpected::Bytes(b),
+            Value::Timestamp(_) => Unexpected::Other("timestamp"),
             Value::Sequence(_) => Unexpected::Seq,
             Value::Mapping(_) => Unexpected::Map,
+            Value::Tagged(_) => Unexpected::Enum,
+            Value::Anchored(anchored) => anchored.value.unexpected(),
+            Value::Alias(_) => Unexpected::Other("alias"),
         }
     }
 }
+
+fn unresolved_alias<E>(name: &str) -> E
+where
+    E: de::Error,
+{
+    E::custom(format_args!(
+        "cannot deserialize the alias `*{}`, expand aliases with Value::expand_aliases first",
+        name,
+    ))
+}
@@ -21,6 +21,11 @@ from_number! {
     f32 f64
 }
 
+#[cfg(feature = "arbitrary_precision")]
+from_number! {
+    i128 u128
+}
+
 impl From<bool> for Value {
     /// Convert boolean to `Value`
     ///
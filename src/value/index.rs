@@ -156,8 +156,13 @@ impl<'a> fmt::Display for Type<'a> {
             Value::Bool(_) => formatter.write_str("boolean"),
             Value::Number(_) => formatter.write_str("number"),
             Value::String(_) => formatter.write_str("string"),
+            Value::Binary(_) => formatter.write_str("binary data"),
+            Value::Timestamp(_) => formatter.write_str("timestamp"),
             Value::Sequence(_) => formatter.write_str("sequence"),
             Value::Mapping(_) => formatter.write_str("mapping"),
+            Value::Tagged(_) => formatter.write_str("tagged value"),
+            Value::Anchored(_) => formatter.write_str("anchored value"),
+            Value::Alias(_) => formatter.write_str("alias"),
         }
     }
 }
@@ -1,21 +1,41 @@
 //! The Value enum, a loosely typed way of representing any valid YAML value.
 
+pub(crate) mod anchored;
 mod de;
 mod from;
 mod index;
 mod partial_eq;
 mod ser;
+mod spanned;
+pub(crate) mod tagged;
 
-use crate::{Error, Mapping};
-use serde::de::{Deserialize, DeserializeOwned, IntoDeserializer};
+use crate::error;
+use crate::path::{PathBuf, Segment};
+use crate::{Error, Mapping, Timestamp};
+use serde::de::{Deserialize, DeserializeOwned, Error as _, IntoDeserializer};
 use serde::Serialize;
+use std::collections::HashMap;
+use std::mem;
 
+pub use self::anchored::AnchoredValue;
 pub use self::index::Index;
 pub use self::ser::Serializer;
+pub use self::spanned::{from_spanned_value, SpannedNode, SpannedValue};
+pub use self::tagged::{Tag, TaggedValue};
 pub use crate::number::Number;
 
+/// The name of the private newtype struct through which [`Value`],
+/// [`TaggedValue`] and [`SpannedValue`] ask the YAML deserializer to hand over
+/// tags, which other types do not see. Other deserializers visit the newtype
+/// struct's content as usual.
+pub(crate) const NAME: &str = "$__serde_yaml_private_Value";
+
 /// Represents any valid YAML value.
+///
+/// More kinds of value may be added in a minor release, so a match on a
+/// `Value` needs a wildcard arm.
 #[derive(Clone, PartialEq, PartialOrd, Hash, Debug)]
+#[non_exhaustive]
 pub enum Value {
     /// Represents a YAML null value.
     Null,
@@ -25,12 +45,29 @@ pub enum Value {
     Number(Number),
     /// Represents a YAML string.
     String(String),
+    /// Represents YAML's `!!binary` type, a base64-encoded string of bytes.
+    Binary(Vec<u8>),
+    /// Represents YAML's `!!timestamp` type. See [`Timestamp`] for which
+    /// scalars are timestamps.
+    Timestamp(Timestamp),
     /// Represents a YAML sequence in which the elements are
     /// `serde_yaml::Value`.
     Sequence(Sequence),
     /// Represents a YAML mapping in which the keys and values are both
     /// `serde_yaml::Value`.
     Mapping(Mapping),
+    /// A representation of YAML's `!Tag` syntax, used for enums and for
+    /// application-specific tags such as CloudFormation's `!Ref`.
+    Tagged(Box<TaggedValue>),
+    /// A value with an `&anchor`. Only produced when deserializing with
+    /// [`DeserializerOptions::preserve_anchors`](crate::DeserializerOptions::preserve_anchors).
+    Anchored(Box<AnchoredValue>),
+    /// An `*alias` of the anchored value of the same name earlier in the
+    /// document. Only produced when deserializing with
+    /// [`DeserializerOptions::preserve_anchors`](crate::DeserializerOptions::preserve_anchors);
+    /// see [`Value::expand_aliases`] for replacing aliases with the values
+    /// they refer to.
+    Alias(String),
 }
 
 /// The default value is `Value::Null`.
@@ -457,6 +494,58 @@ impl Value {
         }
     }
 
+    /// Returns true if the `Value` is binary data. Returns false otherwise.
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("!!binary aGVsbG8=").unwrap();
+    /// assert!(v.is_binary());
+    /// ```
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("aGVsbG8=").unwrap();
+    /// assert!(!v.is_binary());
+    /// ```
+    pub fn is_binary(&self) -> bool {
+        self.as_binary().is_some()
+    }
+
+    /// If the `Value` is binary data, returns the associated bytes. Returns
+    /// None otherwise.
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("!!binary aGVsbG8=").unwrap();
+    /// assert_eq!(v.as_binary(), Some(&b"hello"[..]));
+    /// ```
+    pub fn as_binary(&self) -> Option<&[u8]> {
+        match self {
+            Value::Binary(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// If the `Value` is a timestamp, returns it. Returns None otherwise.
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("!!timestamp 2001-12-14").unwrap();
+    /// assert_eq!(v.as_timestamp().unwrap().year(), 2001);
+    /// ```
+    ///
+    /// ```
+    /// # use serde_yaml::Value;
+    /// let v: Value = serde_yaml::from_str("2001-12-14").unwrap();
+    /// assert_eq!(v.as_timestamp(), None);
+    /// ```
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Value::Timestamp(timestamp) => Some(*timestamp),
+            _ => None,
+        }
+    }
+
     /// Returns true if the `Value` is a sequence. Returns false otherwise.
     ///
     /// ```
@@ -587,6 +676,262 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Performs merging of `<<` keys into the surrounding mapping.
+    ///
+    /// The value of a merge key may be a single mapping or a sequence of
+    /// mappings. Keys already present in the surrounding mapping take
+    /// precedence over merged ones, and earlier mappings in a sequence take
+    /// precedence over later ones. A merge source that is not a mapping is an
+    /// error, whose [`path`](Error::path) leads to it.
+    ///
+    /// Merge keys are resolved automatically when deserializing YAML text, so
+    /// this is only needed for a `Value` that was built some other way.
+    ///
+    /// ```
+    /// use serde_yaml::{Mapping, Value};
+    ///
+    /// let mut defaults = Mapping::new();
+    /// defaults.insert("retries".into(), 3.into());
+    /// defaults.insert("timeout".into(), 30.into());
+    ///
+    /// let mut service = Mapping::new();
+    /// service.insert("<<".into(), Value::Mapping(defaults));
+    /// service.insert("timeout".into(), 60.into());
+    ///
+    /// let mut value = Value::Mapping(service);
+    /// value.apply_merge().unwrap();
+    ///
+    /// assert_eq!(value["retries"], 3);
+    /// assert_eq!(value["timeout"], 60);
+    /// assert_eq!(value.get("<<"), None);
+    /// ```
+    pub fn apply_merge(&mut self) -> Result<(), Error> {
+        apply_merge(self, &PathBuf::new())
+    }
+
+    /// Replaces every [`Value::Alias`] with a copy of the anchored value it
+    /// refers to. As when reading YAML, an alias refers to the closest
+    /// preceding anchor of the same name, in the order the nodes would be
+    /// written. The anchored values keep their anchors.
+    ///
+    /// The copies may hold at most 100 times as many nodes as this value
+    /// does, the same limit that applies when reading YAML. Use
+    /// [`Value::expand_aliases_with_limit`] to set a different limit.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_yaml::{Deserializer, DeserializerOptions, Value};
+    ///
+    /// let yaml = "base: &defaults {retries: 3}\nservice: *defaults\n";
+    /// let options = DeserializerOptions::new().preserve_anchors(true);
+    /// let mut value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+    ///
+    /// value.expand_aliases().unwrap();
+    /// assert_eq!(value["service"]["retries"], 3);
+    /// ```
+    pub fn expand_aliases(&mut self) -> Result<(), Error> {
+        let max_nodes = node_count(self).saturating_mul(100);
+        self.expand_aliases_with_limit(max_nodes)
+    }
+
+    /// Like [`Value::expand_aliases`], but fails once the copies made for
+    /// aliases hold more than `max_nodes` nodes in total. Every scalar,
+    /// sequence, mapping, tag and anchor counts as one node.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_yaml::{Deserializer, DeserializerOptions, Value};
+    ///
+    /// let yaml = "a: &a [x, y]\nb: [*a, *a]\n";
+    /// let options = DeserializerOptions::new().preserve_anchors(true);
+    /// let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+    ///
+    /// assert!(value.clone().expand_aliases_with_limit(6).is_ok());
+    /// assert!(value.clone().expand_aliases_with_limit(5).is_err());
+    /// ```
+    pub fn expand_aliases_with_limit(&mut self, max_nodes: usize) -> Result<(), Error> {
+        let mut remaining_nodes = max_nodes;
+        expand_aliases(self, &mut HashMap::new(), &mut remaining_nodes)
+    }
+}
+
+fn expand_aliases(
+    value: &mut Value,
+    anchors: &mut HashMap<String, (Value, usize)>,
+    remaining_nodes: &mut usize,
+) -> Result<(), Error> {
+    match value {
+        Value::Alias(name) => match anchors.get(name) {
+            Some((anchored, nodes)) => {
+                *remaining_nodes = match remaining_nodes.checked_sub(*nodes) {
+                    Some(remaining) => remaining,
+                    None => {
+                        return Err(Error::custom(format_args!(
+                            "alias expansion limit exceeded at alias `{}`",
+                            name,
+                        )));
+                    }
+                };
+                *value = anchored.clone();
+            }
+            None => return Err(Error::custom(format_args!("unknown anchor `{}`", name))),
+        },
+        Value::Anchored(anchored) => {
+            expand_aliases(&mut anchored.value, anchors, remaining_nodes)?;
+            let nodes = node_count(&anchored.value);
+            anchors.insert(anchored.anchor.clone(), (anchored.value.clone(), nodes));
+        }
+        Value::Tagged(tagged) => expand_aliases(&mut tagged.value, anchors, remaining_nodes)?,
+        Value::Sequence(sequence) => {
+            for element in sequence {
+                expand_aliases(element, anchors, remaining_nodes)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            let mut expanded = Mapping::with_capacity(mapping.len());
+            for (mut key, mut value) in mem::take(mapping) {
+                expand_aliases(&mut key, anchors, remaining_nodes)?;
+                expand_aliases(&mut value, anchors, remaining_nodes)?;
+                expanded.insert(key, value);
+            }
+            *mapping = expanded;
+        }
+        Value::Null
+        | Value::Bool(_)
+        | Value::Number(_)
+        | Value::String(_)
+        | Value::Binary(_)
+        | Value::Timestamp(_) => {}
+    }
+    Ok(())
+}
+
+fn node_count(value: &Value) -> usize {
+    let mut count = 0;
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        count += 1;
+        match value {
+            Value::Sequence(sequence) => stack.extend(sequence),
+            Value::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    stack.push(key);
+                    stack.push(value);
+                }
+            }
+            Value::Tagged(tagged) => stack.push(&tagged.value),
+            Value::Anchored(anchored) => stack.push(&anchored.value),
+            _ => {}
+        }
+    }
+    count
+}
+
+/// A step from a sequence or mapping to one of its elements or values.
+enum Step<'a> {
+    Index(usize),
+    Key(&'a Value),
+}
+
+impl Step<'_> {
+    fn segment(&self) -> Segment {
+        match self {
+            Step::Index(index) => Segment::Index(*index),
+            Step::Key(Value::String(key)) => Segment::Key(key.clone()),
+            Step::Key(Value::Number(key)) => Segment::Key(key.to_string()),
+            Step::Key(Value::Bool(key)) => Segment::Key(key.to_string()),
+            Step::Key(_) => Segment::Unknown,
+        }
+    }
+}
+
+// Merges the `<<` keys within `value`, which is at `root`. Errors about a bad
+// merge source give the path to it.
+fn apply_merge(value: &mut Value, root: &PathBuf) -> Result<(), Error> {
+    let merge_key = Value::String("<<".to_owned());
+    // Each node comes with the number of steps to its parent and the step
+    // from there, so that `steps` leads to the node being looked at.
+    let mut stack = vec![(value, 0, None)];
+    let mut steps: Vec<Step> = Vec::new();
+    while let Some((node, parent, step)) = stack.pop() {
+        steps.truncate(parent);
+        steps.extend(step);
+        match node {
+            Value::Mapping(mapping) => {
+                if mapping.contains_key(&merge_key) {
+                    let mut path = root.clone();
+                    for step in &steps {
+                        path.push(step.segment());
+                    }
+                    path.push(Segment::Key("<<".to_owned()));
+                    let mut merged = Mapping::with_capacity(mapping.len());
+                    let mut sources = Value::Null;
+                    for (key, value) in mem::take(mapping) {
+                        if key == merge_key {
+                            sources = value;
+                        } else {
+                            merged.insert(key, value);
+                        }
+                    }
+                    match sources {
+                        Value::Mapping(source) => merge_from(&mut merged, source, &path)?,
+                        Value::Sequence(sequence) => {
+                            for (index, source) in sequence.into_iter().enumerate() {
+                                let mut path = path.clone();
+                                path.push(Segment::Index(index));
+                                match source {
+                                    Value::Mapping(source) => {
+                                        merge_from(&mut merged, source, &path)?;
+                                    }
+                                    unexpected => {
+                                        let err = Error::invalid_type(
+                                            unexpected.unexpected(),
+                                            &"a mapping for merging",
+                                        );
+                                        return Err(error::at_path(err, path));
+                                    }
+                                }
+                            }
+                        }
+                        unexpected => {
+                            let err = Error::invalid_type(
+                                unexpected.unexpected(),
+                                &"a mapping or list of mappings for merging",
+                            );
+                            return Err(error::at_path(err, path));
+                        }
+                    }
+                    *mapping = merged;
+                }
+                let depth = steps.len();
+                for (key, value) in mapping.iter_mut() {
+                    stack.push((value, depth, Some(Step::Key(key))));
+                }
+            }
+            Value::Sequence(sequence) => {
+                let depth = steps.len();
+                for (index, element) in sequence.iter_mut().enumerate() {
+                    stack.push((element, depth, Some(Step::Index(index))));
+                }
+            }
+            Value::Tagged(tagged) => stack.push((&mut tagged.value, steps.len(), None)),
+            Value::Anchored(anchored) => stack.push((&mut anchored.value, steps.len(), None)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn merge_from(mapping: &mut Mapping, source: Mapping, path: &PathBuf) -> Result<(), Error> {
+    let mut source = Value::Mapping(source);
+    apply_merge(&mut source, path)?;
+    if let Value::Mapping(source) = source {
+        for (key, value) in source {
+            mapping.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
 }
 
 impl Eq for Value {}
@@ -1,6 +1,12 @@
 use crate::error::Error;
-use crate::value::{to_value, Mapping, Number, Sequence, Value};
+use crate::number;
+use crate::timestamp;
+use crate::value::anchored::{self, AnchoredValue};
+use crate::value::tagged::{self, MaybeTag};
+use crate::value::{to_value, Mapping, Number, Sequence, Tag, TaggedValue, Value};
 use serde::ser::{self, Serialize};
+use std::fmt::Display;
+use std::mem;
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -14,6 +20,8 @@ impl Serialize for Value {
             Value::Bool(b) => serializer.serialize_bool(*b),
             Value::Number(n) => n.serialize(serializer),
             Value::String(s) => serializer.serialize_str(s),
+            Value::Binary(bytes) => serializer.serialize_bytes(bytes),
+            Value::Timestamp(timestamp) => timestamp.serialize(serializer),
             Value::Sequence(seq) => seq.serialize(serializer),
             Value::Mapping(mapping) => {
                 use serde::ser::SerializeMap;
@@ -23,6 +31,9 @@ impl Serialize for Value {
                 }
                 map.end()
             }
+            Value::Tagged(tagged) => tagged.serialize(serializer),
+            Value::Anchored(anchored) => anchored.serialize(serializer),
+            Value::Alias(name) => anchored::serialize_alias(name, serializer),
         }
     }
 }
@@ -78,9 +89,15 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_i64(self, v: i64) -> Result<Value> {
-        Ok(Value::Number(Number::from(v)))
+        Ok(Value::Number(number::take_lent(Number::from(v))))
     }
 
+    #[cfg(feature = "arbitrary_precision")]
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Ok(Value::Number(number::take_lent(Number::from(v))))
+    }
+
+    #[cfg(not(feature = "arbitrary_precision"))]
     fn serialize_i128(self, v: i128) -> Result<Value> {
         if let Ok(v) = u64::try_from(v) {
             self.serialize_u64(v)
@@ -104,9 +121,15 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Value> {
-        Ok(Value::Number(Number::from(v)))
+        Ok(Value::Number(number::take_lent(Number::from(v))))
     }
 
+    #[cfg(feature = "arbitrary_precision")]
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Ok(Value::Number(number::take_lent(Number::from(v))))
+    }
+
+    #[cfg(not(feature = "arbitrary_precision"))]
     fn serialize_u128(self, v: u128) -> Result<Value> {
         if let Ok(v) = u64::try_from(v) {
             self.serialize_u64(v)
@@ -120,7 +143,7 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_f64(self, v: f64) -> Result<Value> {
-        Ok(Value::Number(Number::from(v)))
+        Ok(Value::Number(number::take_lent(Number::from(v))))
     }
 
     fn serialize_char(self, value: char) -> Result<Value> {
@@ -132,11 +155,7 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<Value> {
-        let vec = value
-            .iter()
-            .map(|&b| Value::Number(Number::from(b)))
-            .collect();
-        Ok(Value::Sequence(vec))
+        Ok(Value::Binary(value.to_owned()))
     }
 
     fn serialize_unit(self) -> Result<Value> {
@@ -156,11 +175,23 @@ impl ser::Serializer for Serializer {
         Ok(Value::String(variant.to_owned()))
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Value>
     where
         T: ?Sized + ser::Serialize,
     {
-        value.serialize(self)
+        match name {
+            anchored::ALIAS => anchored::name_of('*', value).map(Value::Alias),
+            timestamp::NAME => timestamp::text_of(value)?.parse().map(Value::Timestamp),
+            #[cfg(feature = "arbitrary_precision")]
+            number::NAME => {
+                let digits = number::digits_of(value)?;
+                match Number::from_decimal(&digits) {
+                    Some(number) => Ok(Value::Number(number::take_lent(number))),
+                    None => Err(ser::Error::custom("expected the digits of a number")),
+                }
+            }
+            _ => value.serialize(self),
+        }
     }
 
     fn serialize_newtype_variant<T>(
@@ -216,11 +247,15 @@ impl ser::Serializer for Serializer {
         })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap> {
-        Ok(SerializeMap {
-            mapping: Mapping::new(),
-            next_key: None,
-        })
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        if len == Some(1) {
+            Ok(SerializeMap::CheckForTag)
+        } else {
+            Ok(SerializeMap::Untagged {
+                mapping: Mapping::new(),
+                next_key: None,
+            })
+        }
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<SerializeStruct> {
@@ -252,9 +287,14 @@ pub struct SerializeTupleVariant {
     sequence: Sequence,
 }
 
-pub struct SerializeMap {
-    mapping: Mapping,
-    next_key: Option<Value>,
+pub enum SerializeMap {
+    CheckForTag,
+    Tagged(TaggedValue),
+    Anchored(AnchoredValue),
+    Untagged {
+        mapping: Mapping,
+        next_key: Option<Value>,
+    },
 }
 
 pub struct SerializeStruct {
@@ -343,7 +383,38 @@ impl ser::SerializeMap for SerializeMap {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.next_key = Some(to_value(key)?);
+        let key = Some(to_value(key)?);
+        match self {
+            SerializeMap::CheckForTag => {
+                *self = SerializeMap::Untagged {
+                    mapping: Mapping::new(),
+                    next_key: key,
+                };
+            }
+            SerializeMap::Tagged(tagged) => {
+                let mut mapping = Mapping::new();
+                mapping.insert(
+                    Value::String(tagged.tag.to_string()),
+                    mem::take(&mut tagged.value),
+                );
+                *self = SerializeMap::Untagged {
+                    mapping,
+                    next_key: key,
+                };
+            }
+            SerializeMap::Anchored(anchored) => {
+                let mut mapping = Mapping::new();
+                mapping.insert(
+                    Value::String(format!("&{}", anchored.anchor)),
+                    mem::take(&mut anchored.value),
+                );
+                *self = SerializeMap::Untagged {
+                    mapping,
+                    next_key: key,
+                };
+            }
+            SerializeMap::Untagged { next_key, .. } => *next_key = key,
+        }
         Ok(())
     }
 
@@ -351,8 +422,14 @@ impl ser::SerializeMap for SerializeMap {
     where
         T: ?Sized + ser::Serialize,
     {
-        match self.next_key.take() {
-            Some(key) => self.mapping.insert(key, to_value(value)?),
+        let (mapping, key) = match self {
+            SerializeMap::CheckForTag | SerializeMap::Tagged(_) | SerializeMap::Anchored(_) => {
+                unreachable!()
+            }
+            SerializeMap::Untagged { mapping, next_key } => (mapping, next_key),
+        };
+        match key.take() {
+            Some(key) => mapping.insert(key, to_value(value)?),
             None => panic!("serialize_value called before serialize_key"),
         };
         Ok(())
@@ -363,15 +440,262 @@ impl ser::SerializeMap for SerializeMap {
         K: ?Sized + ser::Serialize,
         V: ?Sized + ser::Serialize,
     {
-        self.mapping.insert(to_value(key)?, to_value(value)?);
+        match self {
+            SerializeMap::CheckForTag => {
+                *self = match key.serialize(CheckForTag) {
+                    Ok(key) => match key.strip_prefix('&') {
+                        Some(anchor) => SerializeMap::Anchored(AnchoredValue {
+                            anchor: anchor.to_owned(),
+                            value: to_value(value)?,
+                        }),
+                        None => SerializeMap::Tagged(TaggedValue {
+                            tag: Tag::new(key),
+                            value: to_value(value)?,
+                        }),
+                    },
+                    Err(_) => {
+                        let mut mapping = Mapping::new();
+                        mapping.insert(to_value(key)?, to_value(value)?);
+                        SerializeMap::Untagged {
+                            mapping,
+                            next_key: None,
+                        }
+                    }
+                };
+            }
+            SerializeMap::Tagged(tagged) => {
+                let mut mapping = Mapping::new();
+                mapping.insert(
+                    Value::String(tagged.tag.to_string()),
+                    mem::take(&mut tagged.value),
+                );
+                mapping.insert(to_value(key)?, to_value(value)?);
+                *self = SerializeMap::Untagged {
+                    mapping,
+                    next_key: None,
+                };
+            }
+            SerializeMap::Anchored(anchored) => {
+                let mut mapping = Mapping::new();
+                mapping.insert(
+                    Value::String(format!("&{}", anchored.anchor)),
+                    mem::take(&mut anchored.value),
+                );
+                mapping.insert(to_value(key)?, to_value(value)?);
+                *self = SerializeMap::Untagged {
+                    mapping,
+                    next_key: None,
+                };
+            }
+            SerializeMap::Untagged { mapping, .. } => {
+                mapping.insert(to_value(key)?, to_value(value)?);
+            }
+        }
         Ok(())
     }
 
     fn end(self) -> Result<Value> {
-        Ok(Value::Mapping(self.mapping))
+        Ok(match self {
+            SerializeMap::CheckForTag => Value::Mapping(Mapping::new()),
+            SerializeMap::Tagged(tagged) => Value::Tagged(Box::new(tagged)),
+            SerializeMap::Anchored(anchored) => Value::Anchored(Box::new(anchored)),
+            SerializeMap::Untagged { mapping, .. } => Value::Mapping(mapping),
+        })
     }
 }
 
+/// Serializer for the key of a single-entry map, which succeeds only if the
+/// key is a tag written by `collect_str`, or an anchor, which is returned with
+/// its leading `&`. Anything else is reported as an error and the key is then
+/// serialized as an ordinary `Value`.
+struct CheckForTag;
+
+impl ser::Serializer for CheckForTag {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_char(self, _value: char) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_str(self, _value: &str) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<String>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        if name == anchored::ANCHOR {
+            return anchored::name_of('&', value).map(|anchor| format!("&{}", anchor));
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Err(not_a_tag())
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_some<V>(self, _value: &V) -> Result<String>
+    where
+        V: ?Sized + ser::Serialize,
+    {
+        Err(not_a_tag())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _enum: &'static str,
+        _idx: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(not_a_tag())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _enum: &'static str,
+        _idx: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_a_tag())
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<String>
+    where
+        T: ?Sized + Display,
+    {
+        match tagged::check_for_tag(value) {
+            MaybeTag::Tag(tag) => Ok(tag),
+            MaybeTag::NotTag(_) => Err(not_a_tag()),
+        }
+    }
+}
+
+fn not_a_tag() -> Error {
+    ser::Error::custom("expected a YAML tag")
+}
+
 impl ser::SerializeStruct for SerializeStruct {
     type Ok = Value;
     type Error = Error;
@@ -0,0 +1,789 @@
+use crate::error::{self, Error};
+use crate::number;
+use crate::path::PathBuf;
+use crate::spanned::{self, Spanned};
+use crate::timestamp;
+use crate::value::tagged::{self, SerializeTag, TagStringVisitor};
+use crate::value::{Tag, TaggedValue};
+use crate::{Location, Mapping, Number, Timestamp, Value};
+use serde::de::value::{BorrowedStrDeserializer, StrDeserializer};
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, Error as _, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::vec;
+
+/// A YAML value that remembers where every node of it was found in the source.
+///
+/// Deserializing a document into `SpannedValue` instead of [`Value`] keeps the
+/// span and path of each scalar, sequence, mapping and mapping key, so that
+/// problems found while processing the document generically can still be
+/// reported against the source text. A `SpannedValue` can be converted into a
+/// [`Value`], or deserialized into a typed `T`, in which case errors point at
+/// the node they came from.
+///
+/// ```
+/// use serde_yaml::value::{SpannedNode, SpannedValue};
+///
+/// let yaml = "name: serde\nports: [80, 443]\n";
+/// let doc: SpannedValue = serde_yaml::from_str(yaml).unwrap();
+///
+/// let ports = doc.get("ports").unwrap();
+/// assert_eq!(&yaml[ports.span()], "[80, 443]");
+/// assert_eq!(ports.path, "ports");
+/// if let SpannedNode::Sequence(ports) = &ports.value {
+///     assert_eq!(ports[1].start_location.unwrap().column(), 13);
+/// }
+///
+/// let result: Result<Vec<u8>, _> = serde_yaml::from_spanned_value(ports.clone());
+/// let err = result.unwrap_err();
+/// assert_eq!(err.location().unwrap().index(), yaml.find("443").unwrap());
+/// ```
+pub type SpannedValue = Spanned<SpannedNode>;
+
+/// The contents of a [`SpannedValue`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpannedNode {
+    /// Represents a YAML null value.
+    Null,
+    /// Represents a YAML boolean.
+    Bool(bool),
+    /// Represents a YAML numerical value, whether integer or floating point.
+    Number(Number),
+    /// Represents a YAML string.
+    String(String),
+    /// Represents YAML's `!!binary` type, a base64-encoded string of bytes.
+    Binary(Vec<u8>),
+    /// Represents YAML's `!!timestamp` type.
+    Timestamp(Timestamp),
+    /// Represents a YAML sequence in which the elements are spanned.
+    Sequence(Vec<SpannedValue>),
+    /// Represents a YAML mapping in which the keys and values are spanned.
+    /// Entries are kept in source order, including any duplicate keys.
+    Mapping(Vec<(SpannedValue, SpannedValue)>),
+    /// A representation of YAML's `!Tag` syntax. The tagged value shares the
+    /// span of the node that carries the tag.
+    Tagged(Tag, Box<SpannedValue>),
+}
+
+/// Interpret a `serde_yaml::value::SpannedValue` as an instance of type `T`.
+///
+/// Errors raised while deserializing are reported at the location of the node
+/// that caused them.
+pub fn from_spanned_value<T>(value: SpannedValue) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    Deserialize::deserialize(value)
+}
+
+impl Spanned<SpannedNode> {
+    /// Look up the value of a string key in a mapping.
+    ///
+    /// Returns `None` if this is not a mapping or the key is not present. If
+    /// the key occurs more than once, the last occurrence wins, as it does when
+    /// deserializing into a [`Value`].
+    pub fn get(&self, key: &str) -> Option<&SpannedValue> {
+        match &self.value {
+            SpannedNode::Mapping(entries) => entries
+                .iter()
+                .rev()
+                .find(|(k, _)| matches!(&k.value, SpannedNode::String(k) if k == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Discard all span information, producing a plain [`Value`].
+    pub fn into_value(self) -> Value {
+        match self.value {
+            SpannedNode::Null => Value::Null,
+            SpannedNode::Bool(b) => Value::Bool(b),
+            SpannedNode::Number(n) => Value::Number(n),
+            SpannedNode::String(s) => Value::String(s),
+            SpannedNode::Binary(b) => Value::Binary(b),
+            SpannedNode::Timestamp(t) => Value::Timestamp(t),
+            SpannedNode::Sequence(sequence) => {
+                Value::Sequence(sequence.into_iter().map(Self::into_value).collect())
+            }
+            SpannedNode::Mapping(entries) => {
+                let mut mapping = Mapping::new();
+                for (k, v) in entries {
+                    mapping.insert(k.into_value(), v.into_value());
+                }
+                Value::Mapping(mapping)
+            }
+            SpannedNode::Tagged(tag, value) => Value::Tagged(Box::new(TaggedValue {
+                tag,
+                value: value.into_value(),
+            })),
+        }
+    }
+
+    fn untag(self) -> Self {
+        let mut cur = self;
+        while let SpannedNode::Tagged(_, value) = cur.value {
+            cur = *value;
+        }
+        cur
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match &self.value {
+            SpannedNode::Null => Unexpected::Unit,
+            SpannedNode::Bool(b) => Unexpected::Bool(*b),
+            SpannedNode::Number(n) => crate::number::unexpected(n),
+            SpannedNode::String(s) => Unexpected::Str(s),
+            SpannedNode::Binary(b) => Unexpected::Bytes(b),
+            SpannedNode::Timestamp(_) => Unexpected::Other("timestamp"),
+            SpannedNode::Sequence(_) => Unexpected::Seq,
+            SpannedNode::Mapping(_) => Unexpected::Map,
+            SpannedNode::Tagged(..) => Unexpected::Enum,
+        }
+    }
+}
+
+impl From<SpannedValue> for Value {
+    fn from(value: SpannedValue) -> Self {
+        value.into_value()
+    }
+}
+
+impl Serialize for SpannedNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SpannedNode::Null => serializer.serialize_unit(),
+            SpannedNode::Bool(b) => serializer.serialize_bool(*b),
+            SpannedNode::Number(n) => n.serialize(serializer),
+            SpannedNode::String(s) => serializer.serialize_str(s),
+            SpannedNode::Binary(b) => serializer.serialize_bytes(b),
+            SpannedNode::Timestamp(t) => t.serialize(serializer),
+            SpannedNode::Sequence(sequence) => serializer.collect_seq(sequence),
+            SpannedNode::Mapping(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            SpannedNode::Tagged(tag, value) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(&SerializeTag(tag), value)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SpannedNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SpannedNodeVisitor;
+
+        impl<'de> Visitor<'de> for SpannedNodeVisitor {
+            type Value = SpannedNode;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any YAML value")
+            }
+
+            fn visit_bool<E>(self, b: bool) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Bool(b))
+            }
+
+            fn visit_i64<E>(self, i: i64) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Number(number::take_lent(i.into())))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_i128<E>(self, i: i128) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Number(number::take_lent(i.into())))
+            }
+
+            fn visit_u64<E>(self, u: u64) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Number(number::take_lent(u.into())))
+            }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_u128<E>(self, u: u128) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Number(number::take_lent(u.into())))
+            }
+
+            fn visit_f64<E>(self, f: f64) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Number(number::take_lent(f.into())))
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::String(s.to_owned()))
+            }
+
+            fn visit_string<E>(self, s: String) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::String(s))
+            }
+
+            fn visit_bytes<E>(self, b: &[u8]) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Binary(b.to_owned()))
+            }
+
+            fn visit_byte_buf<E>(self, b: Vec<u8>) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Binary(b))
+            }
+
+            fn visit_unit<E>(self) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Null)
+            }
+
+            fn visit_none<E>(self) -> Result<SpannedNode, E> {
+                Ok(SpannedNode::Null)
+            }
+
+            fn visit_some<D>(self, deserializer: D) -> Result<SpannedNode, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                Deserialize::deserialize(deserializer)
+            }
+
+            fn visit_seq<V>(self, mut visitor: V) -> Result<SpannedNode, V::Error>
+            where
+                V: SeqAccess<'de>,
+            {
+                let mut vec = Vec::new();
+                while let Some(element) = visitor.next_element()? {
+                    vec.push(element);
+                }
+                Ok(SpannedNode::Sequence(vec))
+            }
+
+            fn visit_map<V>(self, mut visitor: V) -> Result<SpannedNode, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut entries = Vec::new();
+                // The deserializer hands over timestamps and arbitrary-precision
+                // numbers as maps with a private key.
+                match visitor.next_key_seed(FirstKeySeed)? {
+                    None => {}
+                    Some(FirstKey::Timestamp) => {
+                        let text: String = visitor.next_value()?;
+                        return text
+                            .parse()
+                            .map(SpannedNode::Timestamp)
+                            .map_err(de::Error::custom);
+                    }
+                    #[cfg(feature = "arbitrary_precision")]
+                    Some(FirstKey::Number) => {
+                        let digits: String = visitor.next_value()?;
+                        return match Number::from_decimal(&digits) {
+                            Some(number) => Ok(SpannedNode::Number(number::take_lent(number))),
+                            None => Err(de::Error::custom("expected the digits of a number")),
+                        };
+                    }
+                    Some(FirstKey::Key(key)) => entries.push((key, visitor.next_value()?)),
+                }
+                while let Some(entry) = visitor.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(SpannedNode::Mapping(entries))
+            }
+
+            fn visit_enum<A>(self, data: A) -> Result<SpannedNode, A::Error>
+            where
+                A: EnumAccess<'de>,
+            {
+                let (tag, contents) = data.variant_seed(TagStringVisitor)?;
+                let value = contents.newtype_variant()?;
+                Ok(SpannedNode::Tagged(tag, Box::new(value)))
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<SpannedNode, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(super::NAME, SpannedNodeVisitor)
+    }
+}
+
+/// The first key of a mapping, which is either an ordinary key or the private
+/// key of a timestamp or an arbitrary-precision number.
+enum FirstKey {
+    Timestamp,
+    #[cfg(feature = "arbitrary_precision")]
+    Number,
+    Key(SpannedValue),
+}
+
+struct FirstKeySeed;
+
+impl<'de> DeserializeSeed<'de> for FirstKeySeed {
+    type Value = FirstKey;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<FirstKey, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(spanned::NAME, spanned::FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for FirstKeySeed {
+    type Value = FirstKey;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any YAML value")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<FirstKey, E>
+    where
+        E: de::Error,
+    {
+        match s {
+            timestamp::NAME => Ok(FirstKey::Timestamp),
+            #[cfg(feature = "arbitrary_precision")]
+            number::NAME => Ok(FirstKey::Number),
+            _ => Err(E::invalid_type(Unexpected::Str(s), &self)),
+        }
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<FirstKey, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        SpannedValue::deserialize(de::value::MapAccessDeserializer::new(map)).map(FirstKey::Key)
+    }
+}
+
+// Errors that do not yet have a position are given the position of the node
+// being deserialized. Since children are deserialized first, this attributes
+// each error to the innermost node responsible for it.
+macro_rules! located {
+    ($value:expr, |$node:ident| $body:expr) => {{
+        let start = $value.start_location;
+        let end = $value.end_location;
+        let path = $value.path.clone();
+        let $node = $value;
+        $body.map_err(|err| match (start, end) {
+            (Some(start), Some(end)) => error::fix_location(err, start, end, &path),
+            _ => err,
+        })
+    }};
+}
+
+macro_rules! deserialize_untagged {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.untag().deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for SpannedValue {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        located!(self, |node| match node.value {
+            SpannedNode::Null => visitor.visit_unit(),
+            SpannedNode::Bool(v) => visitor.visit_bool(v),
+            SpannedNode::Number(n) => n.deserialize_any(visitor),
+            SpannedNode::String(v) => visitor.visit_string(v),
+            SpannedNode::Binary(v) => visitor.visit_byte_buf(v),
+            SpannedNode::Timestamp(v) => timestamp::visit(visitor, &v),
+            SpannedNode::Sequence(v) => visit_sequence(v, visitor),
+            SpannedNode::Mapping(v) => visit_mapping(v, visitor),
+            SpannedNode::Tagged(tag, value) => visitor.visit_enum(EnumDeserializer {
+                variant: VariantName::Tag(tag),
+                value: Some(*value),
+            }),
+        })
+    }
+
+    deserialize_untagged! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16
+        deserialize_u32 deserialize_u64 deserialize_u128 deserialize_char
+        deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_seq
+        deserialize_map deserialize_identifier
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let node = self.untag();
+        match &node.value {
+            SpannedNode::Number(n) => {
+                let n = n.clone();
+                located!(node, |_node| n.deserialize_f64(visitor))
+            }
+            _ => node.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        located!(self, |node| match node.value {
+            SpannedNode::Null => visitor.visit_none(),
+            _ => visitor.visit_some(node),
+        })
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        located!(self, |node| visitor.visit_newtype_struct(node))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == spanned::NAME && fields == spanned::FIELDS {
+            return visitor.visit_map(SpannedFields {
+                start: self
+                    .start_location
+                    .unwrap_or_else(|| Location::new(self.start, 0, 0)),
+                end: self
+                    .end_location
+                    .unwrap_or_else(|| Location::new(self.start + self.len, 0, 0)),
+                path: Some(self.path.clone()),
+                value: Some(self),
+                next: 0,
+            });
+        }
+        self.untag().deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        located!(self, |node| match node.value {
+            SpannedNode::Mapping(entries) => {
+                let mut iter = entries.into_iter();
+                match (iter.next(), iter.next()) {
+                    (Some((variant, value)), None) => visitor.visit_enum(EnumDeserializer {
+                        variant: VariantName::Value(variant),
+                        value: Some(value),
+                    }),
+                    _ => Err(Error::invalid_value(
+                        Unexpected::Map,
+                        &"map with a single key",
+                    )),
+                }
+            }
+            SpannedNode::String(_) => visitor.visit_enum(EnumDeserializer {
+                variant: VariantName::Value(node),
+                value: None,
+            }),
+            SpannedNode::Tagged(tag, value) => visitor.visit_enum(EnumDeserializer {
+                variant: VariantName::Tag(tag),
+                value: Some(*value),
+            }),
+            _ => Err(Error::invalid_type(node.unexpected(), &"string or map")),
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
+    }
+}
+
+fn visit_sequence<'de, V>(sequence: Vec<SpannedValue>, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = sequence.len();
+    let mut deserializer = SeqDeserializer {
+        iter: sequence.into_iter(),
+    };
+    let seq = visitor.visit_seq(&mut deserializer)?;
+    if deserializer.iter.len() == 0 {
+        Ok(seq)
+    } else {
+        Err(Error::invalid_length(len, &"fewer elements in sequence"))
+    }
+}
+
+fn visit_mapping<'de, V>(
+    entries: Vec<(SpannedValue, SpannedValue)>,
+    visitor: V,
+) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = entries.len();
+    let mut deserializer = MapDeserializer {
+        iter: entries.into_iter(),
+        value: None,
+    };
+    let map = visitor.visit_map(&mut deserializer)?;
+    if deserializer.iter.len() == 0 {
+        Ok(map)
+    } else {
+        Err(Error::invalid_length(len, &"fewer elements in map"))
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<SpannedValue>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: vec::IntoIter<(SpannedValue, SpannedValue)>,
+    value: Option<SpannedValue>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => panic!("visit_value called before visit_key"),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+enum VariantName {
+    Tag(Tag),
+    Value(SpannedValue),
+}
+
+struct EnumDeserializer {
+    variant: VariantName,
+    value: Option<SpannedValue>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer), Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = match self.variant {
+            VariantName::Tag(tag) => {
+                seed.deserialize(StrDeserializer::<Error>::new(tagged::nobang(&tag.string)))?
+            }
+            VariantName::Value(variant) => seed.deserialize(variant)?,
+        };
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer {
+    value: Option<SpannedValue>,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.value {
+            Some(value) => Deserialize::deserialize(value),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(value) => value.deserialize_seq(visitor),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(value) => value.deserialize_map(visitor),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+/// Presents a `SpannedValue` to the visitor of a `Spanned<T>`.
+struct SpannedFields {
+    start: Location,
+    end: Location,
+    path: Option<PathBuf>,
+    value: Option<SpannedValue>,
+    next: usize,
+}
+
+const SPANNED_FIELD_ORDER: [&str; 4] =
+    [spanned::START, spanned::VALUE, spanned::END, spanned::PATH];
+
+impl<'de> MapAccess<'de> for SpannedFields {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match SPANNED_FIELD_ORDER.get(self.next) {
+            Some(key) => seed
+                .deserialize(BorrowedStrDeserializer::new(key))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let key = SPANNED_FIELD_ORDER[self.next];
+        self.next += 1;
+        if key == spanned::START {
+            seed.deserialize(spanned::location_deserializer(self.start))
+        } else if key == spanned::END {
+            seed.deserialize(spanned::location_deserializer(self.end))
+        } else if key == spanned::PATH {
+            let path = self.path.take().unwrap();
+            seed.deserialize(spanned::path_deserializer(&path))
+        } else {
+            seed.deserialize(self.value.take().unwrap())
+        }
+    }
+}
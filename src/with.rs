@@ -0,0 +1,180 @@
+//! Functions for use with `#[serde(with = "...")]` that write collections as
+//! YAML's `!!set`, `!!pairs` and `!!omap` types.
+//!
+//! Deserializing needs no help: sets such as `HashSet<T>` read the keys of a
+//! `!!set`, and `Vec<(K, V)>` reads `!!pairs` and `!!omap`, whose pairs are
+//! mappings with a single entry. The `deserialize` functions in these modules
+//! are there so that the whole module can be named in `#[serde(with)]`.
+//!
+//! ```
+//! use serde_derive::{Deserialize, Serialize};
+//! use std::collections::BTreeSet;
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Config {
+//!     #[serde(with = "serde_yaml::with::set")]
+//!     hosts: BTreeSet<String>,
+//!     #[serde(with = "serde_yaml::with::omap")]
+//!     steps: Vec<(String, u32)>,
+//! }
+//!
+//! let config = Config {
+//!     hosts: vec!["a".to_owned(), "b".to_owned()].into_iter().collect(),
+//!     steps: vec![("build".to_owned(), 2), ("test".to_owned(), 1)],
+//! };
+//! let yaml = serde_yaml::to_string(&config).unwrap();
+//! assert_eq!(
+//!     yaml,
+//!     "hosts: !!set\n  a: null\n  b: null\nsteps: !!omap\n- build: 2\n- test: 1\n",
+//! );
+//! assert_eq!(serde_yaml::from_str::<Config>(&yaml).unwrap(), config);
+//! ```
+
+use crate::value::tagged::SerializeTag;
+use crate::value::Tag;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// Writes any collection as a `!!set`, a mapping from its elements to null.
+pub mod set {
+    use super::Tagged;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<'a, T, E, S>(set: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a E>,
+        E: Serialize + 'a,
+        S: Serializer,
+    {
+        Tagged("!!set", Set(set)).serialize(serializer)
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+
+    struct Set<'a, T>(&'a T);
+
+    impl<'a, T, E> Serialize for Set<'a, T>
+    where
+        &'a T: IntoIterator<Item = &'a E>,
+        E: Serialize + 'a,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_map(self.0.into_iter().map(|element| (element, ())))
+        }
+    }
+}
+
+/// Writes a sequence of pairs as `!!pairs`, a sequence of mappings with a
+/// single entry, in which keys may repeat.
+pub mod pairs {
+    use super::{Pairs, Tagged};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<'a, T, K, V, S>(pairs: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a (K, V)>,
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        S: Serializer,
+    {
+        Tagged("!!pairs", Pairs(pairs)).serialize(serializer)
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Writes a sequence of pairs as `!!omap`, an ordered mapping written as a
+/// sequence of mappings with a single entry. The keys are expected to be
+/// unique but this is not checked.
+pub mod omap {
+    use super::{Pairs, Tagged};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(missing_docs)]
+    pub fn serialize<'a, T, K, V, S>(pairs: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a (K, V)>,
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        S: Serializer,
+    {
+        Tagged("!!omap", Pairs(pairs)).serialize(serializer)
+    }
+
+    #[allow(missing_docs)]
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// Serializes the same way as a [`TaggedValue`](crate::value::TaggedValue).
+struct Tagged<T>(&'static str, T);
+
+impl<T> Serialize for Tagged<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&SerializeTag(&Tag::new(self.0)), &self.1)?;
+        map.end()
+    }
+}
+
+struct Pairs<'a, T>(&'a T);
+
+impl<'a, T, K, V> Serialize for Pairs<'a, T>
+where
+    &'a T: IntoIterator<Item = &'a (K, V)>,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.into_iter().map(|(key, value)| Pair(key, value)))
+    }
+}
+
+struct Pair<'a, K, V>(&'a K, &'a V);
+
+impl<'a, K, V> Serialize for Pair<'a, K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0, self.1)?;
+        map.end()
+    }
+}
@@ -5,9 +5,10 @@
 )]
 
 use indoc::indoc;
+use serde::Deserialize as _;
 use serde_derive::Deserialize;
-use serde_yaml::Value;
-use std::collections::BTreeMap;
+use serde_yaml::{Deserializer, DeserializerOptions, Encoding, Schema, Spanned, Value};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 
 fn test_de<'de, T>(yaml: &'de str, expected: &T)
@@ -61,6 +62,96 @@ fn test_alias() {
     test_de(yaml, &expected);
 }
 
+#[test]
+fn test_merge_key() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Service {
+        image: String,
+        retries: u8,
+        timeout: u32,
+    }
+
+    let yaml = indoc! {"
+        defaults: &defaults
+          image: base
+          retries: 3
+          timeout: 30
+        service:
+          <<: *defaults
+          timeout: 60
+    "};
+    let mut expected = BTreeMap::new();
+    expected.insert(
+        "defaults".to_owned(),
+        Service {
+            image: "base".to_owned(),
+            retries: 3,
+            timeout: 30,
+        },
+    );
+    expected.insert(
+        "service".to_owned(),
+        Service {
+            image: "base".to_owned(),
+            retries: 3,
+            timeout: 60,
+        },
+    );
+    test_de(yaml, &expected);
+
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(value["service"].as_mapping().unwrap().len(), 3);
+    assert_eq!(value["service"]["timeout"], 60);
+    assert_eq!(value["service"].get("<<"), None);
+}
+
+#[test]
+fn test_merge_key_sequence() {
+    let yaml = indoc! {"
+        - &a {x: 1, y: 1}
+        - &b {y: 2, z: 2}
+        - <<: [*a, *b, {w: 3}]
+          x: 0
+    "};
+    let mut merged = BTreeMap::new();
+    merged.insert("x".to_owned(), 0);
+    merged.insert("y".to_owned(), 1);
+    merged.insert("z".to_owned(), 2);
+    merged.insert("w".to_owned(), 3);
+    let deserialized: Vec<BTreeMap<String, i32>> = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(merged, deserialized[2]);
+
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    let keys: Vec<&str> = value[2]
+        .as_mapping()
+        .unwrap()
+        .iter()
+        .map(|(key, _value)| key.as_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["x", "y", "z", "w"]);
+}
+
+#[test]
+fn test_merge_key_nested() {
+    let yaml = indoc! {"
+        base: &base
+          a: 1
+        middle: &middle
+          <<: *base
+          b: 2
+        top:
+          <<: *middle
+          c: 3
+    "};
+    let mut expected = BTreeMap::new();
+    expected.insert("a".to_owned(), 1);
+    expected.insert("b".to_owned(), 2);
+    expected.insert("c".to_owned(), 3);
+    let deserialized: BTreeMap<String, BTreeMap<String, i32>> = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(expected, deserialized["top"]);
+}
+
 #[test]
 fn test_option() {
     #[derive(Deserialize, PartialEq, Debug)]
@@ -186,6 +277,67 @@ fn test_enum_tag() {
     test_de(yaml, &expected);
 }
 
+#[test]
+fn test_enum_tag_on_collections() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum E {
+        Unit,
+        Tuple(u8, u8),
+        Struct { x: u8 },
+    }
+    let yaml = indoc! {"
+        - !Unit
+        - !Tuple [1, 2]
+        - !Struct
+          x: 3
+    "};
+    let expected = vec![E::Unit, E::Tuple(1, 2), E::Struct { x: 3 }];
+    test_de(yaml, &expected);
+}
+
+#[test]
+fn test_enum_unknown_tag_on_variant_name() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum E {
+        A,
+        B,
+    }
+    test_de("!local A", &E::A);
+    test_de("- !x A\n- !y B\n", &vec![E::A, E::B]);
+}
+
+#[test]
+fn test_tag_ignored_by_untagged_and_flatten() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        S(String),
+    }
+    test_de("!Ref foo", &Untagged::S("foo".to_owned()));
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Inner {
+        name: String,
+    }
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Outer {
+        id: u8,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+    let yaml = indoc! {"
+        id: 1
+        name: !Sub foo
+    "};
+    let expected = Outer {
+        id: 1,
+        inner: Inner {
+            name: "foo".to_owned(),
+        },
+    };
+    test_de(yaml, &expected);
+}
+
 #[test]
 fn test_number_as_string() {
     #[derive(Deserialize, PartialEq, Debug)]
@@ -202,6 +354,139 @@ fn test_number_as_string() {
     test_de(yaml, &expected);
 }
 
+#[test]
+fn test_binary() {
+    let yaml = indoc! {"
+        - !!binary aGVsbG8=
+        - !!binary |
+          AAcOFRwjKjE4P0ZNVFti
+          aXB3foWMk5qhqK+2vcQ=
+    "};
+    let expected = vec![b"hello".to_vec(), (0..200).step_by(7).collect()];
+    test_de(yaml, &expected);
+
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(value[0].as_binary(), Some(&b"hello"[..]));
+    let bytes: Vec<u8> = serde_yaml::from_value(value[0].clone()).unwrap();
+    assert_eq!(bytes, b"hello");
+}
+
+#[test]
+fn test_timestamp() {
+    use serde_yaml::value::{SpannedNode, SpannedValue};
+    use serde_yaml::Timestamp;
+
+    let yaml = indoc! {"
+        canonical: 2001-12-15T02:59:43.1Z
+        iso8601: 2001-12-14t21:59:43.10-05:00
+        spaced: 2001-12-14 21:59:43.10 -5
+        no time zone: 2001-12-15 2:59:43.10
+        date: 2002-12-14
+        tagged: !!timestamp 2002-12-14
+    "};
+    let expected: BTreeMap<String, Timestamp> = [
+        ("canonical", "2001-12-15T02:59:43.1Z"),
+        ("iso8601", "2001-12-14T21:59:43.1-05:00"),
+        ("spaced", "2001-12-14T21:59:43.1-05:00"),
+        ("no time zone", "2001-12-15T02:59:43.1"),
+        ("date", "2002-12-14"),
+        ("tagged", "2002-12-14"),
+    ]
+    .iter()
+    .map(|(key, timestamp)| (key.to_string(), timestamp.parse().unwrap()))
+    .collect();
+    test_de(yaml, &expected);
+
+    // Only the tagged timestamp is one in a Value under the core schema.
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(value["date"], "2002-12-14");
+    assert_eq!(value["tagged"], Value::Timestamp(expected["tagged"]));
+    let timestamp: Timestamp = serde_yaml::from_value(value["date"].clone()).unwrap();
+    assert_eq!(timestamp, expected["date"]);
+
+    let options = DeserializerOptions::new().schema(Schema::Yaml11);
+    let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+    for (key, timestamp) in &expected {
+        assert_eq!(value[key.as_str()], Value::Timestamp(*timestamp));
+    }
+    let map: BTreeMap<String, Timestamp> = serde_yaml::from_value(value).unwrap();
+    assert_eq!(map, expected);
+
+    let spanned: SpannedValue = serde_yaml::from_str(yaml).unwrap();
+    let tagged = spanned.get("tagged").unwrap();
+    assert_eq!(tagged.value, SpannedNode::Timestamp(expected["tagged"]));
+    assert_eq!(&yaml[tagged.span()], "!!timestamp 2002-12-14");
+
+    // Other types see the text of a timestamp.
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        S(String),
+    }
+    let yaml = "!!timestamp 2001-12-14";
+    test_de(yaml, &Untagged::S("2001-12-14".to_owned()));
+    test_de(yaml, &"2001-12-14".to_owned());
+    let options = DeserializerOptions::new().schema(Schema::Yaml11);
+    let de = Deserializer::from_str("2001-12-14").with_options(options);
+    let err = BTreeMap::<String, u8>::deserialize(de).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"2001-12-14\", expected a map at position 0",
+    );
+}
+
+#[test]
+fn test_collection_tags() {
+    use std::collections::HashSet;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Data {
+        set: BTreeSet<String>,
+        hash_set: HashSet<u8>,
+        omap: Vec<(String, u8)>,
+        pairs: Vec<(String, u8)>,
+    }
+
+    let yaml = indoc! {"
+        set: !!set {b, a}
+        hash_set: !!set
+          ? 1
+          ? 2
+        omap: !!omap
+        - b: 1
+        - a: 2
+        pairs: !!pairs [x: 1, x: 2]
+    "};
+    let expected = Data {
+        set: vec!["a".to_owned(), "b".to_owned()].into_iter().collect(),
+        hash_set: vec![1, 2].into_iter().collect(),
+        omap: vec![("b".to_owned(), 1), ("a".to_owned(), 2)],
+        pairs: vec![("x".to_owned(), 1), ("x".to_owned(), 2)],
+    };
+    test_de(yaml, &expected);
+
+    // Other visitors of deserialize_any see the untagged collections.
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Map(BTreeMap<String, ()>),
+        Seq(Vec<BTreeMap<String, u8>>),
+    }
+    let mut set = BTreeMap::new();
+    set.insert("a".to_owned(), ());
+    test_de("!!set {a}", &Untagged::Map(set));
+    let mut pair = BTreeMap::new();
+    pair.insert("x".to_owned(), 1);
+    test_de("!!omap [x: 1]", &Untagged::Seq(vec![pair]));
+
+    // Only the elements of `!!pairs` and `!!omap` may be written as mappings.
+    let result = serde_yaml::from_str::<Vec<(String, u8)>>("[x: 1]");
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        ".[0]: invalid type: map, expected a tuple of size 2 at line 1 column 2",
+    );
+}
+
 #[test]
 fn test_i128_big() {
     let expected: i128 = i64::MIN as i128 - 1;
@@ -405,3 +690,317 @@ fn test_stateful() {
         test_de_seed(yaml, Seed(seed), &expected);
     }
 }
+
+#[test]
+fn test_schemas() {
+    fn de(yaml: &str, schema: Schema) -> Value {
+        let options = DeserializerOptions::new().schema(schema);
+        Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap()
+    }
+
+    let schemas = [
+        Schema::Failsafe,
+        Schema::Json,
+        Schema::Compat,
+        Schema::Core,
+        Schema::Yaml11,
+    ];
+
+    // The expected values are written as core schema YAML.
+    let cases = [
+        ("null", ["'null'", "~", "~", "~", "~"]),
+        ("NULL", ["'NULL'", "'NULL'", "'NULL'", "~", "~"]),
+        ("~", ["'~'", "'~'", "~", "~", "~"]),
+        ("true", ["'true'", "true", "true", "true", "true"]),
+        ("True", ["'True'", "'True'", "'True'", "true", "true"]),
+        ("FALSE", ["'FALSE'", "'FALSE'", "'FALSE'", "false", "false"]),
+        ("yes", ["'yes'", "'yes'", "'yes'", "'yes'", "true"]),
+        ("Off", ["'Off'", "'Off'", "'Off'", "'Off'", "false"]),
+        ("y", ["'y'", "'y'", "'y'", "'y'", "'y'"]),
+        ("12", ["'12'", "12", "12", "12", "12"]),
+        ("-12", ["'-12'", "-12", "-12", "-12", "-12"]),
+        ("+12", ["'+12'", "'+12'", "12", "12", "12"]),
+        ("0777", ["'0777'", "'0777'", "'0777'", "'0777'", "511"]),
+        ("0o777", ["'0o777'", "'0o777'", "511", "511", "'0o777'"]),
+        ("0x_1F", ["'0x_1F'", "'0x_1F'", "'0x_1F'", "'0x_1F'", "31"]),
+        ("0b1010", ["'0b1010'", "'0b1010'", "10", "10", "10"]),
+        (
+            "1_000",
+            ["'1_000'", "'1_000'", "'1_000'", "'1_000'", "1000"],
+        ),
+        ("-1:30", ["'-1:30'", "'-1:30'", "'-1:30'", "'-1:30'", "-90"]),
+        ("1:60", ["'1:60'", "'1:60'", "'1:60'", "'1:60'", "'1:60'"]),
+        ("1.5", ["'1.5'", "1.5", "1.5", "1.5", "1.5"]),
+        ("1e3", ["'1e3'", "1000.0", "1000.0", "1000.0", "'1e3'"]),
+        (
+            "1.0e+3",
+            ["'1.0e+3'", "1000.0", "1000.0", "1000.0", "1000.0"],
+        ),
+        (
+            "1_0.5",
+            ["'1_0.5'", "'1_0.5'", "'1_0.5'", "'1_0.5'", "10.5"],
+        ),
+        (
+            "1:30.5",
+            ["'1:30.5'", "'1:30.5'", "'1:30.5'", "'1:30.5'", "90.5"],
+        ),
+        (".inf", ["'.inf'", "'.inf'", ".inf", ".inf", ".inf"]),
+    ];
+    for (yaml, expected) in &cases {
+        for (schema, expected) in schemas.iter().zip(expected) {
+            let expected: Value = serde_yaml::from_str(expected).unwrap();
+            assert_eq!(expected, de(yaml, *schema), "{:?} {:?}", schema, yaml);
+        }
+    }
+
+    // Explicit tags are honored even by the failsafe schema.
+    assert_eq!(Value::from(1), de("!!int 1", Schema::Failsafe));
+}
+
+#[test]
+fn test_default_schema() {
+    // As in earlier releases, only the lowercase spellings of nulls and
+    // booleans are nulls and booleans unless another schema is chosen.
+    let value: Value = serde_yaml::from_str("[True, NULL, Null, true, null]").unwrap();
+    assert_eq!(value[0], "True");
+    assert_eq!(value[1], "NULL");
+    assert_eq!(value[2], "Null");
+    assert_eq!(value[3], true);
+    assert_eq!(value[4], Value::Null);
+
+    let yaml = serde_yaml::to_string(&["True", "NULL", "Null", "true", "null"]).unwrap();
+    assert_eq!(yaml, "- True\n- NULL\n- Null\n- 'true'\n- 'null'\n");
+}
+
+#[test]
+fn test_schema_typed() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        enabled: bool,
+        mode: u32,
+        size: u64,
+        ratio: f64,
+        name: Option<String>,
+    }
+
+    let yaml = indoc! {"
+        enabled: on
+        mode: 0755
+        size: 1_048_576
+        ratio: 1:30.5
+        name: Null
+    "};
+    let options = DeserializerOptions::new().schema(Schema::Yaml11);
+    let de = Deserializer::from_str(yaml).with_options(options);
+    let expected = Config {
+        enabled: true,
+        mode: 0o755,
+        size: 1_048_576,
+        ratio: 90.5,
+        name: None,
+    };
+    assert_eq!(expected, Config::deserialize(de).unwrap());
+
+    let options = DeserializerOptions::new().schema(Schema::Failsafe);
+    let de = Deserializer::from_str("name: ~").with_options(options);
+    let value = BTreeMap::<String, Option<String>>::deserialize(de).unwrap();
+    assert_eq!(value["name"].as_deref(), Some("~"));
+}
+
+fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for ch in text.chars() {
+        match encoding {
+            Encoding::Utf8 => bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+            Encoding::Utf16Le => {
+                for unit in ch.encode_utf16(&mut [0; 2]) {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                }
+            }
+            Encoding::Utf16Be => {
+                for unit in ch.encode_utf16(&mut [0; 2]) {
+                    bytes.extend_from_slice(&unit.to_be_bytes());
+                }
+            }
+            Encoding::Utf32Le => bytes.extend_from_slice(&(ch as u32).to_le_bytes()),
+            Encoding::Utf32Be => bytes.extend_from_slice(&(ch as u32).to_be_bytes()),
+        }
+    }
+    bytes
+}
+
+#[test]
+fn test_encodings() {
+    let yaml = indoc! {"
+        name: \u{e9}t\u{e9} \u{1f600}
+        list: [1, 2]
+    "};
+    let expected: Value = serde_yaml::from_str(yaml).unwrap();
+
+    let encodings = [
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Utf32Le,
+        Encoding::Utf32Be,
+    ];
+    for encoding in encodings {
+        for bom in ["", "\u{feff}"] {
+            let bytes = encode(&format!("{}{}", bom, yaml), encoding);
+            let value: Value = serde_yaml::from_slice(&bytes).unwrap();
+            assert_eq!(expected, value, "{} {:?}", encoding, bom);
+
+            let value: Value = serde_yaml::from_reader(&*bytes).unwrap();
+            assert_eq!(expected, value, "{} {:?}", encoding, bom);
+
+            let options = DeserializerOptions::new().encoding(encoding);
+            let de = Deserializer::from_slice(&bytes).with_options(options);
+            assert_eq!(expected, Value::deserialize(de).unwrap());
+        }
+    }
+
+    let value: Value = serde_yaml::from_str(&format!("\u{feff}{}", yaml)).unwrap();
+    assert_eq!(expected, value);
+}
+
+#[test]
+fn test_encoding_spans() {
+    let yaml = indoc! {"
+        \u{feff}a: \u{e9}
+        b: \u{1f600}
+        c: x
+    "};
+    // Byte widths of the BOM, of an ASCII character, of 'é' and of the emoji.
+    let cases = [
+        (Encoding::Utf8, 3, 1, 2, 4),
+        (Encoding::Utf16Le, 2, 2, 2, 4),
+        (Encoding::Utf32Be, 4, 4, 4, 4),
+    ];
+    for (encoding, bom, ascii, e, emoji) in cases {
+        let bytes = encode(yaml, encoding);
+        let map: BTreeMap<String, Spanned<String>> = serde_yaml::from_slice(&bytes).unwrap();
+
+        let a = bom + 3 * ascii;
+        assert_eq!(map["a"].span(), a..a + e);
+
+        let c = a + e + 4 * ascii + emoji + 4 * ascii;
+        assert_eq!(map["c"].span(), c..c + ascii);
+        assert_eq!(map["c"].start_location.unwrap().line(), 3);
+        assert_eq!(map["c"].start_location.unwrap().column(), 4);
+    }
+}
+
+#[test]
+fn test_document_info() {
+    let yaml = indoc! {"
+        %TAG ! tag:example.com,2000:app/
+        %TAG !! tag:example.com,2000:
+        --- !foo
+        a: 1
+        ...
+        %YAML 1.1
+        ---
+        b: 2
+        ---
+        c: 3
+    "};
+    let infos: Vec<_> = Deserializer::from_str(yaml)
+        .map(|de| de.document_info().unwrap().clone())
+        .collect();
+    assert_eq!(3, infos.len());
+
+    let tags = [
+        ("!".to_owned(), "tag:example.com,2000:app/".to_owned()),
+        ("!!".to_owned(), "tag:example.com,2000:".to_owned()),
+    ];
+    assert_eq!(0, infos[0].index());
+    assert_eq!(None, infos[0].version());
+    assert_eq!(tags, infos[0].tag_directives());
+    assert!(infos[0].explicit_start());
+    assert!(infos[0].explicit_end());
+
+    assert_eq!(1, infos[1].index());
+    assert_eq!(Some((1, 1)), infos[1].version());
+    assert!(infos[1].tag_directives().is_empty());
+    assert!(infos[1].explicit_start());
+    assert!(!infos[1].explicit_end());
+
+    assert_eq!(2, infos[2].index());
+    assert_eq!(None, infos[2].version());
+
+    assert!(Deserializer::from_str(yaml).document_info().is_none());
+}
+
+#[test]
+fn test_ignored_fields() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        servers: Vec<Server>,
+    }
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: String,
+        #[serde(default)]
+        timeout: u64,
+    }
+    let yaml = indoc! {"
+        name: app
+        defaults: &defaults {timeuot: 30}
+        servers:
+          - host: a.example.com
+            timeuot: *defaults
+          - host: b.example.com
+            timeout: 5
+            8080: open
+    "};
+    let (config, ignored) = serde_yaml::from_str_with_ignored::<Config>(yaml).unwrap();
+    let expected = Config {
+        name: "app".to_owned(),
+        servers: vec![
+            Server {
+                host: "a.example.com".to_owned(),
+                timeout: 0,
+            },
+            Server {
+                host: "b.example.com".to_owned(),
+                timeout: 5,
+            },
+        ],
+    };
+    assert_eq!(expected, config);
+
+    let ignored: Vec<_> = ignored
+        .iter()
+        .map(|field| {
+            let location = field.location();
+            (field.path().to_string(), location.line(), location.column())
+        })
+        .collect();
+    let expected = [
+        ("defaults".to_owned(), 2, 1),
+        ("servers[0].timeuot".to_owned(), 5, 5),
+        ("servers[1].8080".to_owned(), 8, 5),
+    ];
+    assert_eq!(expected[..], ignored);
+
+    let (_, ignored) = serde_yaml::from_str_with_ignored::<Value>(yaml).unwrap();
+    assert!(ignored.is_empty());
+
+    // A variant that holds IgnoredAny on purpose is not an ignored field.
+    #[derive(Deserialize, Debug)]
+    enum E {
+        A(#[allow(dead_code)] serde::de::IgnoredAny),
+    }
+    let (_, ignored) = serde_yaml::from_str_with_ignored::<Vec<E>>("- !A 1\n").unwrap();
+    assert!(ignored.is_empty());
+    let (_, ignored) =
+        serde_yaml::from_str_with_ignored::<BTreeMap<String, E>>("x: !A 1\n").unwrap();
+    assert!(ignored.is_empty());
+
+    // Neither are the null values of a `!!set`.
+    let (set, ignored) =
+        serde_yaml::from_str_with_ignored::<BTreeSet<String>>("!!set {a, b}").unwrap();
+    assert_eq!(set.len(), 2);
+    assert!(ignored.is_empty());
+}
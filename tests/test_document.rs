@@ -0,0 +1,298 @@
+use indoc::indoc;
+use serde_derive::Serialize;
+use serde_yaml::Document;
+use std::collections::BTreeMap;
+
+fn edit(yaml: &str, f: impl FnOnce(&mut Document)) -> String {
+    let mut doc: Document = yaml.parse().unwrap();
+    f(&mut doc);
+    doc.into_string()
+}
+
+#[test]
+fn test_untouched() {
+    let yaml = indoc! {"
+        # comment
+        a: 'quoted'   # trailing
+        b:
+          - 1
+
+          - {x: 1,   y: 2}
+    "};
+    let doc: Document = yaml.parse().unwrap();
+    assert_eq!(doc.as_str(), yaml);
+    assert_eq!(doc.to_string(), yaml);
+}
+
+#[test]
+fn test_get() {
+    let yaml = indoc! {"
+        a:
+          b: [1, 2, 3]
+          c: text
+    "};
+    let doc: Document = yaml.parse().unwrap();
+    assert_eq!(doc.get::<Vec<u8>>("a.b").unwrap(), [1, 2, 3]);
+    assert_eq!(doc.get::<u8>("a.b[1]").unwrap(), 2);
+    assert_eq!(doc.get::<String>("a.c").unwrap(), "text");
+    assert_eq!(doc.span("a.c").unwrap(), 23..27);
+    assert_eq!(
+        doc.get::<u8>("a.d").unwrap_err().to_string(),
+        "no value at `a.d`",
+    );
+    assert_eq!(
+        doc.get::<u8>("a.b[3]").unwrap_err().to_string(),
+        "no value at `a.b[3]`",
+    );
+}
+
+#[test]
+fn test_replace_scalar() {
+    let yaml = indoc! {"
+        a: 1  # keep me
+        b: \"two\"
+        c: [1, 2]
+        d:
+        e:
+        - 
+    "};
+    let edited = edit(yaml, |doc| {
+        doc.replace("d", &true).unwrap();
+        doc.replace("e[0]", "item").unwrap();
+        doc.replace("a", &10).unwrap();
+        doc.replace("b", "x: y").unwrap();
+        doc.replace("c[1]", &20).unwrap();
+    });
+    let expected = indoc! {"
+        a: 10  # keep me
+        b: 'x: y'
+        c: [1, 20]
+        d: true
+        e:
+        - item
+    "};
+    assert_eq!(edited, expected);
+}
+
+#[test]
+fn test_replace_with_block() {
+    let yaml = indoc! {"
+        outer:
+          a: 1
+          b:
+          c: old
+        list:
+        - x
+        - y
+    "};
+    let mut map = BTreeMap::new();
+    map.insert("k", vec![1, 2]);
+    let edited = edit(yaml, |doc| {
+        doc.replace("outer.a", &map).unwrap();
+        doc.replace("outer.b", "line1\nline2").unwrap();
+        doc.replace("list[1]", &map).unwrap();
+    });
+    let expected = indoc! {"
+        outer:
+          a:
+            k:
+            - 1
+            - 2
+          b: |-
+            line1
+            line2
+          c: old
+        list:
+        - x
+        - k:
+          - 1
+          - 2
+    "};
+    assert_eq!(edited, expected);
+    serde_yaml::from_str::<serde_yaml::Value>(&edited).unwrap();
+}
+
+#[test]
+fn test_replace_in_flow() {
+    let yaml = "a: [1, 2]\n";
+    let mut doc: Document = yaml.parse().unwrap();
+    let err = doc.replace("a[0]", &vec![1]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cannot write a value that needs block style inside the flow collection at `a[0]`",
+    );
+    doc.replace("a[0]", &Vec::<u8>::new()).unwrap();
+    assert_eq!(doc.as_str(), "a: [[], 2]\n");
+}
+
+#[test]
+fn test_insert_mapping() {
+    #[derive(Serialize)]
+    struct Server {
+        host: &'static str,
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        servers:
+          first:
+            host: a  # primary
+        flow: {x: 1}
+        empty: {}
+    "};
+    let server = Server {
+        host: "b",
+        port: 80,
+    };
+    let edited = edit(yaml, |doc| {
+        doc.insert("servers.second", &server).unwrap();
+        doc.insert("servers.first.port", &8080).unwrap();
+        doc.insert("flow.y", &2).unwrap();
+        doc.insert("empty.z", "text").unwrap();
+    });
+    let expected = indoc! {"
+        servers:
+          first:
+            host: a  # primary
+            port: 8080
+          second:
+            host: b
+            port: 80
+        flow: {x: 1, y: 2}
+        empty: {z: text}
+    "};
+    assert_eq!(edited, expected);
+
+    let mut doc: Document = yaml.parse().unwrap();
+    assert_eq!(
+        doc.insert("servers.first", &1).unwrap_err().to_string(),
+        "there is already a value at `servers.first`",
+    );
+}
+
+#[test]
+fn test_insert_sequence() {
+    let yaml = indoc! {"
+        list:
+          - a
+          - b  # last
+        flow: [1, 3]
+    "};
+    let edited = edit(yaml, |doc| {
+        doc.insert("list[0]", "first").unwrap();
+        doc.insert("list[3]", "end").unwrap();
+        doc.insert("flow[1]", &2).unwrap();
+        doc.insert("flow[3]", &4).unwrap();
+    });
+    let expected = indoc! {"
+        list:
+          - first
+          - a
+          - b  # last
+          - end
+        flow: [1, 2, 3, 4]
+    "};
+    assert_eq!(edited, expected);
+
+    let mut doc: Document = yaml.parse().unwrap();
+    assert_eq!(
+        doc.insert("list[5]", "x").unwrap_err().to_string(),
+        "cannot insert at index 5 of the sequence at `list` of length 2",
+    );
+}
+
+#[test]
+fn test_insert_after_kept_block_scalar() {
+    let yaml = "a:\n  b: |+\n    keep\n\n";
+    let mut doc: Document = yaml.parse().unwrap();
+    doc.insert("a.c", &1).unwrap();
+    assert_eq!(doc.as_str(), "a:\n  b: |+\n    keep\n\n  c: 1\n");
+    assert_eq!(doc.get::<String>("a.b").unwrap(), "keep\n\n");
+
+    let yaml = "- |+\n  x\n\n";
+    let edited = edit(yaml, |doc| doc.insert("[1]", &1).unwrap());
+    assert_eq!(edited, "- |+\n  x\n\n- 1\n");
+}
+
+#[test]
+fn test_edit_keeps_line_breaks() {
+    let yaml = "a: 1\r\nb: 2\r\n";
+    let edited = edit(yaml, |doc| {
+        doc.insert("c", &[1, 2]).unwrap();
+        doc.replace("a", &[1]).unwrap();
+        doc.remove("b").unwrap();
+    });
+    assert_eq!(edited, "a:\r\n  - 1\r\nc:\r\n  - 1\r\n  - 2\r\n");
+}
+
+#[test]
+fn test_remove() {
+    let yaml = indoc! {"
+        # header
+        a: 1  # gone
+        b:
+          - x
+          - y
+        c: {p: 1, q: 2}
+        d: [only]
+        e: last
+    "};
+    let edited = edit(yaml, |doc| {
+        doc.remove("a").unwrap();
+        doc.remove("b[0]").unwrap();
+        doc.remove("c.q").unwrap();
+        doc.remove("d[0]").unwrap();
+        doc.remove("e").unwrap();
+    });
+    let expected = indoc! {"
+        # header
+        b:
+          - y
+        c: {p: 1}
+        d: []
+    "};
+    assert_eq!(edited, expected);
+}
+
+#[test]
+fn test_invalid_edit_keeps_document() {
+    let yaml = "a: &anchor 1\nb: *anchor\n";
+    let mut doc: Document = yaml.parse().unwrap();
+    assert_eq!(doc.get::<u8>("b").unwrap(), 1);
+    let err = doc.replace("a", &2).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("the edit would make the document invalid: "));
+    assert_eq!(doc.as_str(), yaml);
+}
+
+#[test]
+fn test_at_path() {
+    let yaml = indoc! {"
+        package:
+          version: \"0.1.0\"
+        dependencies:
+          serde: { version: 1.0.100, features: [derive] }
+    "};
+    let span = serde_yaml::span_at_path(yaml, "dependencies.serde.version").unwrap();
+    assert_eq!(&yaml[span], "1.0.100");
+    let span = serde_yaml::span_at_path(yaml, "package.version").unwrap();
+    assert_eq!(&yaml[span], "\"0.1.0\"");
+
+    let bumped = serde_yaml::replace_at_path(yaml, "dependencies.serde.version", "1.0.101");
+    let expected = indoc! {"
+        package:
+          version: \"0.1.0\"
+        dependencies:
+          serde: { version: 1.0.101, features: [derive] }
+    "};
+    assert_eq!(bumped.unwrap(), expected);
+
+    let err = serde_yaml::replace_at_path(yaml, "dependencies.rand", "0.8").unwrap_err();
+    assert_eq!(err.to_string(), "no value at `dependencies.rand`");
+}
+
+#[test]
+fn test_multiple_documents() {
+    assert!("a: 1\n---\nb: 2\n".parse::<Document>().is_err());
+}
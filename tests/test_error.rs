@@ -1,7 +1,13 @@
 use indoc::indoc;
+use serde::de::value::MapDeserializer;
+use serde::de::IgnoredAny;
+use serde::Deserialize as _;
 use serde_derive::Deserialize;
-use serde_yaml::Deserializer;
+use serde_yaml::path::{PathBuf, Segment};
+use serde_yaml::{Deserializer, DeserializerOptions, Encoding, Location, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 fn test_error<T>(yaml: &str, expected: &str)
 where
@@ -95,6 +101,38 @@ fn test_ignored_unknown_anchor() {
     test_error::<Wrapper>(yaml, expected);
 }
 
+#[test]
+fn test_merge_scalar() {
+    let yaml = indoc! {"
+        a:
+          <<: 1
+          b: 2
+    "};
+    let expected = "a.<<: invalid type: integer `1`, expected a mapping or list of mappings for merging at line 2 column 7";
+    test_error::<serde_yaml::Value>(yaml, expected);
+}
+
+#[test]
+fn test_merge_sequence_element() {
+    let yaml = indoc! {"
+        base: &base
+          x: 1
+        a:
+          <<: [*base, [y]]
+    "};
+    let expected =
+        "a.<<[1]: invalid type: sequence, expected a mapping for merging at line 4 column 15";
+    test_error::<serde_yaml::Value>(yaml, expected);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_merge_recursive() {
+    let yaml = "&a {<<: *a}";
+    let expected = "recursion limit exceeded at position 0";
+    test_error::<serde_yaml::Value>(yaml, expected);
+}
+
 #[test]
 fn test_two_documents() {
     let yaml = indoc! {"
@@ -169,6 +207,48 @@ fn test_variant_not_string() {
     test_error::<E>(yaml, expected);
 }
 
+#[test]
+fn test_serialize_nested_tag() {
+    use serde_yaml::value::{Tag, TaggedValue, Value};
+
+    let value = Value::Tagged(Box::new(TaggedValue {
+        tag: Tag::new("Outer"),
+        value: Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("Inner"),
+            value: Value::Null,
+        })),
+    }));
+    let expected = "serializing a value with more than one YAML tag is not supported";
+    assert_eq!(
+        expected,
+        serde_yaml::to_string(&value).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_spanned_value_error() {
+    #[derive(Deserialize, Debug)]
+    struct Config {
+        #[allow(dead_code)]
+        ports: Vec<u8>,
+    }
+
+    let yaml = indoc! {"
+        ports:
+          - 80
+          - 443
+    "};
+    let spanned: serde_yaml::SpannedValue = serde_yaml::from_str(yaml).unwrap();
+    let err = serde_yaml::from_spanned_value::<Config>(spanned).unwrap_err();
+    let expected = "ports[1]: invalid value: integer `443`, expected u8 at line 3 column 5";
+    assert_eq!(expected, err.to_string());
+    assert_eq!(err.location().unwrap().index(), yaml.find("443").unwrap());
+
+    let spanned: serde_yaml::SpannedValue = serde_yaml::from_str("other: 1").unwrap();
+    let err = serde_yaml::from_spanned_value::<Config>(spanned).unwrap_err();
+    assert_eq!("missing field `ports` at position 0", err.to_string());
+}
+
 #[test]
 fn test_bad_bool() {
     let yaml = indoc! {"
@@ -209,6 +289,41 @@ fn test_bad_null() {
     test_error::<()>(yaml, expected);
 }
 
+#[test]
+fn test_bad_binary() {
+    let yaml = indoc! {"
+        ---
+        !!binary aGVsbG8
+    "};
+    let expected =
+        "invalid value: string \"aGVsbG8\", expected base64-encoded binary data at line 2 column 1";
+    test_error::<Value>(yaml, expected);
+}
+
+#[test]
+fn test_bad_timestamp() {
+    let yaml = indoc! {"
+        ---
+        !!timestamp 2001-02-29
+    "};
+    let expected = "invalid value: string \"2001-02-29\", expected a timestamp at line 2 column 1";
+    test_error::<Value>(yaml, expected);
+
+    let expected = "invalid value: string \"2001-13-01\", expected a timestamp at position 0";
+    test_error::<serde_yaml::Timestamp>("2001-13-01", expected);
+}
+
+#[test]
+fn test_bad_pair() {
+    let yaml = indoc! {"
+        --- !!pairs
+        - a: 1
+          b: 2
+    "};
+    let expected = ".[0]: invalid length 2, expected map containing 1 entry at line 2 column 3";
+    test_error::<Vec<(String, u8)>>(yaml, expected);
+}
+
 #[test]
 fn test_short_tuple() {
     let yaml = indoc! {"
@@ -297,3 +412,726 @@ fn test_finite_recursion_arrays() {
     let expected = "recursion limit exceeded at line 1 column 129";
     test_error::<S>(&yaml, expected);
 }
+
+#[test]
+fn test_max_depth_option() {
+    let yaml = "a: [1, [2]]";
+    let options = DeserializerOptions::new().max_depth(2);
+    let de = Deserializer::from_str(yaml).with_options(options);
+    let err = Value::deserialize(de).unwrap_err();
+    assert_eq!(
+        "recursion limit exceeded at line 1 column 8",
+        err.to_string(),
+    );
+    assert_eq!(Some(Location::new(7, 1, 8)), err.location());
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_billion_laughs() {
+    let yaml = indoc! {"
+        a: &a ~
+        b: &b [*a,*a,*a,*a,*a,*a,*a,*a,*a]
+        c: &c [*b,*b,*b,*b,*b,*b,*b,*b,*b]
+        d: &d [*c,*c,*c,*c,*c,*c,*c,*c,*c]
+        e: &e [*d,*d,*d,*d,*d,*d,*d,*d,*d]
+        f: &f [*e,*e,*e,*e,*e,*e,*e,*e,*e]
+        g: &g [*f,*f,*f,*f,*f,*f,*f,*f,*f]
+        h: &h [*g,*g,*g,*g,*g,*g,*g,*g,*g]
+        i: &i [*h,*h,*h,*h,*h,*h,*h,*h,*h]
+    "};
+    let expected = "alias expansion limit exceeded at line 6 column 8";
+    test_error::<Value>(yaml, expected);
+
+    let options = DeserializerOptions::new().max_alias_expansions(20);
+    let de = Deserializer::from_str(yaml).with_options(options);
+    let err = Value::deserialize(de).unwrap_err();
+    assert_eq!(
+        "alias expansion limit exceeded at line 3 column 11",
+        err.to_string(),
+    );
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_billion_laughs_merge() {
+    let yaml = indoc! {"
+        a: &a {lol: 1}
+        b: &b {<<: [*a,*a,*a,*a,*a,*a,*a,*a,*a]}
+        c: &c {<<: [*b,*b,*b,*b,*b,*b,*b,*b,*b]}
+        d: &d {<<: [*c,*c,*c,*c,*c,*c,*c,*c,*c]}
+        e: &e {<<: [*d,*d,*d,*d,*d,*d,*d,*d,*d]}
+        f: &f {<<: [*e,*e,*e,*e,*e,*e,*e,*e,*e]}
+        g: &g {<<: [*f,*f,*f,*f,*f,*f,*f,*f,*f]}
+        h: &h {<<: [*g,*g,*g,*g,*g,*g,*g,*g,*g]}
+        i: &i {<<: [*h,*h,*h,*h,*h,*h,*h,*h,*h]}
+    "};
+    let expected = "alias expansion limit exceeded at line 6 column 13";
+    test_error::<Value>(yaml, expected);
+}
+
+#[cfg(not(miri))]
+#[test]
+fn test_billion_laughs_key() {
+    #[derive(Deserialize, Debug)]
+    pub struct Config {
+        #[allow(dead_code)]
+        map: BTreeMap<String, u8>,
+    }
+    let yaml = indoc! {"
+        a: &a [lol,lol,lol,lol,lol,lol,lol,lol,lol]
+        b: &b [*a,*a,*a,*a,*a,*a,*a,*a,*a]
+        c: &c [*b,*b,*b,*b,*b,*b,*b,*b,*b]
+        d: &d [*c,*c,*c,*c,*c,*c,*c,*c,*c]
+        e: &e [*d,*d,*d,*d,*d,*d,*d,*d,*d]
+        f: &f [*e,*e,*e,*e,*e,*e,*e,*e,*e]
+        g: &g [*f,*f,*f,*f,*f,*f,*f,*f,*f]
+        h: &h [*g,*g,*g,*g,*g,*g,*g,*g,*g]
+        map:
+          ? *h
+          : 1
+    "};
+    let err = serde_yaml::from_str::<Config>(yaml).unwrap_err();
+    assert_eq!(
+        "map: invalid type: sequence, expected a string at line 8 column 4",
+        err.to_string(),
+    );
+}
+
+#[test]
+fn test_max_document_bytes() {
+    let yaml = indoc! {"
+        ---
+        short: doc
+        ---
+        a: much longer document
+    "};
+    let options = DeserializerOptions::new().max_document_bytes(20);
+    let mut de = Deserializer::from_str(yaml).with_options(options);
+    let first_doc = de.next().unwrap();
+    assert!(Value::deserialize(first_doc).is_ok());
+
+    let second_doc = de.next().unwrap();
+    let err = Value::deserialize(second_doc).unwrap_err();
+    let expected = "document size limit exceeded at line 4 column 4";
+    assert_eq!(expected, err.to_string());
+    assert!(de.next().is_none());
+}
+
+#[test]
+fn test_max_documents() {
+    let yaml = indoc! {"
+        ---
+        0
+        ---
+        1
+        ---
+        2
+    "};
+    let options = DeserializerOptions::new().max_documents(2);
+    let mut de = Deserializer::from_str(yaml).with_options(options);
+    for expected in 0..2 {
+        let doc = de.next().unwrap();
+        assert_eq!(expected, usize::deserialize(doc).unwrap());
+    }
+
+    let third_doc = de.next().unwrap();
+    let err = usize::deserialize(third_doc).unwrap_err();
+    let expected = "document count limit exceeded at line 5 column 1";
+    assert_eq!(expected, err.to_string());
+    assert!(de.next().is_none());
+}
+
+#[test]
+fn test_duplicate_key_value() {
+    let yaml = indoc! {"
+        server:
+          host: example.com
+          port: 80
+          port: 8080
+    "};
+    let expected =
+        "server.port: duplicate key `port` at line 4 column 3, first defined at line 3 column 3";
+    test_error::<Value>(yaml, expected);
+    test_error::<serde_yaml::Mapping>(yaml, expected);
+
+    let err = serde_yaml::from_str::<Value>(yaml).unwrap_err();
+    assert_eq!(Some(Location::new(41, 4, 3)), err.location());
+    assert_eq!("server.port", err.path().unwrap().to_string());
+
+    // Other deserializers keep the last of the repeated keys.
+    let entries = vec![("port", 80), ("port", 8080)];
+    let de = MapDeserializer::<_, serde::de::value::Error>::new(entries.into_iter());
+    let mapping = serde_yaml::Mapping::deserialize(de).unwrap();
+    assert_eq!(
+        serde_yaml::from_str::<serde_yaml::Mapping>("port: 8080").unwrap(),
+        mapping
+    );
+}
+
+#[test]
+fn test_duplicate_key_struct() {
+    #[derive(Deserialize, Debug)]
+    struct S {
+        #[allow(dead_code)]
+        x: u8,
+        #[allow(dead_code)]
+        y: u8,
+    }
+
+    let yaml = indoc! {"
+        x: 1
+        y: 2
+        x: 3
+    "};
+    let expected = "x: duplicate key `x` at line 3 column 1, first defined at line 1 column 1";
+    test_error::<S>(yaml, expected);
+}
+
+#[test]
+fn test_duplicate_key_other_error() {
+    #[derive(Deserialize, Debug)]
+    struct S {
+        #[allow(dead_code)]
+        x: u8,
+        #[allow(dead_code)]
+        y: u8,
+    }
+
+    // The struct ignores the repeated unknown key, so the error is the
+    // missing field.
+    let yaml = indoc! {"
+        y: 1
+        z: 2
+        z: 3
+    "};
+    let expected = "missing field `x` at position 0";
+    test_error::<S>(yaml, expected);
+}
+
+#[test]
+fn test_duplicate_key_typed() {
+    let yaml = indoc! {"
+        a: 1
+        b: 2
+        ? b
+        : 3
+    "};
+    let map: BTreeMap<String, u8> = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(map["b"], 3);
+
+    let options = DeserializerOptions::new().deny_duplicate_keys(true);
+    let de = Deserializer::from_str(yaml).with_options(options);
+    let err = BTreeMap::<String, u8>::deserialize(de).unwrap_err();
+    let expected = "b: duplicate key `b` at line 3 column 3, first defined at line 2 column 1";
+    assert_eq!(expected, err.to_string());
+}
+
+#[test]
+fn test_duplicate_key_merge() {
+    // Keys brought in by a merge key may be overridden, but the mapping's own
+    // keys must still be unique.
+    let yaml = indoc! {"
+        base: &base {a: 1, b: 2}
+        derived:
+          <<: *base
+          a: 3
+          c: 4
+          c: 5
+    "};
+    let expected =
+        "derived.c: duplicate key `c` at line 6 column 3, first defined at line 5 column 3";
+    test_error::<Value>(yaml, expected);
+}
+
+#[test]
+fn test_invalid_utf16() {
+    // "a: " then an unpaired high surrogate, in UTF-16LE with a BOM.
+    let bytes = b"\xFF\xFEa\0:\0 \0\x00\xD8\n\0";
+    let err = serde_yaml::from_slice::<Value>(bytes).unwrap_err();
+    let expected = "invalid UTF-16LE input at line 1 column 4";
+    assert_eq!(expected, err.to_string());
+    assert_eq!(8, err.location().unwrap().index());
+
+    let options = DeserializerOptions::new().encoding(Encoding::Utf32Be);
+    let de = Deserializer::from_slice(b"\0\0\0a\0\0\0").with_options(options);
+    let err = Value::deserialize(de).unwrap_err();
+    assert_eq!("invalid UTF-32BE input at line 1 column 2", err.to_string());
+}
+
+#[test]
+fn test_utf16_error_location() {
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in "a: 1\nb: [\n".encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    let err = serde_yaml::from_slice::<Value>(&bytes).unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!(3, location.line());
+    assert_eq!(1, location.column());
+    assert_eq!(bytes.len(), location.index());
+}
+
+#[test]
+fn test_error_document_index() {
+    let yaml = indoc! {"
+        ---
+        0
+        ---
+        str
+        ---
+        [
+    "};
+    let mut de = Deserializer::from_str(yaml);
+    let first_doc = de.next().unwrap();
+    assert_eq!(0, u32::deserialize(first_doc).unwrap());
+
+    let second_doc = de.next().unwrap();
+    let err = u32::deserialize(second_doc).unwrap_err();
+    assert_eq!(Some(1), err.document_index());
+    let expected = "invalid type: string \"str\", expected u32 at line 4 column 1";
+    assert_eq!(expected, err.to_string());
+    assert_eq!(4, err.location().unwrap().line());
+
+    let third_doc = de.next().unwrap();
+    let err = u32::deserialize(third_doc).unwrap_err();
+    assert_eq!(Some(2), err.document_index());
+    assert!(de.next().is_none());
+
+    let err = serde_yaml::from_str::<u32>("str").unwrap_err();
+    assert_eq!(None, err.document_index());
+}
+
+#[test]
+fn test_recovering() {
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Deployment {
+        #[allow(dead_code)]
+        name: String,
+        #[allow(dead_code)]
+        replicas: u32,
+        #[allow(dead_code)]
+        containers: Vec<Container>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Container {
+        #[allow(dead_code)]
+        image: String,
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    let yaml = indoc! {"
+        name: web
+        replicas: -1
+        containers:
+          - image: nginx
+            port: 80
+          - image: app
+            port: 100000
+          - image: sidecar
+            prot: 9000
+        extra: true
+    "};
+    let errors = serde_yaml::from_str_recovering::<Deployment>(yaml).unwrap_err();
+    // The unknown field is refused by the struct itself, which ends
+    // deserialization before `extra` is reached.
+    let expected = indoc! {"
+        replicas: invalid type: integer `-1`, expected u32 at line 2 column 11
+        containers[1].port: invalid value: integer `100000`, expected u16 at line 7 column 11
+        containers[2]: unknown field `prot`, expected `image` or `port` at line 9 column 5, did you mean `port`?"
+    };
+    assert_eq!(expected, errors.to_string());
+    assert_eq!(3, errors.len());
+
+    // The container whose port is replaced by a placeholder deserializes, so
+    // deserialization goes on to the missing field in the next one.
+    let yaml = indoc! {"
+        name: web
+        replicas: 1
+        containers:
+          - image: app
+            port: x
+          - port: 80
+    "};
+    let errors = serde_yaml::from_str_recovering::<Deployment>(yaml).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    let expected = [
+        "containers[0].port: invalid type: string \"x\", expected u16 at line 5 column 11",
+        "containers[1]: missing field `image` at line 6 column 5",
+    ];
+    assert_eq!(expected, *messages);
+
+    let yaml = "name: web\nreplicas: 1\ncontainers: []\n";
+    assert!(serde_yaml::from_str_recovering::<Deployment>(yaml).is_ok());
+}
+
+#[test]
+fn test_recovering_single_pass() {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug)]
+    struct Counted;
+
+    impl<'de> serde::Deserialize<'de> for Counted {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            IgnoredAny::deserialize(deserializer)?;
+            Ok(Counted)
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Inner {
+        #[allow(dead_code)]
+        a: u8,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct S {
+        #[allow(dead_code)]
+        counted: Counted,
+        #[allow(dead_code)]
+        x: u8,
+        #[allow(dead_code)]
+        y: Vec<u8>,
+        #[allow(dead_code)]
+        z: u8,
+        #[allow(dead_code)]
+        inner: Inner,
+    }
+
+    // The placeholder for `inner` lacks its field, which is not reported
+    // again.
+    let yaml = indoc! {"
+        counted: 1
+        x: a
+        y: b
+        z: 256
+        inner: 1
+    "};
+    let errors = serde_yaml::from_str_recovering::<S>(yaml).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    let expected = [
+        "x: invalid type: string \"a\", expected u8 at line 2 column 4",
+        "y: invalid type: string \"b\", expected a sequence at line 3 column 4",
+        "z: invalid value: integer `256`, expected u8 at line 4 column 4",
+        "inner: invalid type: integer `1`, expected struct Inner at line 5 column 8",
+    ];
+    assert_eq!(expected, *messages);
+    assert_eq!(1, CALLS.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_recovering_stops_at_syntax_error() {
+    let yaml = indoc! {"
+        a: x
+        b: [1, 2
+    "};
+    // The errors before the syntax error are reported along with it.
+    let errors = serde_yaml::from_str_recovering::<BTreeMap<String, u8>>(yaml).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    let expected = [
+        "a: invalid type: string \"x\", expected u8 at line 1 column 4",
+        "did not find expected ',' or ']' at line 3 column 1, while parsing a flow sequence at line 2 column 4",
+    ];
+    assert_eq!(expected, *messages);
+}
+
+#[test]
+fn test_recovering_document_index() {
+    let yaml = indoc! {"
+        ---
+        [1, 2]
+        ---
+        [x, 3, y]
+    "};
+    let mut de = Deserializer::from_str(yaml);
+    let first_doc = de.next().unwrap();
+    assert_eq!(
+        vec![1, 2],
+        first_doc.deserialize_recovering::<Vec<u8>>().unwrap()
+    );
+
+    let second_doc = de.next().unwrap();
+    let errors = second_doc.deserialize_recovering::<Vec<u8>>().unwrap_err();
+    assert_eq!(2, errors.len());
+    for err in &errors {
+        assert_eq!(Some(1), err.document_index());
+    }
+    let paths: Vec<_> = errors
+        .iter()
+        .map(|err| err.path().unwrap().to_string())
+        .collect();
+    assert_eq!([".[0]", ".[2]"], *paths);
+}
+
+#[test]
+fn test_display_with_source() {
+    fn test<T: serde::de::DeserializeOwned + Debug>(yaml: &str, expected: &str) {
+        let err = serde_yaml::from_str::<T>(yaml).unwrap_err();
+        assert_eq!(expected, err.display_with_source(yaml).to_string());
+    }
+
+    // Lines between the positions are shown for context, up to a point.
+    let yaml = indoc! {"
+        x: 0
+        a: 1
+        b: 2
+        a: 3
+    "};
+    let expected = indoc! {"
+        a: duplicate key `a` at line 4 column 1, first defined at line 2 column 1
+          |
+        2 | a: 1
+          | ^
+        3 | b: 2
+        4 | a: 3
+          | ^"
+    };
+    test::<Value>(yaml, expected);
+
+    let yaml = "x: 0\na: 1\nb: 2\nc: 3\nd: 4\ne: 5\nf: 6\na: 7\n";
+    let expected = indoc! {"
+        a: duplicate key `a` at line 8 column 1, first defined at line 2 column 1
+          |
+        2 | a: 1
+          | ^
+        ...
+        8 | a: 7
+          | ^"
+    };
+    test::<Value>(yaml, expected);
+
+    // Tabs and wide characters before the position push the caret over.
+    let yaml = "\"\t\u{4f60}\u{597d}\": x\n";
+    let expected = indoc! {"
+        \t\u{4f60}\u{597d}: invalid type: string \"x\", expected u8 at line 1 column 8
+          |
+        1 | \"    \u{4f60}\u{597d}\": x
+          |             ^"
+    };
+    test::<BTreeMap<String, u8>>(yaml, expected);
+
+    // Syntax errors point at the construct being parsed too.
+    let yaml = "a: [1, 2\n";
+    let expected = indoc! {"
+        did not find expected ',' or ']' at line 2 column 1, while parsing a flow sequence at line 1 column 4
+          |
+        1 | a: [1, 2
+          |    ^
+        2 |
+          | ^"
+    };
+    test::<Value>(yaml, expected);
+
+    let yaml = "---\n1\n---\n2\n";
+    let expected = "deserializing from YAML containing more than one document is not supported";
+    test::<u8>(yaml, expected);
+}
+
+#[test]
+fn test_unknown_name_suggestion() {
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Server {
+        #[allow(dead_code)]
+        host: String,
+        #[allow(dead_code)]
+        protocol: Protocol,
+    }
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    pub enum Protocol {
+        Http,
+        Https { port: u16 },
+        Unix(String),
+    }
+
+    let yaml = indoc! {"
+        host: example.com
+        \"protocl\": Http
+    "};
+    let err = serde_yaml::from_str::<Server>(yaml).unwrap_err();
+    let expected = "unknown field `protocl`, expected `host` or `protocol` at line 2 column 1, did you mean `protocol`?";
+    assert_eq!(expected, err.to_string());
+    let suggestion = err.suggestion().unwrap();
+    assert_eq!("protocol", suggestion.name());
+    assert_eq!("\"protocl\"", &yaml[suggestion.span()]);
+    assert_eq!(2, suggestion.end().line());
+    assert_eq!(10, suggestion.end().column());
+
+    let spanned: serde_yaml::SpannedValue = serde_yaml::from_str(yaml).unwrap();
+    let err = serde_yaml::from_spanned_value::<Server>(spanned).unwrap_err();
+    assert_eq!(expected, err.to_string());
+    assert_eq!(Some(suggestion), err.suggestion());
+
+    let yaml = indoc! {"
+        host: example.com
+        protocol:
+          HTTPS: {port: 443}
+    "};
+    let err = serde_yaml::from_str::<Server>(yaml).unwrap_err();
+    let expected = "protocol: unknown variant `HTTPS`, expected one of `Http`, `Https`, `Unix` at line 3 column 3, did you mean `Https`?";
+    assert_eq!(expected, err.to_string());
+    let suggestion = err.suggestion().unwrap();
+    assert_eq!("HTTPS", &yaml[suggestion.span()]);
+
+    let cases = [
+        ("protocol: Htp", "Htp", "Http", 11),
+        ("protocol: !Unx /run/socket", "Unx", "Unix", 12),
+        ("protocol: !Htps {port: 443}", "Htps", "Https", 12),
+        ("protocol: !<!Unx> /run/socket", "Unx", "Unix", 14),
+        ("protocol: &p !Htps {port: 443}", "Htps", "Https", 15),
+    ];
+    for (yaml, unknown, name, column) in cases {
+        let yaml = format!("host: example.com\n{}\n", yaml);
+        let err = serde_yaml::from_str::<Server>(&yaml).unwrap_err();
+        let suggestion = err.suggestion().unwrap();
+        assert_eq!(name, suggestion.name());
+        assert_eq!(unknown, &yaml[suggestion.span()]);
+        assert_eq!(2, suggestion.start().line());
+        assert_eq!(column, suggestion.start().column());
+        assert_eq!(Some(suggestion.start()), err.location());
+        assert!(err.to_string().starts_with("protocol: unknown variant"));
+    }
+
+    let cases = [
+        ("!", "!e!Unx /run/socket", "Unx", 14),
+        ("!U", "!e!nx /run/socket", "!e!nx /run/socket", 11),
+    ];
+    for (prefix, tagged, unknown, column) in cases {
+        let yaml = format!(
+            "%TAG !e! {}\n---\nhost: example.com\nprotocol: {}\n",
+            prefix, tagged,
+        );
+        let err = serde_yaml::from_str::<Server>(&yaml).unwrap_err();
+        let suggestion = err.suggestion().unwrap();
+        assert_eq!("Unix", suggestion.name());
+        assert_eq!(unknown, &yaml[suggestion.span()]);
+        assert_eq!(4, suggestion.start().line());
+        assert_eq!(column, suggestion.start().column());
+    }
+
+    let err = serde_yaml::from_str::<Server>("host: x\nprotocol: Ftp\n").unwrap_err();
+    assert_eq!(None, err.suggestion());
+    assert!(!err.to_string().contains("did you mean"));
+}
+
+#[test]
+fn test_error_path_segments() {
+    let yaml = indoc! {"
+        hosts:
+          example.com:
+            ports: [80, x]
+    "};
+    let err =
+        serde_yaml::from_str::<BTreeMap<String, BTreeMap<String, BTreeMap<String, Vec<u16>>>>>(
+            yaml,
+        )
+        .unwrap_err();
+    let path = err.path().unwrap();
+    let expected = [
+        Segment::Key("hosts".to_owned()),
+        Segment::Key("example.com".to_owned()),
+        Segment::Key("ports".to_owned()),
+        Segment::Index(1),
+    ];
+    assert_eq!(expected, path.segments());
+    assert_eq!(r#"hosts."example.com".ports[1]"#, path.to_string());
+    assert_eq!(
+        "/hosts/example.com/ports/1",
+        path.to_json_pointer().unwrap()
+    );
+}
+
+#[test]
+fn test_error_path_non_string_keys() {
+    #[derive(Deserialize, Debug)]
+    pub struct Response {
+        #[allow(dead_code)]
+        body: String,
+    }
+    let yaml = indoc! {"
+        responses:
+          200: {body: ok}
+          404: {body: [missing]}
+    "};
+    let err = serde_yaml::from_str::<BTreeMap<String, BTreeMap<u16, Response>>>(yaml).unwrap_err();
+    assert_eq!("responses.404.body", err.path().unwrap().to_string());
+
+    let yaml = indoc! {"
+        merged:
+          <<: {true: {body: [yes]}}
+    "};
+    let err = serde_yaml::from_str::<BTreeMap<String, BTreeMap<bool, Response>>>(yaml).unwrap_err();
+    assert_eq!("merged.true.body", err.path().unwrap().to_string());
+
+    let yaml = indoc! {"
+        - &port 8080: {body: ok}
+        - *port : {body: [again]}
+    "};
+    let err = serde_yaml::from_str::<Vec<BTreeMap<u16, Response>>>(yaml).unwrap_err();
+    assert_eq!(".[1].*port.body", err.path().unwrap().to_string());
+
+    let yaml = indoc! {"
+        ? [0, {x: 1.5}]
+        : {body: [origin]}
+    "};
+    let err = serde_yaml::from_str::<HashMap<Value, Response>>(yaml).unwrap_err();
+    let path = err.path().unwrap();
+    assert_eq!(
+        [
+            Segment::Key("[0, {x: 1.5}]".to_owned()),
+            Segment::Key("body".to_owned()),
+        ],
+        path.segments(),
+    );
+    assert_eq!(r#""[0, {x: 1.5}]".body"#, path.to_string());
+
+    let yaml = format!("? [{}]\n: {{body: [long]}}\n", "word, ".repeat(30));
+    let err = serde_yaml::from_str::<HashMap<Value, Response>>(&yaml).unwrap_err();
+    let key = format!("[{}...", &"word, ".repeat(30)[..99]);
+    assert_eq!(
+        [Segment::Key(key), Segment::Key("body".to_owned())],
+        err.path().unwrap().segments(),
+    );
+}
+
+#[test]
+fn test_path_parse() {
+    let paths = [
+        ".",
+        "a",
+        ".[0]",
+        ".[0][1].a",
+        "a.b[2].c",
+        r#""".?."a.b"."[\"\\]""#,
+        r#"a."?".b"#,
+    ];
+    for path in paths {
+        let parsed: PathBuf = path.parse().unwrap();
+        assert_eq!(path, parsed.to_string());
+    }
+
+    let path: PathBuf = "?.a".parse().unwrap();
+    assert_eq!(
+        [Segment::Unknown, Segment::Key("a".to_owned())],
+        path.segments(),
+    );
+    assert_eq!(None, path.to_json_pointer());
+
+    let path: PathBuf = r#"a/b."~""#.parse().unwrap();
+    assert_eq!("/a~1b/~0", path.to_json_pointer().unwrap());
+
+    for invalid in ["", "a.", "a..b", "a[x]", "a[1", "\"a", "a]b", "a[0]b"] {
+        let err = invalid.parse::<PathBuf>().unwrap_err();
+        assert!(err.to_string().starts_with("invalid path"), "{}", invalid);
+    }
+}
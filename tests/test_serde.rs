@@ -6,8 +6,9 @@
 )]
 
 use indoc::indoc;
+use serde::Serialize as _;
 use serde_derive::{Deserialize, Serialize};
-use serde_yaml::Value;
+use serde_yaml::{Schema, Serializer, Value};
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 
@@ -238,6 +239,85 @@ fn test_strings_needing_quote() {
     test_serde(&thing, yaml);
 }
 
+#[test]
+fn test_strings_needing_quote_by_schema() {
+    fn to_string(thing: &[&str], schema: Schema) -> String {
+        let mut buffer = Vec::new();
+        let mut ser = Serializer::new(&mut buffer).with_schema(schema);
+        thing.serialize(&mut ser).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    let thing = [
+        "True",
+        "NULL",
+        "yes",
+        "off",
+        "0777",
+        "1_000",
+        "1:30",
+        "1e3",
+        "2001-12-14",
+        "x",
+    ];
+
+    let yaml = indoc! {"
+        - True
+        - NULL
+        - yes
+        - off
+        - 0777
+        - 1_000
+        - 1:30
+        - 1e3
+        - 2001-12-14
+        - x
+    "};
+    assert_eq!(yaml, to_string(&thing, Schema::Failsafe));
+
+    let yaml = indoc! {"
+        - True
+        - NULL
+        - yes
+        - off
+        - 0777
+        - 1_000
+        - 1:30
+        - '1e3'
+        - 2001-12-14
+        - x
+    "};
+    assert_eq!(yaml, to_string(&thing, Schema::Compat));
+
+    let yaml = indoc! {"
+        - 'True'
+        - 'NULL'
+        - yes
+        - off
+        - 0777
+        - 1_000
+        - 1:30
+        - '1e3'
+        - 2001-12-14
+        - x
+    "};
+    assert_eq!(yaml, to_string(&thing, Schema::Core));
+
+    let yaml = indoc! {"
+        - 'True'
+        - 'NULL'
+        - 'yes'
+        - 'off'
+        - '0777'
+        - '1_000'
+        - '1:30'
+        - 1e3
+        - '2001-12-14'
+        - x
+    "};
+    assert_eq!(yaml, to_string(&thing, Schema::Yaml11));
+}
+
 #[test]
 fn test_nested_vec() {
     let thing = vec![vec![1, 2, 3], vec![4, 5, 6]];
@@ -416,6 +496,179 @@ fn test_value() {
     test_serde(&thing, yaml);
 }
 
+#[test]
+fn test_tagged() {
+    use serde_yaml::value::{Tag, TaggedValue};
+
+    let mut thing = BTreeMap::new();
+    thing.insert(
+        "Arn".to_owned(),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("GetAtt"),
+            value: Value::Sequence(vec!["Bucket".into(), "Arn".into()]),
+        })),
+    );
+    thing.insert(
+        "Name".to_owned(),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("!Sub"),
+            value: Value::String("${AWS::StackName}-bucket".to_owned()),
+        })),
+    );
+    thing.insert(
+        "Port".to_owned(),
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("Ref"),
+            value: Value::Number(8080.into()),
+        })),
+    );
+    let yaml = indoc! {"
+        Arn: !GetAtt
+        - Bucket
+        - Arn
+        Name: !Sub ${AWS::StackName}-bucket
+        Port: !Ref 8080
+    "};
+    test_serde(&thing, yaml);
+}
+
+#[test]
+fn test_binary() {
+    #[derive(PartialEq, Debug)]
+    struct Bytes(Vec<u8>);
+
+    impl serde::Serialize for Bytes {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Bytes {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        empty: Bytes,
+        short: Bytes,
+        long: Bytes,
+    }
+    let thing = Data {
+        empty: Bytes(Vec::new()),
+        short: Bytes(b"hello".to_vec()),
+        long: Bytes((0..=60).collect()),
+    };
+    let yaml = indoc! {"
+        empty: !!binary \"\"
+        short: !!binary |
+          aGVsbG8=
+        long: !!binary |
+          AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4
+          OTo7PA==
+    "};
+    test_serde(&thing, yaml);
+}
+
+#[test]
+fn test_timestamp() {
+    use serde_yaml::Timestamp;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Schedule {
+        date: Timestamp,
+        start: Timestamp,
+        times: Vec<Timestamp>,
+    }
+    let thing = Schedule {
+        date: "2002-12-14".parse().unwrap(),
+        start: "2001-12-14t21:59:43.10-05:00".parse().unwrap(),
+        times: vec![
+            "2001-12-15 2:59:43.1Z".parse().unwrap(),
+            "2001-12-14 21:59:43.000000001".parse().unwrap(),
+        ],
+    };
+    let yaml = indoc! {"
+        date: !!timestamp 2002-12-14
+        start: !!timestamp 2001-12-14T21:59:43.1-05:00
+        times:
+        - !!timestamp 2001-12-15T02:59:43.1Z
+        - !!timestamp 2001-12-14T21:59:43.000000001
+    "};
+    test_serde(&thing, yaml);
+
+    let mut buffer = Vec::new();
+    let mut ser = Serializer::new(&mut buffer).with_schema(Schema::Yaml11);
+    thing.serialize(&mut ser).unwrap();
+    let yaml = indoc! {"
+        date: 2002-12-14
+        start: 2001-12-14T21:59:43.1-05:00
+        times:
+        - 2001-12-15T02:59:43.1Z
+        - 2001-12-14T21:59:43.000000001
+    "};
+    assert_eq!(yaml, String::from_utf8(buffer).unwrap());
+}
+
+#[test]
+fn test_collection_tags() {
+    use std::collections::BTreeSet;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        #[serde(with = "serde_yaml::with::set")]
+        set: BTreeSet<u8>,
+        #[serde(with = "serde_yaml::with::omap")]
+        omap: Vec<(String, u8)>,
+        #[serde(with = "serde_yaml::with::pairs")]
+        pairs: Vec<(String, u8)>,
+        plain: Vec<(String, u8)>,
+    }
+
+    let thing = Data {
+        set: vec![1, 2].into_iter().collect(),
+        omap: vec![("b".to_owned(), 1), ("a".to_owned(), 2)],
+        pairs: vec![("x".to_owned(), 1), ("x".to_owned(), 2)],
+        plain: vec![("x".to_owned(), 1)],
+    };
+    let yaml = indoc! {"
+        set: !!set
+          1: null
+          2: null
+        omap: !!omap
+        - b: 1
+        - a: 2
+        pairs: !!pairs
+        - x: 1
+        - x: 2
+        plain:
+        - - x
+          - 1
+    "};
+    test_serde(&thing, yaml);
+}
+
 #[test]
 fn test_mapping() {
     use serde_yaml::Mapping;
@@ -1,9 +1,11 @@
 #![allow(clippy::derive_partial_eq_without_eq, clippy::eq_op)]
 
+use indoc::indoc;
 use serde::de::IntoDeserializer;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_derive::Deserialize;
-use serde_yaml::{Number, Value};
+use serde_yaml::value::{AnchoredValue, SpannedNode, SpannedValue, Tag, TaggedValue};
+use serde_yaml::{Deserializer, DeserializerOptions, Number, Schema, Serializer, Value};
 
 #[test]
 fn test_nan() {
@@ -52,3 +54,436 @@ fn test_into_deserializer() {
         }
     );
 }
+
+#[test]
+fn test_apply_merge() {
+    let mut value: Value = serde_yaml::from_str(indoc! {"
+        a: 1
+        b: 2
+    "})
+    .unwrap();
+    let mut with_merge = serde_yaml::Mapping::new();
+    with_merge.insert("<<".into(), value.clone());
+    with_merge.insert("b".into(), 20.into());
+    with_merge.insert("c".into(), 30.into());
+    value = Value::Sequence(vec![Value::Mapping(with_merge)]);
+
+    value.apply_merge().unwrap();
+    let expected: Value = serde_yaml::from_str(indoc! {"
+        - b: 20
+          c: 30
+          a: 1
+    "})
+    .unwrap();
+    assert_eq!(value, expected);
+
+    let mut bad = serde_yaml::Mapping::new();
+    bad.insert("<<".into(), Value::Sequence(vec!["a".into()]));
+    let error = Value::Mapping(bad).apply_merge().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "<<[0]: invalid type: string \"a\", expected a mapping for merging",
+    );
+    assert_eq!(None, error.location());
+
+    let mut bad = serde_yaml::Mapping::new();
+    bad.insert("<<".into(), 1.into());
+    let mut source = serde_yaml::Mapping::new();
+    source.insert("<<".into(), Value::Mapping(bad));
+    let mut services = serde_yaml::Mapping::new();
+    services.insert("web".into(), Value::Mapping(source));
+    let mut value = Value::Sequence(vec![Value::Null, Value::Mapping(services)]);
+    let error = value.apply_merge().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        ".[1].web.<<.<<: invalid type: integer `1`, expected a mapping or list of mappings for merging",
+    );
+    assert_eq!(".[1].web.<<.<<", error.path().unwrap().to_string());
+}
+
+#[test]
+fn test_tagged() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Intrinsic {
+        Ref(String),
+        GetAtt(Vec<String>),
+    }
+
+    let value: Value = serde_yaml::from_str(indoc! {"
+        - !Ref Bucket
+        - !GetAtt [Bucket, Arn]
+        - !Custom 1
+        - !Custom '1'
+    "})
+    .unwrap();
+
+    let expected = Value::Tagged(Box::new(TaggedValue {
+        tag: Tag::new("Ref"),
+        value: Value::String("Bucket".to_owned()),
+    }));
+    assert_eq!(value[0], expected);
+    match &value[2] {
+        Value::Tagged(tagged) => {
+            assert_eq!(tagged.tag, "!Custom");
+            assert_eq!(tagged.value, 1);
+        }
+        other => panic!("expected a tagged value, got {:?}", other),
+    }
+    match &value[3] {
+        Value::Tagged(tagged) => assert_eq!(tagged.value, "1"),
+        other => panic!("expected a tagged value, got {:?}", other),
+    }
+
+    let intrinsics: Vec<Intrinsic> =
+        serde_yaml::from_value(Value::Sequence(value.as_sequence().unwrap()[..2].to_vec()))
+            .unwrap();
+    assert_eq!(
+        intrinsics,
+        [
+            Intrinsic::Ref("Bucket".to_owned()),
+            Intrinsic::GetAtt(vec!["Bucket".to_owned(), "Arn".to_owned()]),
+        ],
+    );
+
+    // Typed targets other than enums see through the tag.
+    let port: u16 = serde_yaml::from_value(value[2].clone()).unwrap();
+    assert_eq!(port, 1);
+}
+
+#[test]
+fn test_spanned_value() {
+    let yaml = indoc! {"
+        name: example
+        'quoted key': !Ref target
+        items:
+          - 1
+          - [two, 3.0]
+        empty:
+    "};
+    let spanned: SpannedValue = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(&yaml[spanned.span()], yaml.trim_end());
+    let entries = match &spanned.value {
+        SpannedNode::Mapping(entries) => entries,
+        other => panic!("expected a mapping, got {:?}", other),
+    };
+    let keys: Vec<&str> = entries.iter().map(|(k, _)| &yaml[k.span()]).collect();
+    assert_eq!(keys, ["name", "'quoted key'", "items", "empty"]);
+
+    let tagged = spanned.get("quoted key").unwrap();
+    assert_eq!(&yaml[tagged.span()], "!Ref target");
+    assert_eq!(tagged.path, "quoted key");
+    match &tagged.value {
+        SpannedNode::Tagged(tag, value) => {
+            assert_eq!(*tag, "Ref");
+            assert_eq!(value.value, SpannedNode::String("target".to_owned()));
+        }
+        other => panic!("expected a tagged value, got {:?}", other),
+    }
+
+    let items = spanned.get("items").unwrap();
+    assert_eq!(&yaml[items.span()], "- 1\n  - [two, 3.0]");
+    let nested = match &items.value {
+        SpannedNode::Sequence(items) => &items[1],
+        other => panic!("expected a sequence, got {:?}", other),
+    };
+    assert_eq!(nested.path, "items[1]");
+    assert_eq!(nested.start_location.unwrap().line(), 5);
+    assert_eq!(nested.start_location.unwrap().column(), 5);
+
+    let empty = spanned.get("empty").unwrap();
+    assert_eq!(empty.value, SpannedNode::Null);
+    assert!(empty.is_empty());
+
+    let expected: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(Value::from(spanned.clone()), expected);
+    assert_eq!(serde_yaml::to_value(&spanned).unwrap(), expected);
+}
+
+#[test]
+fn test_from_spanned_value() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        ports: Vec<u16>,
+        target: Intrinsic,
+        optional: Option<serde_yaml::Spanned<u16>>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Intrinsic {
+        Ref(String),
+    }
+
+    let yaml = indoc! {"
+        name: example
+        ports: [80, 443]
+        target: !Ref bucket
+        optional: 8080
+    "};
+    let spanned: SpannedValue = serde_yaml::from_str(yaml).unwrap();
+    let config: Config = serde_yaml::from_spanned_value(spanned).unwrap();
+    assert_eq!(config.name, "example");
+    assert_eq!(config.ports, [80, 443]);
+    assert_eq!(config.target, Intrinsic::Ref("bucket".to_owned()));
+    let optional = config.optional.unwrap();
+    assert_eq!(optional.value, 8080);
+    assert_eq!(&yaml[optional.span()], "8080");
+    assert_eq!(optional.path, "optional");
+}
+
+#[test]
+fn test_preserve_anchors() {
+    let yaml = indoc! {"
+        base: &base
+          retries: 3
+          tags: &tags [a, b]
+        service:
+          <<: *base
+          tags: *tags
+          name: &name !Name web
+    "};
+    let options = DeserializerOptions::new().preserve_anchors(true);
+    let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+
+    match &value["base"] {
+        Value::Anchored(anchored) => {
+            assert_eq!(anchored.anchor, "base");
+            assert_eq!(anchored.value["retries"], 3);
+        }
+        other => panic!("expected an anchored value, got {:?}", other),
+    }
+    assert_eq!(value["service"]["<<"], Value::Alias("base".to_owned()));
+    assert_eq!(value["service"]["tags"], Value::Alias("tags".to_owned()));
+    let expected = Value::Anchored(Box::new(AnchoredValue {
+        anchor: "name".to_owned(),
+        value: Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("Name"),
+            value: "web".into(),
+        })),
+    }));
+    assert_eq!(value["service"]["name"], expected);
+
+    let serialized = serde_yaml::to_string(&value).unwrap();
+    let expected = indoc! {"
+        base: &base
+          retries: 3
+          tags: &tags
+          - a
+          - b
+        service:
+          <<: *base
+          tags: *tags
+          name: &name !Name web
+    "};
+    assert_eq!(serialized, expected);
+    assert_eq!(serde_yaml::to_value(&value).unwrap(), value);
+
+    let mut expanded = value;
+    expanded.expand_aliases().unwrap();
+    expanded.apply_merge().unwrap();
+    let service = &expanded["service"];
+    assert_eq!(service["retries"], 3);
+    assert_eq!(
+        service["tags"],
+        Value::Sequence(vec!["a".into(), "b".into()])
+    );
+    assert!(service.get("<<").is_none());
+}
+
+#[test]
+fn test_unresolved_alias() {
+    let value = Value::Alias("missing".to_owned());
+    let error = serde_yaml::from_value::<u8>(value.clone()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "cannot deserialize the alias `*missing`, expand aliases with Value::expand_aliases first",
+    );
+    let error = value.clone().expand_aliases().unwrap_err();
+    assert_eq!(error.to_string(), "unknown anchor `missing`");
+}
+
+#[test]
+fn test_expand_aliases_limit() {
+    let yaml = indoc! {"
+        a: &a [lol,lol,lol,lol,lol,lol,lol,lol,lol]
+        b: &b [*a,*a,*a,*a,*a,*a,*a,*a,*a]
+        c: &c [*b,*b,*b,*b,*b,*b,*b,*b,*b]
+        d: &d [*c,*c,*c,*c,*c,*c,*c,*c,*c]
+        e: &e [*d,*d,*d,*d,*d,*d,*d,*d,*d]
+        f: &f [*e,*e,*e,*e,*e,*e,*e,*e,*e]
+        g: &g [*f,*f,*f,*f,*f,*f,*f,*f,*f]
+        h: &h [*g,*g,*g,*g,*g,*g,*g,*g,*g]
+        i: &i [*h,*h,*h,*h,*h,*h,*h,*h,*h]
+    "};
+    let options = DeserializerOptions::new().preserve_anchors(true);
+    let value = Value::deserialize(Deserializer::from_str(yaml).with_options(options)).unwrap();
+    let error = value.clone().expand_aliases().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "alias expansion limit exceeded at alias `d`"
+    );
+    let error = value
+        .clone()
+        .expand_aliases_with_limit(1_000_000)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "alias expansion limit exceeded at alias `f`"
+    );
+}
+
+#[test]
+fn test_collection_tags() {
+    let yaml = indoc! {"
+        set: !!set {a, b}
+        omap: !!omap [x: 1, y: 2]
+        pairs: !!pairs [x: 1, x: 2]
+        map: {a: null}
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    for (key, tag) in [("set", "!!set"), ("omap", "!!omap"), ("pairs", "!!pairs")] {
+        match &value[key] {
+            Value::Tagged(tagged) => {
+                assert_eq!(tagged.tag, Tag::new(tag));
+                assert_eq!(tagged.tag.to_string(), tag);
+                assert_ne!(tagged.tag, Tag::new(&tag[1..]));
+            }
+            other => panic!("expected tagged value, got {:?}", other),
+        }
+    }
+    assert!(value["map"].is_mapping());
+
+    let set: Vec<String> = serde_yaml::from_value(value["set"].clone()).unwrap();
+    assert_eq!(set, ["a", "b"]);
+    let pairs: Vec<(String, u8)> = serde_yaml::from_value(value["pairs"].clone()).unwrap();
+    assert_eq!(pairs, [("x".to_owned(), 1), ("x".to_owned(), 2)]);
+    let untagged = match value["pairs"].clone() {
+        Value::Tagged(tagged) => tagged.value,
+        other => other,
+    };
+    let err = serde_yaml::from_value::<Vec<(String, u8)>>(untagged).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: map, expected a tuple of size 2"
+    );
+
+    let expected = indoc! {"
+        set: !!set
+          a: null
+          b: null
+        omap: !!omap
+        - x: 1
+        - y: 2
+        pairs: !!pairs
+        - x: 1
+        - x: 2
+        map:
+          a: null
+    "};
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), expected);
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[test]
+fn test_arbitrary_precision() {
+    let yaml = indoc! {"
+        big: 340282366920938463463374607431768211455
+        negative: -170141183460469231731687303715884105728
+        huge: 123456789012345678901234567890123456789012
+        precise: 0.1000000000000000055511151231257827
+        plain: 0.1
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+
+    assert_eq!(value["big"], Value::from(u128::MAX));
+    assert_eq!(value["negative"], Value::from(i128::MIN));
+    assert_eq!(value["big"].as_u64(), None);
+    let big: u128 = serde_yaml::from_value(value["big"].clone()).unwrap();
+    assert_eq!(big, u128::MAX);
+    let negative: i128 = serde_yaml::from_value(value["negative"].clone()).unwrap();
+    assert_eq!(negative, i128::MIN);
+
+    assert_eq!(value["plain"].as_f64(), Some(0.1));
+    assert_eq!(value["precise"].as_f64(), Some(0.1));
+    assert_ne!(value["precise"], value["plain"]);
+    assert!(value["precise"].is_f64());
+    let precise: f64 = serde_yaml::from_value(value["precise"].clone()).unwrap();
+    assert_eq!(precise, 0.1);
+
+    let err = serde_yaml::from_value::<u64>(value["big"].clone()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `340282366920938463463374607431768211455` as u128, expected u64",
+    );
+
+    let number: serde_yaml::Number = serde_yaml::from_str("-0.30000000000000000001").unwrap();
+    assert_eq!(number.to_string(), "-0.30000000000000000001");
+    assert_eq!(
+        serde_yaml::to_value(&number).unwrap(),
+        Value::Number(number)
+    );
+}
+
+// Untagged enums buffer the private map that carries an arbitrary-precision
+// number, which none of the primitive variants accept.
+#[cfg(feature = "arbitrary_precision")]
+#[test]
+fn test_arbitrary_precision_untagged() {
+    #[derive(Deserialize, Debug)]
+    #[serde(untagged)]
+    enum V {
+        F(#[allow(dead_code)] f64),
+        S(#[allow(dead_code)] String),
+    }
+
+    let precise = "0.1000000000000000055511151231257827";
+    assert_eq!(serde_yaml::from_str::<f64>(precise).unwrap(), 0.1);
+    assert!(matches!(serde_yaml::from_str::<V>("0.1").unwrap(), V::F(_)));
+    let err = serde_yaml::from_str::<V>(precise).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "data did not match any variant of untagged enum V",
+    );
+
+    #[derive(Deserialize, Debug)]
+    #[serde(untagged)]
+    enum N {
+        Number(serde_yaml::Number),
+    }
+    let N::Number(n) = serde_yaml::from_str::<N>(precise).unwrap();
+    assert_eq!(n.to_string(), precise);
+}
+
+#[test]
+fn test_number_repr() {
+    let yaml = indoc! {"
+        hex: 0x1F
+        octal: 0o755
+        exponent: 1e3
+        plus: +12
+        canonical: 1.5
+        infinity: .Inf
+    "};
+    let value: Value = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(serde_yaml::to_string(&value).unwrap(), yaml);
+
+    assert_eq!(value["hex"], Value::from(31));
+    assert_eq!(value["octal"], 493);
+    assert_eq!(value["exponent"], 1000.0);
+    assert_eq!(value["plus"], serde_yaml::from_str::<Value>("12").unwrap());
+
+    let copy = serde_yaml::to_value(&value).unwrap();
+    assert_eq!(serde_yaml::to_string(&copy).unwrap(), yaml);
+    let number: Number = serde_yaml::from_value(value["hex"].clone()).unwrap();
+    assert_eq!(serde_yaml::to_string(&number).unwrap(), "0x1F\n");
+    let int: u32 = serde_yaml::from_value(value["hex"].clone()).unwrap();
+    assert_eq!(serde_yaml::to_string(&int).unwrap(), "31\n");
+
+    // The YAML 1.1 schema reads `0o755` as a string, so it is not reused.
+    let mut buffer = Vec::new();
+    let mut ser = Serializer::new(&mut buffer).with_schema(Schema::Yaml11);
+    value["octal"].serialize(&mut ser).unwrap();
+    assert_eq!(String::from_utf8(buffer).unwrap(), "493\n");
+}